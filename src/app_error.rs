@@ -8,20 +8,28 @@ pub enum AppError {
     Auth {
         status: u16,
         message: String,
+        /// The raw response body when `message` was extracted from it.
+        detail: Option<String>,
     },
     RateLimited {
         message: String,
         retry_after_ms: Option<u64>,
+        detail: Option<String>,
     },
     Upstream {
         status: Option<u16>,
         message: String,
+        /// `timeout`, `dns`, `connect`, `body` or `other` when no response arrived.
+        transport_kind: Option<&'static str>,
     },
     Api {
         status: u16,
         message: String,
+        detail: Option<String>,
     },
     Internal(String),
+    /// The run succeeded but emitted warnings while `--fail-on-warnings` was set.
+    WarningsPresent(Vec<String>),
 }
 
 impl AppError {
@@ -33,10 +41,49 @@ impl AppError {
             Self::Upstream { .. } => 5,
             Self::Api { .. } => 6,
             Self::Internal(_) => 1,
+            Self::WarningsPresent(_) => 7,
         }
     }
 
-    pub fn to_json(&self) -> Value {
+    /// Renders the error envelope. Schema version 1 is the original minimal shape;
+    /// version 2 adds `schema_version` and an `errors` list of messages.
+    pub fn to_json(&self, schema_version: u8) -> Value {
+        let mut value = self.to_json_v1();
+        if let Some(detail) = self.detail() {
+            value["error"]["detail"] = json!(detail);
+        }
+        if schema_version >= 2 {
+            let error = &mut value["error"];
+            error["schema_version"] = json!(schema_version);
+            error["errors"] = json!([self.message()]);
+        }
+        value
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::WarningsPresent(warnings) => format!(
+                "{} warning(s) emitted with --fail-on-warnings set.",
+                warnings.len()
+            ),
+            Self::Usage(message) | Self::Internal(message) => message.clone(),
+            Self::Auth { message, .. }
+            | Self::RateLimited { message, .. }
+            | Self::Upstream { message, .. }
+            | Self::Api { message, .. } => message.clone(),
+        }
+    }
+
+    fn detail(&self) -> Option<&str> {
+        match self {
+            Self::Auth { detail, .. }
+            | Self::RateLimited { detail, .. }
+            | Self::Api { detail, .. } => detail.as_deref(),
+            _ => None,
+        }
+    }
+
+    fn to_json_v1(&self) -> Value {
         match self {
             Self::Usage(message) => json!({
                 "error": {
@@ -46,7 +93,9 @@ impl AppError {
                     "message": message,
                 }
             }),
-            Self::Auth { status, message } => json!({
+            Self::Auth {
+                status, message, ..
+            } => json!({
                 "error": {
                     "category": "auth",
                     "exit_code": self.exit_code(),
@@ -58,6 +107,7 @@ impl AppError {
             Self::RateLimited {
                 message,
                 retry_after_ms,
+                ..
             } => json!({
                 "error": {
                     "category": "rate_limit",
@@ -68,16 +118,28 @@ impl AppError {
                     "message": message,
                 }
             }),
-            Self::Upstream { status, message } => json!({
-                "error": {
-                    "category": "upstream",
-                    "exit_code": self.exit_code(),
-                    "status": status,
-                    "retryable": true,
-                    "message": message,
+            Self::Upstream {
+                status,
+                message,
+                transport_kind,
+            } => {
+                let mut value = json!({
+                    "error": {
+                        "category": "upstream",
+                        "exit_code": self.exit_code(),
+                        "status": status,
+                        "retryable": true,
+                        "message": message,
+                    }
+                });
+                if let Some(kind) = transport_kind {
+                    value["error"]["transport_kind"] = json!(kind);
                 }
-            }),
-            Self::Api { status, message } => json!({
+                value
+            }
+            Self::Api {
+                status, message, ..
+            } => json!({
                 "error": {
                     "category": "api",
                     "exit_code": self.exit_code(),
@@ -94,6 +156,15 @@ impl AppError {
                     "message": message,
                 }
             }),
+            Self::WarningsPresent(warnings) => json!({
+                "error": {
+                    "category": "warnings_present",
+                    "exit_code": self.exit_code(),
+                    "retryable": false,
+                    "warnings": warnings,
+                    "message": self.message(),
+                }
+            }),
         }
     }
 }
@@ -102,22 +173,164 @@ impl From<DatadogError> for AppError {
     fn from(value: DatadogError) -> Self {
         match value {
             DatadogError::InvalidRequest(message) => Self::Usage(message),
-            DatadogError::Auth { status, body } => Self::Auth {
-                status,
-                message: body,
-            },
+            DatadogError::Auth { status, body } => {
+                let (message, detail) = readable_body(body);
+                Self::Auth {
+                    status,
+                    message,
+                    detail,
+                }
+            }
             DatadogError::RateLimited {
                 body,
                 retry_after_ms,
-            } => Self::RateLimited {
-                message: body,
-                retry_after_ms,
-            },
-            DatadogError::Retryable { status, message } => Self::Upstream { status, message },
-            DatadogError::Api { status, body } => Self::Api {
+            } => {
+                let (message, detail) = readable_body(body);
+                Self::RateLimited {
+                    message,
+                    retry_after_ms,
+                    detail,
+                }
+            }
+            DatadogError::Retryable {
                 status,
-                message: body,
+                message,
+                transport_kind,
+            } => Self::Upstream {
+                status,
+                message,
+                transport_kind,
             },
+            DatadogError::Api { status, body } => {
+                let (message, detail) = readable_body(body);
+                Self::Api {
+                    status,
+                    message,
+                    detail,
+                }
+            }
+            // The completed chunks are output by the raw command; the exit status
+            // comes from the chunk that failed.
+            DatadogError::ChunkFailed { source, .. } => Self::from(*source),
         }
     }
 }
+
+/// Turns a Datadog error body into a message. A JSON body with an `errors` list
+/// (plain strings, or JSON:API objects with `detail`/`title`) becomes those
+/// messages joined by "; ", and the raw body is kept as detail. Any other body is
+/// the message as-is.
+fn readable_body(body: String) -> (String, Option<String>) {
+    let messages: Vec<String> = serde_json::from_str::<Value>(&body)
+        .ok()
+        .and_then(|value| value.get("errors")?.as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|error| match error {
+            Value::String(message) => Some(message.clone()),
+            Value::Object(error) => error
+                .get("detail")
+                .or_else(|| error.get("title"))
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            _ => None,
+        })
+        .collect();
+    if messages.is_empty() {
+        (body, None)
+    } else {
+        (messages.join("; "), Some(body))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::AppError;
+    use crate::datadog::DatadogError;
+
+    #[test]
+    fn to_json_renders_both_schema_versions() {
+        let err = AppError::Api {
+            status: 400,
+            message: "bad query".to_string(),
+            detail: None,
+        };
+
+        assert_eq!(
+            err.to_json(1),
+            json!({
+                "error": {
+                    "category": "api",
+                    "exit_code": 6,
+                    "status": 400,
+                    "retryable": false,
+                    "message": "bad query",
+                }
+            })
+        );
+        assert_eq!(
+            err.to_json(2),
+            json!({
+                "error": {
+                    "category": "api",
+                    "exit_code": 6,
+                    "status": 400,
+                    "retryable": false,
+                    "message": "bad query",
+                    "schema_version": 2,
+                    "errors": ["bad query"],
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn datadog_errors_list_becomes_the_message() {
+        let body = r#"{"errors":["Invalid query","Unknown metric"]}"#;
+        let err = AppError::from(DatadogError::Api {
+            status: 400,
+            body: body.to_string(),
+        });
+
+        let json = err.to_json(1);
+        assert_eq!(json["error"]["message"], "Invalid query; Unknown metric");
+        assert_eq!(json["error"]["detail"], body);
+
+        let err = AppError::from(DatadogError::Auth {
+            status: 403,
+            body: r#"{"errors":[{"status":"403","title":"Forbidden"}]}"#.to_string(),
+        });
+        assert_eq!(err.to_json(1)["error"]["message"], "Forbidden");
+    }
+
+    #[test]
+    fn non_json_error_bodies_pass_through() {
+        let err = AppError::from(DatadogError::Api {
+            status: 502,
+            body: "<html>Bad Gateway</html>".to_string(),
+        });
+
+        let json = err.to_json(1);
+        assert_eq!(json["error"]["message"], "<html>Bad Gateway</html>");
+        assert!(json["error"].get("detail").is_none());
+    }
+
+    #[test]
+    fn upstream_errors_carry_the_transport_kind_when_known() {
+        let err = AppError::from(DatadogError::Retryable {
+            status: None,
+            message: "connection refused".to_string(),
+            transport_kind: Some("connect"),
+        });
+        assert_eq!(err.to_json(1)["error"]["transport_kind"], "connect");
+
+        let err = AppError::from(DatadogError::Retryable {
+            status: Some(503),
+            message: "unavailable".to_string(),
+            transport_kind: None,
+        });
+        assert!(err.to_json(1)["error"].get("transport_kind").is_none());
+    }
+}
@@ -0,0 +1,195 @@
+use serde_json::{Value, json};
+
+use crate::app_error::AppError;
+
+/// Combines the per-item results of a batch command into one JSON array.
+///
+/// Without `collect_errors` the first failure aborts the batch. With it, each
+/// element becomes `{"ok": <result>}` or an error envelope, and the first error is
+/// returned alongside the output only when no item succeeded, so the process still
+/// exits non-zero for an entirely failed batch. With `fail_fast_auth`, an auth
+/// error aborts the batch even when collecting errors.
+pub fn collect(
+    mut results: Vec<Result<Value, AppError>>,
+    collect_errors: bool,
+    fail_fast_auth: bool,
+    error_schema_version: u8,
+) -> Result<(Value, Option<AppError>), AppError> {
+    if fail_fast_auth
+        && let Some(index) = results.iter().position(is_auth_error)
+        && let Err(err) = results.swap_remove(index)
+    {
+        return Err(err);
+    }
+    if !collect_errors {
+        let values = results.into_iter().collect::<Result<Vec<_>, _>>()?;
+        return Ok((Value::Array(values), None));
+    }
+
+    let any_succeeded = results.iter().any(Result::is_ok);
+    let mut first_error = None;
+    let items = results
+        .into_iter()
+        .map(|result| match result {
+            Ok(value) => json!({ "ok": value }),
+            Err(err) => {
+                let rendered = err.to_json(error_schema_version);
+                first_error.get_or_insert(err);
+                rendered
+            }
+        })
+        .collect();
+
+    let failure = if any_succeeded { None } else { first_error };
+    Ok((Value::Array(items), failure))
+}
+
+/// Whether `result` failed authentication, which retrying or moving on to the
+/// next item or page cannot fix.
+pub fn is_auth_error(result: &Result<Value, AppError>) -> bool {
+    matches!(result, Err(AppError::Auth { .. }))
+}
+
+/// Replaces the error envelopes of a collected batch with one entry per
+/// category/status pair: its `count` and the first error's message as `sample`.
+/// Produces `{"results": [<successful items>], "error_summary": [...]}`, with
+/// groups in order of first appearance. `outcome` picks the `{"ok": ...}` or error
+/// envelope out of each item.
+pub fn summarize(output: Value, outcome: impl Fn(&Value) -> Option<&Value>) -> Value {
+    let Value::Array(items) = output else {
+        return output;
+    };
+
+    let mut results = Vec::new();
+    let mut groups: Vec<Value> = Vec::new();
+    for item in items {
+        let Some(error) = outcome(&item).and_then(|outcome| outcome.get("error")) else {
+            results.push(item);
+            continue;
+        };
+        let category = error.get("category").cloned().unwrap_or(Value::Null);
+        let status = error.get("status").cloned().unwrap_or(Value::Null);
+        match groups
+            .iter_mut()
+            .find(|group| group["category"] == category && group["status"] == status)
+        {
+            Some(group) => group["count"] = json!(group["count"].as_u64().unwrap_or(0) + 1),
+            None => groups.push(json!({
+                "category": category,
+                "status": status,
+                "count": 1,
+                "sample": error.get("message").cloned().unwrap_or(Value::Null),
+            })),
+        }
+    }
+    json!({ "results": results, "error_summary": groups })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{collect, summarize};
+    use crate::app_error::AppError;
+
+    fn mixed_results() -> Vec<Result<serde_json::Value, AppError>> {
+        vec![
+            Ok(json!({ "series": [] })),
+            Err(AppError::Api {
+                status: 400,
+                message: "bad query".to_string(),
+                detail: None,
+            }),
+        ]
+    }
+
+    #[test]
+    fn collect_wraps_each_item_when_collecting_errors() {
+        let (output, failure) = collect(mixed_results(), true, true, 1).unwrap();
+
+        assert!(failure.is_none());
+        assert_eq!(output[0], json!({ "ok": { "series": [] } }));
+        assert_eq!(output[1]["error"]["category"], "api");
+        assert_eq!(output[1]["error"]["status"], 400);
+    }
+
+    #[test]
+    fn collect_reports_failure_when_every_item_failed() {
+        let results = vec![
+            Err(AppError::Usage("first".to_string())),
+            Err(AppError::Internal("second".to_string())),
+        ];
+
+        let (output, failure) = collect(results, true, true, 1).unwrap();
+
+        assert_eq!(output.as_array().unwrap().len(), 2);
+        assert!(matches!(failure, Some(AppError::Usage(message)) if message == "first"));
+    }
+
+    #[test]
+    fn collect_aborts_on_auth_errors_unless_told_to_keep_going() {
+        let results = || {
+            let mut results = mixed_results();
+            results.push(Err(AppError::Auth {
+                status: 403,
+                message: "Forbidden".to_string(),
+                detail: None,
+            }));
+            results
+        };
+
+        let result = collect(results(), true, true, 1);
+        assert!(matches!(result, Err(AppError::Auth { status: 403, .. })));
+
+        let (output, failure) = collect(results(), true, false, 1).unwrap();
+        assert!(failure.is_none());
+        assert_eq!(output[2]["error"]["category"], "auth");
+    }
+
+    #[test]
+    fn collect_fails_fast_without_collecting_errors() {
+        let result = collect(mixed_results(), false, true, 1);
+
+        assert!(matches!(result, Err(AppError::Api { status: 400, .. })));
+    }
+
+    #[test]
+    fn summarize_groups_errors_by_category_and_status() {
+        let api_error = |message: &str| {
+            Err(AppError::Api {
+                status: 400,
+                message: message.to_string(),
+                detail: None,
+            })
+        };
+        let results = vec![
+            Ok(json!({ "series": [1] })),
+            api_error("bad query `a`"),
+            Err(AppError::RateLimited {
+                retry_after_ms: None,
+                message: "slow down".to_string(),
+                detail: None,
+            }),
+            api_error("bad query `b`"),
+            Err(AppError::Api {
+                status: 404,
+                message: "no such metric".to_string(),
+                detail: None,
+            }),
+        ];
+        let (output, failure) = collect(results, true, true, 1).unwrap();
+
+        let summary = summarize(output, |item: &serde_json::Value| Some(item));
+
+        assert!(failure.is_none());
+        assert_eq!(summary["results"], json!([{ "ok": { "series": [1] } }]));
+        assert_eq!(
+            summary["error_summary"],
+            json!([
+                { "category": "api", "status": 400, "count": 2, "sample": "bad query `a`" },
+                { "category": "rate_limit", "status": 429, "count": 1, "sample": "slow down" },
+                { "category": "api", "status": 404, "count": 1, "sample": "no such metric" }
+            ])
+        );
+    }
+}
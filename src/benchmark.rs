@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use serde_json::{Value, json};
+
+/// Summarizes run latencies in milliseconds. Percentiles use the nearest-rank
+/// method, so each one is an observed duration.
+pub fn summarize(durations: &[Duration]) -> Value {
+    let mut millis: Vec<f64> = durations
+        .iter()
+        .map(|duration| duration.as_secs_f64() * 1_000.0)
+        .collect();
+    millis.sort_by(f64::total_cmp);
+
+    let Some((&min, &max)) = millis.first().zip(millis.last()) else {
+        return json!({ "runs": 0 });
+    };
+    let mean = millis.iter().sum::<f64>() / millis.len() as f64;
+    json!({
+        "runs": millis.len(),
+        "min_ms": min,
+        "p50_ms": percentile(&millis, 50.0),
+        "p90_ms": percentile(&millis, 90.0),
+        "p99_ms": percentile(&millis, 99.0),
+        "max_ms": max,
+        "mean_ms": mean,
+    })
+}
+
+fn percentile(sorted: &[f64], percent: f64) -> f64 {
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::summarize;
+
+    #[test]
+    fn summarize_reports_nearest_rank_percentiles() {
+        // Shuffled 1..=100 ms so the input order does not matter.
+        let durations: Vec<Duration> = (1..=100u64)
+            .map(|ms| Duration::from_millis((ms * 37) % 101))
+            .collect();
+
+        assert_eq!(
+            summarize(&durations),
+            json!({
+                "runs": 100,
+                "min_ms": 1.0,
+                "p50_ms": 50.0,
+                "p90_ms": 90.0,
+                "p99_ms": 99.0,
+                "max_ms": 100.0,
+                "mean_ms": 50.5,
+            })
+        );
+        assert_eq!(summarize(&[]), json!({ "runs": 0 }));
+    }
+}
@@ -1,27 +1,74 @@
 use std::path::PathBuf;
 
-use clap::{ArgAction, Parser, Subcommand, ValueEnum};
+use clap::{ArgAction, Args, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Clone, Debug)]
 #[command(
     name = "ddq",
     version,
-    about = "Query Datadog APIs from local CLI without MCP"
+    about = "Query Datadog APIs from local CLI without MCP",
+    args_override_self = true
 )]
 pub struct Cli {
+    /// YAML/JSON file describing the command and its flags; flags given on the
+    /// command line override the spec
+    #[arg(long, value_name = "PATH")]
+    pub spec: Option<PathBuf>,
+
     /// Datadog site suffix or full API base URL.
     /// Examples: datadoghq.com, us3.datadoghq.com, https://api.datadoghq.com
+    /// Shortcuts: eu, us3, us5, ap1, gov
     #[arg(long)]
     pub site: Option<String>,
 
-    /// Datadog API key (falls back to DD_API_KEY)
+    /// Named environment from the config file's [environments] table; selects the
+    /// site and DD_API_KEY_<NAME>/DD_APP_KEY_<NAME> credentials
+    #[arg(long, conflicts_with = "site")]
+    pub environment: Option<String>,
+
+    /// Named [profiles.<NAME>] table of the config file whose api_key/app_key/site
+    /// are used ahead of environment variables
+    #[arg(long, conflicts_with = "environment")]
+    pub profile: Option<String>,
+
+    /// Config file path (default: ~/.config/ddq/config.toml; a missing file is ignored)
+    #[arg(long)]
+    pub config: Option<PathBuf>,
+
+    /// Datadog API key (falls back to DD_API_KEY, then the config file)
     #[arg(long)]
     pub api_key: Option<String>,
 
-    /// Datadog application key (falls back to DD_APP_KEY or DD_APPLICATION_KEY)
+    /// Datadog application key (falls back to DD_APP_KEY or DD_APPLICATION_KEY, then
+    /// the config file)
     #[arg(long)]
     pub app_key: Option<String>,
 
+    /// Read the Datadog API key from this file (surrounding whitespace trimmed);
+    /// keeps the secret out of shell history and `ps`
+    #[arg(long, value_name = "PATH", conflicts_with = "api_key")]
+    pub api_key_file: Option<PathBuf>,
+
+    /// Read the Datadog application key from this file (surrounding whitespace trimmed)
+    #[arg(long, value_name = "PATH", conflicts_with = "app_key")]
+    pub app_key_file: Option<PathBuf>,
+
+    /// Run this shell command and use its trimmed stdout as the Datadog API key
+    /// (e.g. "vault read -field=api_key secret/datadog")
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["api_key", "api_key_file"])]
+    pub api_key_command: Option<String>,
+
+    /// Run this shell command and use its trimmed stdout as the Datadog
+    /// application key
+    #[arg(long, value_name = "COMMAND", conflicts_with_all = ["app_key", "app_key_file"])]
+    pub app_key_command: Option<String>,
+
+    /// Load API/application keys from the OS keyring (stored with `login`) before
+    /// falling back to environment variables
+    #[arg(long)]
+    pub use_keyring: bool,
+
     /// Print compact JSON
     /// Deprecated: prefer --output json
     #[arg(long)]
@@ -31,34 +78,270 @@ pub struct Cli {
     #[arg(long, value_enum, default_value_t = OutputFormat::Json)]
     pub output: OutputFormat,
 
-    /// Number of retry attempts for retryable upstream failures
-    #[arg(long, default_value_t = 3)]
-    pub retries: u32,
+    /// With --output ndjson, end each record with a NUL byte instead of a newline
+    /// (for `xargs -0`)
+    #[arg(long)]
+    pub nul_separated: bool,
+
+    /// With --output csv, also write a JSON schema of the CSV columns to this
+    /// path, each typed string/number/bool/timestamp from a sample of the records
+    #[arg(long, value_name = "PATH")]
+    pub emit_schema: Option<PathBuf>,
+
+    /// Output only the value at this RFC6901 JSON pointer (e.g. /data/0/id);
+    /// prints null when it does not resolve
+    #[arg(long, value_name = "JSON_POINTER")]
+    pub extract: Option<String>,
+
+    /// With --extract, fail instead of printing null when the pointer does not resolve
+    #[arg(long, requires = "extract")]
+    pub require_extract: bool,
+
+    /// POST the final output JSON to this URL after a successful command
+    #[arg(long, value_name = "URL")]
+    pub webhook: Option<String>,
+
+    /// Extra webhook header as "Name: value"; repeatable
+    #[arg(long, value_name = "HEADER", requires = "webhook")]
+    pub webhook_header: Vec<String>,
+
+    /// Do not print the result to stdout (useful with --webhook)
+    #[arg(long)]
+    pub quiet: bool,
+
+    /// Pipe pretty output through $PAGER (default `less -R`) when stdout is a terminal
+    #[arg(long, overrides_with = "no_pager")]
+    pub pager: bool,
+
+    /// Never use a pager (overrides --pager)
+    #[arg(long, overrides_with = "pager")]
+    pub no_pager: bool,
+
+    /// Exit non-zero (category warnings_present) if any warning was emitted, after
+    /// printing the output
+    #[arg(long)]
+    pub fail_on_warnings: bool,
+
+    /// Incremental runs (logs, events, metrics): use the end time stored in this file
+    /// as --from, and store this run's --to there after a complete success
+    #[arg(long, value_name = "PATH")]
+    pub resume_from_marker: Option<PathBuf>,
+
+    /// Print the method, path, params and body that logs/metrics/events would send,
+    /// without sending anything
+    #[arg(long, conflicts_with = "resume_from_marker")]
+    pub explain_request: bool,
 
-    /// Base retry backoff in milliseconds (exponential, capped by --retry-max-backoff-ms)
-    #[arg(long, default_value_t = 250)]
-    pub retry_backoff_ms: u64,
+    /// In pretty output, add a `<field>_human` sibling (e.g. "1.5B") next to numeric
+    /// fields of 1000 or more; JSON output is never changed
+    #[arg(long)]
+    pub humanize: bool,
+
+    /// Maximum nesting depth shown in pretty output; deeper values are replaced
+    /// with a placeholder. JSON output is never truncated.
+    #[arg(long, value_name = "N")]
+    pub max_depth: Option<usize>,
 
-    /// Maximum retry backoff in milliseconds
-    #[arg(long, default_value_t = 5_000)]
-    pub retry_max_backoff_ms: u64,
+    /// Error JSON shape: 1 (original minimal envelope) or 2 (adds schema_version
+    /// and an errors list)
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=2))]
+    pub error_schema_version: u8,
+
+    /// In batch commands, report each item as {"ok": ...} or an error envelope
+    /// instead of failing on the first error; exits 0 if any item succeeded
+    #[arg(long)]
+    pub collect_errors: bool,
+
+    /// Like --collect-errors, but replace the error envelopes with counts per
+    /// category/status and one sample message each:
+    /// {"results": [...], "error_summary": [...]}
+    #[arg(long)]
+    pub summarize_errors: bool,
+
+    /// Keep going after a 401/403 in batches under --collect-errors; by default
+    /// an auth error aborts the whole run since later items would fail the same way
+    #[arg(long)]
+    pub no_fail_fast_auth: bool,
+
+    /// Run logs/metrics/events once per stdin line, replacing `{}` in the query with
+    /// the line; outputs [{"input": ..., "result": ...}]
+    #[arg(long)]
+    pub each_from_stdin: bool,
+
+    /// Trim logs/events queries and collapse whitespace and newlines outside quoted
+    /// strings [default: true with --spec or --each-from-stdin, else false]
+    #[arg(long, value_name = "BOOL", action = ArgAction::Set)]
+    pub normalize_query: Option<bool>,
+
+    /// Maximum queries in flight for --each-from-stdin and logs --query
+    #[arg(long, default_value_t = 4, value_parser = clap::value_parser!(u16).range(1..))]
+    pub concurrency: u16,
+
+    /// Number of retry attempts for retryable upstream failures [default: 3]
+    #[arg(long)]
+    pub retries: Option<u32>,
+
+    /// Base retry backoff in milliseconds (capped by --retry-max-backoff-ms)
+    /// [default: 250]
+    #[arg(long)]
+    pub retry_backoff_ms: Option<u64>,
+
+    /// How the retry delay grows: doubling from --retry-backoff-ms, or the same
+    /// --retry-backoff-ms before every retry
+    #[arg(long, value_enum, default_value_t = BackoffStrategy::Exponential)]
+    pub backoff_strategy: BackoffStrategy,
+
+    /// Randomize each computed retry delay so concurrent clients spread out:
+    /// off, full (0 to delay), or equal (half to full delay); a Retry-After delay
+    /// is never jittered
+    #[arg(long, value_enum, default_value_t = RetryJitter::Equal)]
+    pub retry_jitter: RetryJitter,
+
+    /// Maximum retry backoff in milliseconds [default: 5000]
+    #[arg(long)]
+    pub retry_max_backoff_ms: Option<u64>,
+
+    /// Longest Retry-After delay honored, in milliseconds; longer server-requested
+    /// waits are cut to this
+    #[arg(long, value_name = "MS", default_value_t = 60_000)]
+    pub retry_after_max_ms: u64,
 
     /// Whether to retry rate-limited (HTTP 429) responses.
     /// Pass `--retry-rate-limit=false` to disable.
     #[arg(long, default_value_t = true, action = ArgAction::Set)]
     pub retry_rate_limit: bool,
 
+    /// What to do with a 429 once retries are used up: fail, or wait out the
+    /// (capped) Retry-After and try again so long exports keep going
+    #[arg(long, value_enum, default_value_t = RateLimitPolicy::Fail)]
+    pub on_rate_limit: RateLimitPolicy,
+
+    /// With --on-rate-limit wait, the most extra waits for the whole run before
+    /// a 429 fails after all
+    #[arg(long, value_name = "N", default_value_t = 10)]
+    pub max_rate_limit_waits: u32,
+
+    /// Append a JSON line describing each retry decision to this file
+    #[arg(long, value_name = "PATH")]
+    pub retry_log: Option<PathBuf>,
+
+    /// IANA timezone for the today/yesterday/this-week/this-month time anchors
+    #[arg(long, default_value = "UTC")]
+    pub timezone: String,
+
+    /// Reject bare integer times before 2000 (likely a missing unit) and relative
+    /// times without a unit such as now-15
+    #[arg(long)]
+    pub strict_timeparse: bool,
+
+    /// Display-only: rewrite RFC3339 timestamps in output records as local time in
+    /// this IANA timezone; queries are unaffected
+    #[arg(long, value_name = "ZONE")]
+    pub display_timezone: Option<String>,
+
+    /// Display-only: rewrite RFC3339 timestamps in output records as unix-s,
+    /// unix-ms, rfc3339, or custom:<strftime> (e.g. "custom:%Y-%m-%d %H:%M")
+    #[arg(long, value_name = "FORMAT")]
+    pub timestamp_format: Option<String>,
+
+    /// Dotted path of a timestamp field rewritten by --display-timezone or
+    /// --timestamp-format; repeatable
+    #[arg(
+        long = "timestamp-field",
+        value_name = "DOTTED.PATH",
+        default_value = "attributes.timestamp"
+    )]
+    pub timestamp_fields: Vec<String>,
+
+    /// Reshape the output `data` array into an object of records keyed by the value
+    /// at this dotted path (e.g. attributes.service); records without it go under
+    /// "_ungrouped"
+    #[arg(long, value_name = "DOTTED.PATH")]
+    pub group_output_by: Option<String>,
+
     /// HTTP timeout for Datadog requests in seconds
     #[arg(long, default_value_t = 30)]
     pub timeout_seconds: u64,
 
+    /// Overall time budget in seconds for the whole command, across retries and pages
+    #[arg(long)]
+    pub total_timeout_seconds: Option<u64>,
+
+    /// What to do when a request has the same query param key more than once
+    #[arg(long, value_enum, default_value_t = DuplicateParamPolicy::KeepAll)]
+    pub on_duplicate_param: DuplicateParamPolicy,
+
+    /// Run the command N times without printing results and report latency
+    /// statistics (min, p50, p90, p99, max, mean) to stderr
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub benchmark: Option<u32>,
+
+    /// Require the Datadog server certificate chain to contain a public key with
+    /// this base64 SHA-256 SPKI hash; repeatable to allow key rotation
+    #[arg(long = "pin-sha256", value_name = "BASE64")]
+    pub pin_sha256: Vec<String>,
+
+    /// Gzip JSON request bodies larger than 1 KiB sent to Datadog
+    /// (Content-Encoding: gzip)
+    #[arg(long)]
+    pub compress_request: bool,
+
+    /// Send this id as X-Correlation-Id on every request and add it to the output's
+    /// meta.correlation_id and to error JSON
+    #[arg(long, value_name = "ID")]
+    pub correlation_id: Option<String>,
+
+    /// Print each request (method, full URL, headers with keys masked, body) as
+    /// JSON instead of sending it
+    #[arg(long, conflicts_with = "resume_from_marker")]
+    pub dry_run: bool,
+
+    /// Exit status for a successful --dry-run, so CI can tell "would have run"
+    /// from a real success
+    #[arg(long, value_name = "CODE", default_value_t = 0, requires = "dry_run")]
+    pub dry_run_exit_code: u8,
+
+    /// Report request timing: pretty output becomes {"meta": {"elapsed_ms",
+    /// "attempts", "requests"}, "result": ...}; other formats print
+    /// {"timings": {...}} to stderr and leave stdout unchanged
+    #[arg(long)]
+    pub timings: bool,
+
+    /// Trace each HTTP attempt (method, URL, status, elapsed time) and retry
+    /// decision to stderr as {"trace": ...} lines; -vv adds failed response bodies
+    #[arg(short, long, action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Cut response bodies in the -vv trace to this many bytes (error messages
+    /// keep their own 2048-byte cap)
+    #[arg(long, value_name = "N", default_value_t = 2_048)]
+    pub max_body_log_bytes: usize,
+
+    /// Add the HTTP version negotiated with Datadog (e.g. HTTP/2.0) to the
+    /// output's meta.http_version
+    #[arg(long)]
+    pub report_http_version: bool,
+
     #[command(subcommand)]
     pub command: Command,
 }
 
 impl Cli {
     pub fn compact_output(&self) -> bool {
-        self.compact || matches!(self.output, OutputFormat::Json)
+        self.compact
+            || matches!(
+                self.output,
+                OutputFormat::Json
+                    | OutputFormat::Canonical
+                    | OutputFormat::Csv
+                    | OutputFormat::Ndjson
+            )
+    }
+
+    /// Whether stdout gets pretty-printed JSON, the only output that display-only
+    /// options such as `--humanize` and `--max-depth` rewrite. Tables are excluded.
+    pub fn pretty_json(&self) -> bool {
+        !self.compact_output() && self.output != OutputFormat::Table
     }
 }
 
@@ -66,21 +349,78 @@ impl Cli {
 pub enum OutputFormat {
     Json,
     Pretty,
+    /// Sorted keys, no whitespace, normalized numbers; stable for hashing
+    Canonical,
+    /// Logs/events records as CSV: one row per record, dot-joined field columns
+    Csv,
+    /// One compact JSON line per element of `data`, or the whole response on one line
+    Ndjson,
+    /// Logs/events records as an aligned text table (timestamp, service, host,
+    /// status, message, or the --fields set), fitted to $COLUMNS
+    Table,
 }
 
-#[derive(Subcommand, Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum BackoffStrategy {
+    /// Double the delay after each retry
+    Exponential,
+    /// Sleep --retry-backoff-ms before every retry
+    Constant,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum RetryJitter {
+    /// Sleep exactly the computed delay
+    Off,
+    /// Sleep a random delay between 0 and the computed delay
+    Full,
+    /// Sleep half the computed delay plus a random part of the other half
+    Equal,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum RateLimitPolicy {
+    /// Report the 429 as a rate_limit error
+    Fail,
+    /// Sleep the Retry-After delay (or the backoff) and send the request again
+    Wait,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+pub enum DuplicateParamPolicy {
+    /// Send every occurrence (e.g. for endpoints that read repeated keys as arrays)
+    KeepAll,
+    /// Send only the last value given for each key
+    LastWins,
+    /// Reject the request
+    Error,
+}
+
+#[derive(Subcommand, Clone, Debug)]
 pub enum Command {
     /// Query logs via /api/v2/logs/events/search
     Logs {
         /// Datadog log query string
         #[arg(value_name = "QUERY")]
         query: String,
+        /// Another query run concurrently with QUERY (up to --concurrency at once);
+        /// results are merged, deduplicated by id and re-sorted by timestamp;
+        /// repeatable
+        #[arg(long = "query", value_name = "QUERY", conflicts_with = "cursor")]
+        queries: Vec<String>,
         /// Start time; supports RFC3339 or relative expressions like now-15m
         #[arg(long, default_value = "now-15m")]
         from: String,
         /// End time; supports RFC3339 or relative expressions like now
         #[arg(long, default_value = "now")]
         to: String,
+        /// Center the time window on this RFC3339 or unix time instead of
+        /// --from/--to; needs --window
+        #[arg(long, value_name = "TIME", requires = "window", conflicts_with_all = ["from", "to"])]
+        around: Option<String>,
+        /// Length of the window centered on --around, e.g. 10m or 1h30m
+        #[arg(long, value_name = "DURATION", requires = "around")]
+        window: Option<String>,
         /// Result count (max currently enforced by Datadog API)
         #[arg(long, default_value_t = 50)]
         limit: u32,
@@ -90,18 +430,150 @@ pub enum Command {
         /// Pagination cursor from previous response
         #[arg(long)]
         cursor: Option<String>,
+        /// Follow meta.page.after until no cursor remains, fetching --limit logs per
+        /// page, and merge the pages into one response
+        #[arg(long)]
+        all: bool,
+        /// Print only {"count": N}, the exact number of matching logs, computed by
+        /// /api/v2/logs/analytics/aggregate instead of fetching records
+        #[arg(long, conflicts_with_all = ["all", "cursor", "queries"])]
+        count: bool,
+        /// With --all, stop after N pages; if more logs remain the output has
+        /// "complete": false and the next cursor in meta.page.after
+        #[arg(long, value_name = "N", requires = "all", value_parser = clap::value_parser!(u32).range(1..))]
+        max_pages: Option<u32>,
+        /// Match any of several values: facet=v1,v2 expands to (facet:v1 OR facet:v2)
+        /// and is ANDed with QUERY; repeat to AND several groups
+        #[arg(long, value_name = "FACET=V1,V2")]
+        any: Vec<String>,
+        /// Keep each returned record with this probability (0.0-1.0)
+        #[arg(long, value_name = "RATE")]
+        sample: Option<f64>,
+        /// Seed for --sample so the kept records are reproducible
+        #[arg(long, requires = "sample")]
+        sample_seed: Option<u64>,
+        /// Keep only these attributes in each record (comma-separated dotted paths,
+        /// standard or custom). The search API has no field selection, so this is
+        /// applied client-side after the response arrives.
+        #[arg(long, value_name = "A,B,C", value_delimiter = ',')]
+        return_fields: Vec<String>,
+        /// Lift each record's `attributes` fields to the top level
+        #[arg(long)]
+        flatten_attributes: bool,
+        /// Prefix added to lifted attribute keys
+        #[arg(long, requires = "flatten_attributes")]
+        prefix: Option<String>,
+        #[command(flatten)]
+        records: RecordArgs,
+    },
+    /// Count or aggregate logs into groups via /api/v2/logs/analytics/aggregate
+    LogsAggregate {
+        /// Datadog log query string
+        #[arg(value_name = "QUERY", default_value = "*")]
+        query: String,
+        /// Start time; supports RFC3339 or relative expressions like now-15m
+        #[arg(long, default_value = "now-15m")]
+        from: String,
+        /// End time; supports RFC3339 or relative expressions like now
+        #[arg(long, default_value = "now")]
+        to: String,
+        /// Aggregation as AGG or AGG:METRIC, e.g. count, avg:@duration,
+        /// pc95:@latency, cardinality:@usr.id; repeatable (default: count)
+        #[arg(long, value_name = "AGG[:METRIC]")]
+        compute: Vec<String>,
+        /// Facet to group by, e.g. service or @http.status_code; repeatable
+        #[arg(long, value_name = "FACET")]
+        group_by: Vec<String>,
+        /// Maximum number of groups returned per --group-by facet
+        #[arg(long, default_value_t = 10, requires = "group_by")]
+        group_limit: u32,
+    },
+    /// List, get, or create log-based metrics via /api/v2/logs/config/metrics
+    LogsMetrics {
+        #[command(subcommand)]
+        action: LogsMetricsAction,
+    },
+    /// Rehydrate archived logs via /api/v2/logs/config/archives/{id}/rehydrations
+    LogsRehydrate {
+        /// Log archive to rehydrate from
+        #[arg(long)]
+        archive_id: String,
+        /// Name of the rehydrated index
+        #[arg(long)]
+        name: String,
+        /// Datadog log query selecting the logs to rehydrate
+        #[arg(long, default_value = "*")]
+        query: String,
+        /// Start time; supports unix seconds or milliseconds, RFC3339, now-15m, today
+        #[arg(long)]
+        from: String,
+        /// End time; supports unix seconds or milliseconds, RFC3339, now, now+5m
+        #[arg(long, default_value = "now")]
+        to: String,
+        /// Poll the rehydration until it is done or failed and print its final status
+        #[arg(long)]
+        wait: bool,
+        /// Delay between status polls in milliseconds
+        #[arg(long, default_value_t = 5_000, requires = "wait")]
+        poll_interval_ms: u64,
+        /// Give up waiting after this many seconds
+        #[arg(long, default_value_t = 3_600, requires = "wait")]
+        wait_timeout_seconds: u64,
     },
     /// Query metrics via /api/v1/query
     Metrics {
-        /// Datadog metric query expression
-        #[arg(value_name = "QUERY")]
-        query: String,
-        /// Start time; supports unix seconds, RFC3339, now-15m, now-1h, now-2d
+        /// Datadog metric query expression; pass several to run them as a batch (up
+        /// to --concurrency at once)
+        #[arg(value_name = "QUERY", required = true)]
+        queries: Vec<String>,
+        /// Start time; supports unix seconds or milliseconds, RFC3339, now-15m, now-1h30m, now-2d
         #[arg(long, default_value = "now-15m")]
         from: String,
-        /// End time; supports unix seconds, RFC3339, now
+        /// End time; supports unix seconds or milliseconds, RFC3339, now, now+5m
         #[arg(long, default_value = "now")]
         to: String,
+        /// Round --from down and --to up to a multiple of this duration (e.g. 5m)
+        /// so Datadog's buckets start on round times
+        #[arg(long, value_name = "DURATION")]
+        align_window: Option<String>,
+        /// Fail when the response contains more than N series
+        #[arg(long, value_name = "N")]
+        max_series: Option<usize>,
+        /// With --max-series, keep the first N series and warn instead of failing
+        #[arg(long, requires = "max_series")]
+        truncate_series: bool,
+        /// Attach each series' unit from the metric metadata (/api/v1/metrics/{name})
+        #[arg(long)]
+        annotate_units: bool,
+        /// Send all queries in one request to /api/v2/query/timeseries instead of
+        /// /api/v1/query; name a query with a prefix (a:avg:system.cpu.user{*}) to use
+        /// it in --formula
+        #[arg(long, conflicts_with_all = ["max_series", "annotate_units"])]
+        v2: bool,
+        /// With --v2, a formula over the named queries (e.g. "a / 100"); repeatable
+        #[arg(long, value_name = "EXPR", requires = "v2")]
+        formula: Vec<String>,
+        /// With --v2, the interval between points in milliseconds
+        #[arg(long, value_name = "MS", requires = "v2")]
+        interval: Option<u64>,
+    },
+    /// Submit a single metric point via /api/v2/series
+    MetricsSubmit {
+        /// Metric name
+        #[arg(long)]
+        metric: String,
+        /// Point value
+        #[arg(long, allow_negative_numbers = true)]
+        value: f64,
+        /// Point time; supports unix seconds or milliseconds, RFC3339, now-15m, now
+        #[arg(long, default_value = "now")]
+        timestamp: String,
+        /// Metric type: gauge, count, rate, or unspecified
+        #[arg(long = "type", default_value = "gauge")]
+        metric_type: String,
+        /// Metric tags as repeated key:value
+        #[arg(long = "tag")]
+        tags: Vec<String>,
     },
     /// Query events via /api/v2/events
     Events {
@@ -114,12 +586,93 @@ pub enum Command {
         /// End time; supports RFC3339 or relative expressions like now
         #[arg(long, default_value = "now")]
         to: String,
-        /// Result count
+        /// Total number of events to return, fetched across pages as needed
         #[arg(long, default_value_t = 50)]
         limit: u32,
+        /// Events per request (default: --limit, capped at 1000); the last request asks
+        /// only for what --limit still allows
+        #[arg(long)]
+        page_size: Option<u32>,
+        /// Stop after N pages; if more events remain the output has
+        /// "complete": false and the next cursor in meta.page.after
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        max_pages: Option<u32>,
         /// Sort order: asc or desc
         #[arg(long, default_value = "desc")]
         sort: String,
+        /// Only events with this priority: normal or low
+        #[arg(long)]
+        priority: Option<String>,
+        /// Only events with this alert type: error, warning, info or success
+        #[arg(long)]
+        alert_type: Option<String>,
+        /// Fetch every event in the window instead of stopping at --limit (still
+        /// bounded by --max-pages)
+        #[arg(long)]
+        all: bool,
+        /// With --all, count the events grouped by this attribute (e.g. alert_type,
+        /// source); repeat to nest groups
+        #[arg(long = "group-by", value_name = "FIELD", requires = "all")]
+        group_by: Vec<String>,
+        #[command(flatten)]
+        records: RecordArgs,
+    },
+    /// Query live processes via /api/v2/processes
+    Processes {
+        /// Text matched against process command lines
+        #[arg(long)]
+        search: Option<String>,
+        /// Comma-separated tags filter (e.g. env:prod,role:db)
+        #[arg(long)]
+        tags: Option<String>,
+        /// Start time; supports unix seconds or milliseconds, RFC3339, now-15m
+        #[arg(long, default_value = "now-15m")]
+        from: String,
+        /// End time; supports unix seconds or milliseconds, RFC3339, now, now+5m
+        #[arg(long, default_value = "now")]
+        to: String,
+        /// Processes per page
+        #[arg(long, default_value_t = 1_000)]
+        limit: u32,
+        /// Pagination cursor from a previous response's meta.page.after
+        #[arg(long)]
+        cursor: Option<String>,
+    },
+    /// Read or manage host tags via /api/v1/tags/hosts/{host}
+    Tags {
+        #[command(subcommand)]
+        action: TagsAction,
+    },
+    /// List or fetch monitors via /api/v1/monitor
+    Monitors {
+        #[command(subcommand)]
+        action: MonitorsAction,
+    },
+    /// List hosts or count them via /api/v1/hosts
+    Hosts {
+        #[command(subcommand)]
+        action: HostsAction,
+    },
+    /// List or fetch service level objectives via /api/v1/slo
+    Slo {
+        #[command(subcommand)]
+        action: SloAction,
+    },
+    /// Fetch roles or restriction policies for access audits
+    Rbac {
+        #[command(subcommand)]
+        action: RbacAction,
+    },
+    /// Store API/application keys in the OS keyring for --use-keyring.
+    /// Keys come from --api-key/--app-key, or one per line on stdin.
+    Login,
+    /// Validate the config file (--config or the default path) and list unknown
+    /// keys, wrong types and malformed profiles; exits 2 when any are errors
+    ConfigCheck,
+    /// Print a shell completion script to stdout, e.g. ddq completions bash
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
     },
     /// Generic Datadog API call for unsupported endpoints
     Raw {
@@ -138,5 +691,250 @@ pub enum Command {
         /// Read JSON body from file
         #[arg(long)]
         body_file: Option<PathBuf>,
+        /// Idempotency-Key header value; also allows non-GET requests to be resent
+        /// after transport failures
+        #[arg(long)]
+        idempotency_key: Option<String>,
+        /// On 202 Accepted, poll the Location header until the operation completes
+        #[arg(long)]
+        await_completion: bool,
+        /// Dotted path of the status value in poll responses
+        #[arg(long, default_value = "data.attributes.status")]
+        status_field: String,
+        /// Delay between completion polls in milliseconds
+        #[arg(long, default_value_t = 1_000)]
+        poll_interval_ms: u64,
+        /// Give up awaiting completion after this many seconds
+        #[arg(long, default_value_t = 300)]
+        await_timeout_seconds: u64,
+        /// Treat this HTTP error status as success and print its body (e.g. 404 when
+        /// deleting something already gone); repeatable
+        #[arg(long = "ignore-status", value_name = "CODE", value_parser = clap::value_parser!(u16).range(400..=599))]
+        ignore_statuses: Vec<u16>,
+        /// When the body is a JSON array, send it in requests of at most N items
+        /// and print the responses as an array
+        #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+        chunk_size: Option<u32>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum TagsAction {
+    /// Get a host's tags
+    Get {
+        #[arg(value_name = "HOST")]
+        host: String,
+    },
+    /// Replace a host's tags with the given set
+    Update {
+        #[arg(value_name = "HOST")]
+        host: String,
+        /// Tag as key:value; repeatable
+        #[arg(long = "tag", value_name = "KEY:VALUE", required = true)]
+        tags: Vec<String>,
+    },
+    /// Add tags to a host, keeping existing ones
+    Add {
+        #[arg(value_name = "HOST")]
+        host: String,
+        /// Tag as key:value; repeatable
+        #[arg(long = "tag", value_name = "KEY:VALUE", required = true)]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum MonitorsAction {
+    /// List monitors, optionally filtered
+    List {
+        /// Only monitors whose name contains this text
+        #[arg(long)]
+        name: Option<String>,
+        /// Comma-separated scope tags the monitors apply to (e.g. host:web-1,env:prod)
+        #[arg(long)]
+        tags: Option<String>,
+        /// Comma-separated tags set on the monitors themselves (e.g. team:core)
+        #[arg(long)]
+        monitor_tags: Option<String>,
+        /// Monitors per page (enables paging)
+        #[arg(long)]
+        limit: Option<u32>,
+        /// Zero-based page number, used with --limit
+        #[arg(long, requires = "limit")]
+        page: Option<u32>,
+    },
+    /// Get one monitor by id
+    Get {
+        #[arg(long)]
+        id: u64,
+    },
+    /// Mute a monitor, indefinitely or until --end
+    Mute {
+        #[arg(long)]
+        id: u64,
+        /// When the mute expires; supports unix seconds or milliseconds, RFC3339,
+        /// now+2h
+        #[arg(long)]
+        end: Option<String>,
+    },
+    /// Unmute a monitor
+    Unmute {
+        #[arg(long)]
+        id: u64,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum HostsAction {
+    /// List hosts, optionally filtered and sorted
+    List {
+        /// Only hosts matching this name, alias or tag (e.g. env:prod)
+        #[arg(long)]
+        filter: Option<String>,
+        /// Field to sort by (e.g. cpu, iowait, load, apps, name)
+        #[arg(long)]
+        sort_field: Option<String>,
+        /// Sort order: asc or desc
+        #[arg(long, value_parser = ["asc", "desc"])]
+        sort_dir: Option<String>,
+        /// Hosts to return (max 1000)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..=1000))]
+        count: Option<u32>,
+        /// Index of the first host to return, for paging
+        #[arg(long)]
+        start: Option<u32>,
     },
+    /// Count total and active hosts via /api/v1/hosts/totals
+    Totals,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum SloAction {
+    /// List SLOs, optionally filtered
+    List {
+        /// Search text matched against SLO names and tags
+        #[arg(long)]
+        query: Option<String>,
+        /// Only SLOs with these tags (e.g. team:core AND env:prod)
+        #[arg(long)]
+        tags_query: Option<String>,
+        /// SLOs per page (enables paging)
+        #[arg(long)]
+        limit: Option<u32>,
+        /// Number of SLOs to skip, used with --limit
+        #[arg(long, requires = "limit")]
+        offset: Option<u32>,
+    },
+    /// Get one SLO by id, or its history with --history
+    Get {
+        #[arg(long)]
+        id: String,
+        /// Fetch /api/v1/slo/{id}/history for the --from/--to window instead
+        #[arg(long)]
+        history: bool,
+        /// History start; supports unix seconds or milliseconds, RFC3339, now-7d
+        /// (default now-7d)
+        #[arg(long, requires = "history")]
+        from: Option<String>,
+        /// History end (default now)
+        #[arg(long, requires = "history")]
+        to: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum RbacAction {
+    /// List roles via /api/v2/roles
+    Roles {
+        /// Only roles whose name contains this text
+        #[arg(long)]
+        filter: Option<String>,
+        /// Roles per page (max 100)
+        #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..=100))]
+        page_size: u32,
+        /// Zero-based page number
+        #[arg(long, default_value_t = 0)]
+        page_number: u32,
+        /// Fetch every page from --page-number on and merge the roles
+        #[arg(long)]
+        all: bool,
+        /// Stop after this many pages when using --all
+        #[arg(long, requires = "all")]
+        max_pages: Option<u32>,
+    },
+    /// Get a resource's restriction policy via /api/v2/restriction_policy
+    RestrictionPolicy {
+        /// Resource as <type>:<id>, e.g. dashboard:abc-def-ghi
+        #[arg(long)]
+        resource_id: String,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+pub enum LogsMetricsAction {
+    /// List all log-based metrics
+    List,
+    /// Get one log-based metric by name
+    Get {
+        /// Metric name (the log-based metric id)
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+    /// Create a log-based metric
+    Create {
+        /// Metric name
+        #[arg(long)]
+        name: String,
+        /// Log query selecting the logs counted by the metric
+        #[arg(long, default_value = "*")]
+        query: String,
+        /// Aggregation: count or distribution
+        #[arg(long, default_value = "count")]
+        aggregation_type: String,
+        /// Attribute path measured by a distribution metric (e.g. @duration)
+        #[arg(long)]
+        path: Option<String>,
+        /// Compute percentiles for a distribution metric
+        #[arg(long)]
+        include_percentiles: bool,
+        /// Attribute path to group by as PATH or PATH:TAG_NAME; repeatable
+        #[arg(long = "group-by", value_name = "PATH[:TAG]")]
+        group_by: Vec<String>,
+    },
+}
+
+/// Post-processing options shared by the record-returning commands (logs, events).
+#[derive(Args, Clone, Debug, Default)]
+pub struct RecordArgs {
+    /// Drop records whose `id` was already seen, keeping the first occurrence
+    #[arg(long)]
+    pub dedupe: bool,
+
+    /// Dotted path of the value used to re-sort records client-side, following --sort
+    /// (e.g. attributes.timestamp)
+    #[arg(long, value_name = "DOTTED.PATH")]
+    pub sort_key: Option<String>,
+
+    /// Keep only these dotted paths inside each record's `attributes` (e.g.
+    /// timestamp,host,service,message); custom attributes are matched too and
+    /// missing paths become null
+    #[arg(long, value_name = "A,B,C", value_delimiter = ',')]
+    pub fields: Vec<String>,
+
+    /// Fetch a single record (ignoring --limit and --cursor) to validate a query
+    #[arg(long)]
+    pub preview: bool,
+
+    /// Re-query when the response has no records (e.g. indexing lag), up to
+    /// --empty-retries times
+    #[arg(long)]
+    pub retry_on_empty: bool,
+
+    /// Maximum extra queries made by --retry-on-empty
+    #[arg(long, default_value_t = 3, requires = "retry_on_empty")]
+    pub empty_retries: u32,
+
+    /// Delay before each --retry-on-empty query in milliseconds
+    #[arg(long, default_value_t = 1_000, requires = "retry_on_empty")]
+    pub empty_retry_delay_ms: u64,
 }
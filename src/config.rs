@@ -1,8 +1,19 @@
+use std::collections::BTreeMap;
 use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use anyhow::{Context, Result, anyhow};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Value, json};
 
-use crate::cli::Cli;
+use crate::cli::{BackoffStrategy, Cli, DuplicateParamPolicy, RateLimitPolicy, RetryJitter};
+use crate::credentials::{
+    API_KEY_ACCOUNT, APP_KEY_ACCOUNT, CommandRunner, CredentialStore, KeyringStore, ShellRunner,
+    key_from_command,
+};
+use crate::tls::{SpkiPin, parse_pin};
 
 pub struct Config {
     pub api_key: String,
@@ -10,44 +21,191 @@ pub struct Config {
     pub base_url: String,
     pub retry: RetryConfig,
     pub timeout_seconds: u64,
+    pub total_timeout_seconds: Option<u64>,
+    pub compress_request: bool,
+    pub duplicate_params: DuplicateParamPolicy,
+    pub pins: Vec<SpkiPin>,
+    pub correlation_id: Option<String>,
+    pub dry_run: bool,
+    /// How many times `-v` was given.
+    pub verbosity: u8,
+    /// Cap on response bodies in the `-vv` trace.
+    pub max_body_log_bytes: usize,
 }
 
 pub struct RetryConfig {
     pub max_retries: u32,
     pub backoff_ms: u64,
     pub max_backoff_ms: u64,
+    pub strategy: BackoffStrategy,
+    pub jitter: RetryJitter,
+    /// Cap on a server-requested Retry-After delay.
+    pub retry_after_max_ms: u64,
     pub retry_rate_limit: bool,
+    /// What a 429 does once retries are used up.
+    pub on_rate_limit: RateLimitPolicy,
+    /// Cap on `RateLimitPolicy::Wait` waits across the whole run.
+    pub max_rate_limit_waits: u32,
+    pub retry_log: Option<PathBuf>,
+}
+
+const DEFAULT_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF_MS: u64 = 250;
+const DEFAULT_RETRY_MAX_BACKOFF_MS: u64 = 5_000;
+
+/// Optional settings read from the TOML config file. Each applies only when
+/// neither the matching flag nor environment variable is set.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub api_key: Option<String>,
+    pub app_key: Option<String>,
+    pub site: Option<String>,
+    pub retries: Option<u32>,
+    pub retry_backoff_ms: Option<u64>,
+    pub retry_max_backoff_ms: Option<u64>,
+    /// Environment name to Datadog site, e.g. `prod = "us3.datadoghq.com"`.
+    #[serde(default)]
+    pub environments: BTreeMap<String, String>,
+    /// Named credential sets selected with `--profile`, e.g. `[profiles.eu]`.
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// One `[profiles.<name>]` table of the config file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub api_key: Option<String>,
+    pub app_key: Option<String>,
+    pub site: Option<String>,
+}
+
+impl FileConfig {
+    /// Loads the config file at `path` (or the default location). A missing file
+    /// yields an empty config.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path.map(Path::to_path_buf).or_else(default_config_path) else {
+            return Ok(Self::default());
+        };
+
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(err)
+                    .with_context(|| format!("Failed reading config file `{}`", path.display()));
+            }
+        };
+        toml::from_str(&contents)
+            .with_context(|| format!("Invalid config file `{}`", path.display()))
+    }
 }
 
 impl Config {
     pub fn from_cli(cli: &Cli) -> Result<Self> {
-        let api_key = cli
-            .api_key
-            .clone()
-            .or_else(|| env::var("DD_API_KEY").ok())
-            .context("Missing Datadog API key. Set --api-key or DD_API_KEY.")?;
-
-        let app_key = cli
-            .app_key
-            .clone()
-            .or_else(|| env::var("DD_APP_KEY").ok())
-            .or_else(|| env::var("DD_APPLICATION_KEY").ok())
-            .context(
-                "Missing Datadog application key. Set --app-key or DD_APP_KEY (or DD_APPLICATION_KEY).",
+        let file = FileConfig::load(cli.config.as_deref())?;
+        let keyring = KeyringStore::default();
+        let store = cli.use_keyring.then_some(&keyring as &dyn CredentialStore);
+        Self::resolve(cli, &file, store, &ShellRunner, |name| env::var(name).ok())
+    }
+
+    pub(crate) fn resolve(
+        cli: &Cli,
+        file: &FileConfig,
+        store: Option<&dyn CredentialStore>,
+        runner: &dyn CommandRunner,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Self> {
+        let environment = cli
+            .environment
+            .as_deref()
+            .map(|name| {
+                file.environments
+                    .get(name)
+                    .map(|site| (name, site))
+                    .ok_or_else(|| {
+                        let available: Vec<&str> =
+                            file.environments.keys().map(String::as_str).collect();
+                        anyhow!(
+                            "Unknown environment `{name}`. Available environments: [{}].",
+                            available.join(", ")
+                        )
+                    })
+            })
+            .transpose()?;
+        let profile = match cli.profile.as_deref() {
+            Some(name) => file.profiles.get(name).ok_or_else(|| {
+                let available: Vec<&str> = file.profiles.keys().map(String::as_str).collect();
+                anyhow!(
+                    "Unknown profile `{name}`. Available profiles: [{}].",
+                    available.join(", ")
+                )
+            })?,
+            None => &Profile::default(),
+        };
+        let env_suffix = environment.map(|(name, _)| env_var_suffix(name));
+        let scoped_env = |base: &str| {
+            env_suffix
+                .as_ref()
+                .and_then(|suffix| env(&format!("{base}_{suffix}")))
+        };
+
+        let stored = |account: &str| store.map_or(Ok(None), |store| store.get(account));
+
+        let api_key = first_key(&[
+            &|| Ok(cli.api_key.clone()),
+            &|| read_key_file(cli.api_key_file.as_deref()),
+            &|| run_key_command(runner, cli.api_key_command.as_deref()),
+            &|| Ok(profile.api_key.clone()),
+            &|| stored(API_KEY_ACCOUNT),
+            &|| Ok(scoped_env("DD_API_KEY")),
+            &|| Ok(env("DD_API_KEY")),
+            &|| Ok(file.api_key.clone()),
+        ])?
+        .context("Missing Datadog API key. Set --api-key, --api-key-file or DD_API_KEY.")?;
+
+        let app_key = first_key(&[
+            &|| Ok(cli.app_key.clone()),
+            &|| read_key_file(cli.app_key_file.as_deref()),
+            &|| run_key_command(runner, cli.app_key_command.as_deref()),
+            &|| Ok(profile.app_key.clone()),
+            &|| stored(APP_KEY_ACCOUNT),
+            &|| Ok(scoped_env("DD_APP_KEY")),
+            &|| Ok(env("DD_APP_KEY")),
+            &|| Ok(env("DD_APPLICATION_KEY")),
+            &|| Ok(file.app_key.clone()),
+        ])?
+        .context(
+                "Missing Datadog application key. Set --app-key, --app-key-file or DD_APP_KEY (or DD_APPLICATION_KEY).",
             )?;
 
-        let site = cli
-            .site
-            .clone()
-            .or_else(|| env::var("DD_SITE").ok())
-            .unwrap_or_else(|| "datadoghq.com".to_string());
+        let site = match environment {
+            Some((_, site)) => site.clone(),
+            None => cli
+                .site
+                .clone()
+                .or_else(|| profile.site.clone())
+                .or_else(|| env("DD_SITE"))
+                .or_else(|| file.site.clone())
+                .unwrap_or_else(|| "datadoghq.com".to_string()),
+        };
 
         let base_url = normalize_base_url(&site)?;
 
-        if cli.retry_backoff_ms == 0 {
+        let max_retries = cli.retries.or(file.retries).unwrap_or(DEFAULT_RETRIES);
+        let backoff_ms = cli
+            .retry_backoff_ms
+            .or(file.retry_backoff_ms)
+            .unwrap_or(DEFAULT_RETRY_BACKOFF_MS);
+        let max_backoff_ms = cli
+            .retry_max_backoff_ms
+            .or(file.retry_max_backoff_ms)
+            .unwrap_or(DEFAULT_RETRY_MAX_BACKOFF_MS);
+        if backoff_ms == 0 {
             return Err(anyhow!("--retry-backoff-ms must be greater than 0."));
         }
-        if cli.retry_max_backoff_ms < cli.retry_backoff_ms {
+        if max_backoff_ms < backoff_ms {
             return Err(anyhow!(
                 "--retry-max-backoff-ms must be greater than or equal to --retry-backoff-ms."
             ));
@@ -55,27 +213,243 @@ impl Config {
         if cli.timeout_seconds == 0 {
             return Err(anyhow!("--timeout-seconds must be greater than 0."));
         }
+        if cli.total_timeout_seconds == Some(0) {
+            return Err(anyhow!("--total-timeout-seconds must be greater than 0."));
+        }
 
         Ok(Self {
             api_key,
             app_key,
             base_url,
             retry: RetryConfig {
-                max_retries: cli.retries,
-                backoff_ms: cli.retry_backoff_ms,
-                max_backoff_ms: cli.retry_max_backoff_ms,
+                max_retries,
+                backoff_ms,
+                max_backoff_ms,
+                strategy: cli.backoff_strategy,
+                jitter: cli.retry_jitter,
+                retry_after_max_ms: cli.retry_after_max_ms,
                 retry_rate_limit: cli.retry_rate_limit,
+                on_rate_limit: cli.on_rate_limit,
+                max_rate_limit_waits: cli.max_rate_limit_waits,
+                retry_log: cli.retry_log.clone(),
             },
             timeout_seconds: cli.timeout_seconds,
+            total_timeout_seconds: cli.total_timeout_seconds,
+            compress_request: cli.compress_request,
+            duplicate_params: cli.on_duplicate_param,
+            correlation_id: cli.correlation_id.clone(),
+            dry_run: cli.dry_run,
+            verbosity: cli.verbose,
+            max_body_log_bytes: cli.max_body_log_bytes,
+            pins: cli
+                .pin_sha256
+                .iter()
+                .map(|pin| parse_pin(pin))
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|err| anyhow!(err))?,
         })
     }
 }
 
+/// The key from the first source that has one. Sources are tried in order and
+/// only until one answers, so a key file is not read and a helper command not
+/// run when a higher-priority source already supplied the key.
+fn first_key(sources: &[&dyn Fn() -> Result<Option<String>>]) -> Result<Option<String>> {
+    for source in sources {
+        if let Some(key) = source()? {
+            return Ok(Some(key));
+        }
+    }
+    Ok(None)
+}
+
+/// Runs `--api-key-command`/`--app-key-command` through `runner`.
+fn run_key_command(runner: &dyn CommandRunner, command: Option<&str>) -> Result<Option<String>> {
+    command
+        .map(|command| key_from_command(runner, command))
+        .transpose()
+}
+
+/// Reads a credential from `--api-key-file`/`--app-key-file`, trimmed of
+/// surrounding whitespace. Unlike the config file, a missing file is an error.
+fn read_key_file(path: Option<&Path>) -> Result<Option<String>> {
+    let Some(path) = path else {
+        return Ok(None);
+    };
+    let contents = fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed reading key file `{}`: {err}", path.display()))?;
+    let key = contents.trim();
+    if key.is_empty() {
+        return Err(anyhow!("Key file `{}` is empty.", path.display()));
+    }
+    Ok(Some(key.to_string()))
+}
+
+/// Validates the config file at `path` (or the default location) for
+/// `config-check`: `{"file", "found", "valid", "issues": [{"severity", "key",
+/// "message"}]}`. A missing default file is valid; a missing explicit one is not.
+pub fn check_config_file(path: Option<&Path>) -> Value {
+    let explicit = path.is_some();
+    let path = path.map(Path::to_path_buf).or_else(default_config_path);
+    let (found, issues) = match path.as_deref().map(fs::read_to_string) {
+        None => (false, Vec::new()),
+        Some(Ok(contents)) => (true, check_config_text(&contents)),
+        Some(Err(err)) if err.kind() == std::io::ErrorKind::NotFound && !explicit => {
+            (false, Vec::new())
+        }
+        Some(Err(err)) => (
+            false,
+            vec![config_issue(
+                "error",
+                "",
+                format!("Failed reading the file: {err}"),
+            )],
+        ),
+    };
+    let valid = !issues.iter().any(|issue| issue["severity"] == "error");
+    json!({
+        "file": path.map(|path| path.display().to_string()),
+        "found": found,
+        "valid": valid,
+        "issues": issues,
+    })
+}
+
+/// Lists everything wrong with config file `contents`. TOML syntax, unknown keys
+/// and wrong value types are found by deserializing each entry on its own into
+/// [`FileConfig`] (or [`Profile`]), so the check accepts exactly what
+/// [`FileConfig::load`] does and reports every bad entry, not just the first.
+/// Empty strings and profiles that are empty or half-configured are flagged on
+/// top.
+fn check_config_text(contents: &str) -> Vec<Value> {
+    let table = match contents.parse::<toml::Table>() {
+        Ok(table) => table,
+        Err(err) => return vec![config_issue("error", "", err.message().to_string())],
+    };
+
+    let mut issues = Vec::new();
+    for (key, value) in &table {
+        match (key.as_str(), value.as_table()) {
+            ("environments", Some(environments)) => {
+                for (name, site) in environments {
+                    check_entry::<BTreeMap<String, String>>(
+                        &format!("environments.{name}"),
+                        name,
+                        site,
+                        &mut issues,
+                    );
+                }
+            }
+            ("profiles", Some(profiles)) => {
+                for (name, profile) in profiles {
+                    check_profile(name, profile, &mut issues);
+                }
+            }
+            _ => check_entry::<FileConfig>(key, key, value, &mut issues),
+        }
+    }
+    check_key_pair("", &table, &mut issues);
+    issues
+}
+
+fn check_profile(name: &str, profile: &toml::Value, issues: &mut Vec<Value>) {
+    let prefix = format!("profiles.{name}");
+    let Some(profile) = profile.as_table() else {
+        check_entry::<BTreeMap<String, Profile>>(&prefix, name, profile, issues);
+        return;
+    };
+    if profile.is_empty() {
+        issues.push(config_issue(
+            "warning",
+            &prefix,
+            "sets nothing, so selecting it changes nothing".to_string(),
+        ));
+    }
+    for (key, value) in profile {
+        check_entry::<Profile>(&format!("{prefix}.{key}"), key, value, issues);
+    }
+    check_key_pair(&format!("{prefix}."), profile, issues);
+}
+
+/// Checks the single entry `key = value` by deserializing it as a `T`, reporting
+/// serde's message (unknown field, invalid type) under `path`. A string that
+/// fits but is blank is an error too.
+fn check_entry<T: DeserializeOwned>(
+    path: &str,
+    key: &str,
+    value: &toml::Value,
+    issues: &mut Vec<Value>,
+) {
+    let entry = toml::Table::from_iter([(key.to_string(), value.clone())]);
+    if let Err(err) = toml::Value::Table(entry).try_into::<T>() {
+        issues.push(config_issue("error", path, err.message().to_string()));
+    } else if value.as_str().is_some_and(|text| text.trim().is_empty()) {
+        issues.push(config_issue("error", path, "must not be empty".to_string()));
+    }
+}
+
+/// Warns when a table sets only one of `api_key`/`app_key`: the other then comes
+/// from a later source, which is rarely the intent.
+fn check_key_pair(prefix: &str, table: &toml::Table, issues: &mut Vec<Value>) {
+    let (set, missing) = match (table.contains_key("api_key"), table.contains_key("app_key")) {
+        (true, false) => ("api_key", "app_key"),
+        (false, true) => ("app_key", "api_key"),
+        _ => return,
+    };
+    issues.push(config_issue(
+        "warning",
+        &format!("{prefix}{missing}"),
+        format!("is missing while {set} is set; it falls back to flags or the environment"),
+    ));
+}
+
+fn config_issue(severity: &str, key: &str, message: String) -> Value {
+    json!({ "severity": severity, "key": key, "message": message })
+}
+
+fn default_config_path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var_os("HOME")
+                .or_else(|| env::var_os("USERPROFILE"))
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+    Some(config_home.join("ddq").join("config.toml"))
+}
+
+/// Upper-cases an environment name for credential variables, e.g. `eu-west` ->
+/// `EU_WEST` for `DD_API_KEY_EU_WEST`.
+fn env_var_suffix(name: &str) -> String {
+    name.chars()
+        .map(|ch| {
+            if ch.is_ascii_alphanumeric() {
+                ch.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Short site names people type for regional Datadog sites.
+const SITE_SHORTCUTS: [(&str, &str); 5] = [
+    ("eu", "datadoghq.eu"),
+    ("us3", "us3.datadoghq.com"),
+    ("us5", "us5.datadoghq.com"),
+    ("ap1", "ap1.datadoghq.com"),
+    ("gov", "ddog-gov.com"),
+];
+
 fn normalize_base_url(site: &str) -> Result<String> {
     let cleaned = site.trim().trim_end_matches('/');
     if cleaned.is_empty() {
         return Err(anyhow!("Datadog site value is empty."));
     }
+    let cleaned = SITE_SHORTCUTS
+        .iter()
+        .find(|(shortcut, _)| cleaned.eq_ignore_ascii_case(shortcut))
+        .map_or(cleaned, |(_, site)| site);
 
     if cleaned.starts_with("http://") || cleaned.starts_with("https://") {
         return Ok(cleaned.to_string());
@@ -87,3 +461,413 @@ fn normalize_base_url(site: &str) -> Result<String> {
 
     Ok(format!("https://api.{cleaned}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+
+    use anyhow::{Result, anyhow};
+    use clap::Parser;
+
+    use super::{Config, FileConfig, check_config_text, normalize_base_url};
+    use crate::cli::Cli;
+    use crate::credentials::{CommandOutput, CommandRunner, CredentialStore, ShellRunner};
+
+    #[derive(Default)]
+    struct MemoryStore {
+        secrets: RefCell<HashMap<String, String>>,
+    }
+
+    impl CredentialStore for MemoryStore {
+        fn get(&self, account: &str) -> Result<Option<String>> {
+            Ok(self.secrets.borrow().get(account).cloned())
+        }
+
+        fn set(&self, account: &str, secret: &str) -> Result<()> {
+            self.secrets
+                .borrow_mut()
+                .insert(account.to_string(), secret.to_string());
+            Ok(())
+        }
+    }
+
+    struct UnavailableStore;
+
+    impl CredentialStore for UnavailableStore {
+        fn get(&self, _account: &str) -> Result<Option<String>> {
+            Err(anyhow!("OS keyring is unavailable."))
+        }
+
+        fn set(&self, _account: &str, _secret: &str) -> Result<()> {
+            Err(anyhow!("OS keyring is unavailable."))
+        }
+    }
+
+    fn cli(args: &[&str]) -> Cli {
+        Cli::try_parse_from(["ddq"].iter().chain(args).chain(&["metrics", "q"])).unwrap()
+    }
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn file_config(contents: &str) -> FileConfig {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn environment_resolves_site_and_scoped_credentials() {
+        let file = file_config(
+            r#"
+            [environments]
+            prod = "us3.datadoghq.com"
+            staging = "datadoghq.eu"
+            "#,
+        );
+        let env = env(&[
+            ("DD_API_KEY", "default-api"),
+            ("DD_APP_KEY", "default-app"),
+            ("DD_API_KEY_PROD", "prod-api"),
+            ("DD_APP_KEY_PROD", "prod-app"),
+        ]);
+
+        let prod = Config::resolve(
+            &cli(&["--environment", "prod"]),
+            &file,
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .unwrap();
+        assert_eq!(prod.base_url, "https://api.us3.datadoghq.com");
+        assert_eq!(prod.api_key, "prod-api");
+        assert_eq!(prod.app_key, "prod-app");
+
+        let staging = Config::resolve(
+            &cli(&["--environment", "staging"]),
+            &file,
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .unwrap();
+        assert_eq!(staging.base_url, "https://api.datadoghq.eu");
+        assert_eq!(staging.api_key, "default-api");
+    }
+
+    struct UnreachableRunner;
+
+    impl CommandRunner for UnreachableRunner {
+        fn run(&self, command: &str) -> Result<CommandOutput> {
+            panic!("credential command `{command}` should not run");
+        }
+    }
+
+    #[test]
+    fn lower_priority_key_sources_are_not_consulted() {
+        let mut cli = cli(&["--api-key", "flag-api", "--app-key", "flag-app"]);
+        cli.api_key_command = Some("vault read -field=api_key secret/datadog".to_string());
+        cli.app_key_file = Some("/nonexistent/ddq-app-key".into());
+
+        let config = Config::resolve(
+            &cli,
+            &FileConfig::default(),
+            None,
+            &UnreachableRunner,
+            env(&[]),
+        )
+        .unwrap();
+
+        assert_eq!(config.api_key, "flag-api");
+        assert_eq!(config.app_key, "flag-app");
+    }
+
+    #[test]
+    fn key_files_are_trimmed_and_sit_below_key_flags() {
+        let dir = std::env::temp_dir().join(format!("ddq-key-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let api_path = dir.join("api");
+        let app_path = dir.join("app");
+        std::fs::write(&api_path, "  file-api\n").unwrap();
+        std::fs::write(&app_path, "file-app\n").unwrap();
+        let api_arg = api_path.to_str().unwrap();
+        let app_arg = app_path.to_str().unwrap();
+        let env = env(&[("DD_API_KEY", "env-api"), ("DD_APP_KEY", "env-app")]);
+
+        let config = Config::resolve(
+            &cli(&["--api-key-file", api_arg, "--app-key-file", app_arg]),
+            &FileConfig::default(),
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .unwrap();
+        assert_eq!(config.api_key, "file-api");
+        assert_eq!(config.app_key, "file-app");
+
+        let config = Config::resolve(
+            &cli(&["--api-key-file", api_arg, "--app-key", "flag-app"]),
+            &FileConfig::default(),
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .unwrap();
+        assert_eq!(config.app_key, "flag-app");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        let err = Config::resolve(
+            &cli(&["--api-key-file", api_arg]),
+            &FileConfig::default(),
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains(api_arg));
+    }
+
+    #[test]
+    fn unknown_environment_lists_available_names() {
+        let file = file_config("[environments]\nprod = \"us3.datadoghq.com\"\n");
+
+        let err = Config::resolve(
+            &cli(&["--environment", "qa"]),
+            &file,
+            None,
+            &ShellRunner,
+            env(&[]),
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.to_string().contains("Available environments: [prod]"));
+    }
+
+    #[test]
+    fn environment_conflicts_with_site() {
+        let result = Cli::try_parse_from([
+            "ddq",
+            "--environment",
+            "prod",
+            "--site",
+            "datadoghq.eu",
+            "metrics",
+            "q",
+        ]);
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn keyring_credentials_sit_between_flags_and_env() {
+        let store = MemoryStore::default();
+        store.set("api_key", "stored-api").unwrap();
+        store.set("app_key", "stored-app").unwrap();
+        let env = env(&[("DD_API_KEY", "env-api"), ("DD_APP_KEY", "env-app")]);
+
+        let config = Config::resolve(
+            &cli(&["--use-keyring", "--app-key", "flag-app"]),
+            &FileConfig::default(),
+            Some(&store),
+            &ShellRunner,
+            &env,
+        )
+        .unwrap();
+
+        assert_eq!(config.api_key, "stored-api");
+        assert_eq!(config.app_key, "flag-app");
+    }
+
+    #[test]
+    fn keyring_is_not_read_when_flags_supply_both_keys() {
+        let config = Config::resolve(
+            &cli(&[
+                "--use-keyring",
+                "--api-key",
+                "flag-api",
+                "--app-key",
+                "flag-app",
+            ]),
+            &FileConfig::default(),
+            Some(&UnavailableStore),
+            &ShellRunner,
+            env(&[]),
+        )
+        .unwrap();
+
+        assert_eq!(config.api_key, "flag-api");
+    }
+
+    #[test]
+    fn unavailable_keyring_is_reported() {
+        let err = Config::resolve(
+            &cli(&["--use-keyring"]),
+            &FileConfig::default(),
+            Some(&UnavailableStore),
+            &ShellRunner,
+            env(&[]),
+        )
+        .err()
+        .unwrap();
+
+        assert!(err.to_string().contains("OS keyring is unavailable"));
+    }
+
+    #[test]
+    fn config_file_sits_below_flags_and_env() {
+        let file = file_config(
+            r#"
+            api_key = "file-api"
+            app_key = "file-app"
+            site = "datadoghq.eu"
+            retries = 7
+            retry_backoff_ms = 100
+            "#,
+        );
+
+        let config = Config::resolve(&cli(&[]), &file, None, &ShellRunner, env(&[])).unwrap();
+        assert_eq!(config.api_key, "file-api");
+        assert_eq!(config.app_key, "file-app");
+        assert_eq!(config.base_url, "https://api.datadoghq.eu");
+        assert_eq!(config.retry.max_retries, 7);
+        assert_eq!(config.retry.backoff_ms, 100);
+        assert_eq!(config.retry.max_backoff_ms, 5_000);
+
+        let config = Config::resolve(
+            &cli(&["--app-key", "flag-app", "--retries", "1"]),
+            &file,
+            None,
+            &ShellRunner,
+            env(&[("DD_API_KEY", "env-api"), ("DD_SITE", "us5.datadoghq.com")]),
+        )
+        .unwrap();
+        assert_eq!(config.api_key, "env-api");
+        assert_eq!(config.app_key, "flag-app");
+        assert_eq!(config.base_url, "https://api.us5.datadoghq.com");
+        assert_eq!(config.retry.max_retries, 1);
+    }
+
+    #[test]
+    fn profile_values_win_over_env_and_top_level_file() {
+        let file = file_config(
+            r#"
+            api_key = "file-api"
+            site = "datadoghq.com"
+
+            [profiles.eu]
+            api_key = "eu-api"
+            site = "datadoghq.eu"
+
+            [profiles.us]
+            app_key = "us-app"
+            "#,
+        );
+        let env = env(&[("DD_API_KEY", "env-api"), ("DD_APP_KEY", "env-app")]);
+
+        let config =
+            Config::resolve(&cli(&["--profile", "eu"]), &file, None, &ShellRunner, &env).unwrap();
+        assert_eq!(config.api_key, "eu-api");
+        assert_eq!(config.app_key, "env-app");
+        assert_eq!(config.base_url, "https://api.datadoghq.eu");
+
+        let err = Config::resolve(
+            &cli(&["--profile", "apac"]),
+            &file,
+            None,
+            &ShellRunner,
+            &env,
+        )
+        .err()
+        .unwrap();
+        assert!(
+            err.to_string()
+                .contains("Unknown profile `apac`. Available profiles: [eu, us].")
+        );
+    }
+
+    #[test]
+    fn config_check_accepts_a_good_config() {
+        let issues = check_config_text(
+            r#"
+            site = "us3.datadoghq.com"
+            retries = 5
+
+            [environments]
+            prod = "datadoghq.eu"
+
+            [profiles.eu]
+            api_key = "eu-api"
+            app_key = "eu-app"
+            site = "eu"
+            "#,
+        );
+
+        assert_eq!(issues, Vec::<serde_json::Value>::new());
+    }
+
+    #[test]
+    fn config_check_flags_unknown_keys_bad_types_and_malformed_profiles() {
+        let issues = check_config_text(
+            r#"
+            retries = -1
+            colour = "blue"
+
+            [profiles]
+            broken = "not a table"
+
+            [profiles.half]
+            api_key = "only-the-api-key"
+            apikey = "typo"
+            "#,
+        );
+
+        let found: Vec<(&str, &str)> = issues
+            .iter()
+            .map(|issue| {
+                (
+                    issue["severity"].as_str().unwrap(),
+                    issue["key"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                ("error", "colour"),
+                ("error", "profiles.broken"),
+                ("error", "profiles.half.apikey"),
+                ("warning", "profiles.half.app_key"),
+                ("error", "retries"),
+            ]
+        );
+        assert!(
+            issues[0]["message"]
+                .as_str()
+                .unwrap()
+                .starts_with("unknown field `colour`, expected one of `api_key`")
+        );
+        assert_eq!(check_config_text("site = ")[0]["severity"], "error");
+    }
+
+    #[test]
+    fn site_shortcuts_resolve_to_regional_api_hosts() {
+        for (site, expected) in [
+            ("eu", "https://api.datadoghq.eu"),
+            ("us3", "https://api.us3.datadoghq.com"),
+            ("us5", "https://api.us5.datadoghq.com"),
+            ("AP1", "https://api.ap1.datadoghq.com"),
+            ("gov", "https://api.ddog-gov.com"),
+            ("datadoghq.com", "https://api.datadoghq.com"),
+            ("us9", "https://api.us9"),
+        ] {
+            assert_eq!(normalize_base_url(site).unwrap(), expected, "{site}");
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::process::Command;
+
+use anyhow::{Context, Result, anyhow};
+
+/// Keychain service name under which `ddq login` stores credentials.
+pub const KEYRING_SERVICE: &str = "ddq";
+pub const API_KEY_ACCOUNT: &str = "api_key";
+pub const APP_KEY_ACCOUNT: &str = "app_key";
+
+/// Secret storage used for `--use-keyring` and `ddq login`.
+pub trait CredentialStore {
+    fn get(&self, account: &str) -> Result<Option<String>>;
+    fn set(&self, account: &str, secret: &str) -> Result<()>;
+}
+
+/// Credential store backed by the OS keychain (macOS Keychain, Windows Credential
+/// Manager, the Secret Service on Linux such as GNOME Keyring or KWallet), so
+/// stored keys survive logout and reboot.
+pub struct KeyringStore {
+    service: String,
+}
+
+impl Default for KeyringStore {
+    fn default() -> Self {
+        Self {
+            service: KEYRING_SERVICE.to_string(),
+        }
+    }
+}
+
+impl CredentialStore for KeyringStore {
+    fn get(&self, account: &str) -> Result<Option<String>> {
+        let entry =
+            keyring::Entry::new(&self.service, account).context("OS keyring is unavailable.")?;
+        match entry.get_password() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(err) => Err(err).with_context(|| {
+                format!(
+                    "Failed reading `{account}` from the OS keyring (service `{}`).",
+                    self.service
+                )
+            }),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &str) -> Result<()> {
+        keyring::Entry::new(&self.service, account)
+            .and_then(|entry| entry.set_password(secret))
+            .with_context(|| {
+                format!(
+                    "Failed storing `{account}` in the OS keyring (service `{}`).",
+                    self.service
+                )
+            })
+    }
+}
+
+/// What a credential helper command printed and whether it succeeded.
+pub struct CommandOutput {
+    pub success: bool,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+/// Runs the helper commands behind `--api-key-command`/`--app-key-command`.
+pub trait CommandRunner {
+    fn run(&self, command: &str) -> Result<CommandOutput>;
+}
+
+/// Runs commands through the platform shell (`sh -c`, or `cmd /C` on Windows).
+pub struct ShellRunner;
+
+impl CommandRunner for ShellRunner {
+    fn run(&self, command: &str) -> Result<CommandOutput> {
+        let (shell, flag) = if cfg!(windows) {
+            ("cmd", "/C")
+        } else {
+            ("sh", "-c")
+        };
+        let output = Command::new(shell)
+            .args([flag, command])
+            .output()
+            .with_context(|| format!("Failed starting credential command `{command}`"))?;
+        Ok(CommandOutput {
+            success: output.status.success(),
+            stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        })
+    }
+}
+
+/// Runs a credential helper and returns its trimmed stdout. A failing or silent
+/// helper is an error carrying its stderr.
+pub fn key_from_command(runner: &dyn CommandRunner, command: &str) -> Result<String> {
+    let output = runner.run(command)?;
+    if !output.success {
+        return Err(anyhow!(
+            "Credential command `{command}` failed: {}",
+            output.stderr.trim()
+        ));
+    }
+    let key = output.stdout.trim();
+    if key.is_empty() {
+        return Err(anyhow!("Credential command `{command}` printed nothing."));
+    }
+    Ok(key.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Result;
+
+    use super::{CommandOutput, CommandRunner, key_from_command};
+
+    struct FakeRunner {
+        success: bool,
+        stdout: &'static str,
+        stderr: &'static str,
+    }
+
+    impl CommandRunner for FakeRunner {
+        fn run(&self, _command: &str) -> Result<CommandOutput> {
+            Ok(CommandOutput {
+                success: self.success,
+                stdout: self.stdout.to_string(),
+                stderr: self.stderr.to_string(),
+            })
+        }
+    }
+
+    #[test]
+    fn key_from_command_trims_helper_stdout() {
+        let runner = FakeRunner {
+            success: true,
+            stdout: "  secret-api\n",
+            stderr: "",
+        };
+
+        assert_eq!(
+            key_from_command(&runner, "vault read -field=key dd").unwrap(),
+            "secret-api"
+        );
+    }
+
+    #[test]
+    fn key_from_command_reports_helper_failures() {
+        let failing = FakeRunner {
+            success: false,
+            stdout: "",
+            stderr: "permission denied\n",
+        };
+        let err = key_from_command(&failing, "vault read dd").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Credential command `vault read dd` failed: permission denied"
+        );
+
+        let silent = FakeRunner {
+            success: true,
+            stdout: "\n",
+            stderr: "",
+        };
+        assert!(key_from_command(&silent, "true").is_err());
+    }
+}
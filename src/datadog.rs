@@ -1,22 +1,121 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::time::Duration;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
+use chrono::{DateTime, Utc};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use rand::rngs::StdRng;
+use rand::{Rng, RngExt};
 use reqwest::{Method, StatusCode, Url};
 use serde_json::{Value, json};
 use tokio::time::sleep;
 
+use crate::cli::{BackoffStrategy, DuplicateParamPolicy, RateLimitPolicy, RetryJitter};
 use crate::config::{Config, RetryConfig};
+use crate::records::lookup_path;
+use crate::tls;
+use crate::warnings::Warnings;
 
 pub struct DatadogClient {
     http: reqwest::Client,
+    /// Client for non-Datadog targets (webhooks), which `--pin-sha256` does not cover.
+    unpinned_http: reqwest::Client,
     base_url: String,
     api_key: String,
     app_key: String,
     retry: RetryConfig,
     timeout_seconds: u64,
+    total_timeout_seconds: Option<u64>,
+    compress_request: bool,
+    duplicate_params: DuplicateParamPolicy,
+    correlation_id: Option<String>,
+    deadline: Option<Instant>,
+    /// HTTP version negotiated on the most recent Datadog response.
+    http_version: Mutex<Option<reqwest::Version>>,
+    /// Describe requests instead of sending them.
+    dry_run: bool,
+    /// Detail of the `-v` trace on stderr; 0 prints nothing.
+    verbosity: u8,
+    /// Cap on response bodies in the `-vv` trace.
+    max_body_log_bytes: usize,
+    /// 429s waited out under `--on-rate-limit wait` so far.
+    rate_limit_waits: AtomicU32,
+    /// Requests sent so far, for `--timings`.
+    requests: AtomicU32,
+    /// HTTP calls made so far, retries included.
+    attempts: AtomicU32,
+    /// Time spent sending requests so far, retries and backoff included.
+    request_ms: AtomicU64,
+    warnings: Warnings,
 }
 
+pub struct RawRequest {
+    pub method: String,
+    pub path: String,
+    pub params: Vec<(String, String)>,
+    pub body: Option<Value>,
+    pub idempotency_key: Option<String>,
+    pub await_completion: Option<AwaitCompletion>,
+    /// Error statuses whose response body is returned as a success.
+    pub ignore_statuses: Vec<u16>,
+    /// Send a top-level JSON array body in requests of at most this many items.
+    pub chunk_size: Option<usize>,
+}
+
+/// Polling settings for asynchronous operations that answer `202 Accepted`.
+pub struct AwaitCompletion {
+    /// Dotted path of the status value in each poll response.
+    pub status_field: String,
+    pub poll_interval_ms: u64,
+    pub timeout_seconds: u64,
+}
+
+/// A successful Datadog response with the metadata needed by callers that look
+/// past the body (async polling).
+struct ApiResponse {
+    status: StatusCode,
+    location: Option<String>,
+    body: Value,
+}
+
+const API_KEY_HEADER: &str = "DD-API-KEY";
+const APP_KEY_HEADER: &str = "DD-APPLICATION-KEY";
+
+/// Stands in for an error response body that could not be read or is not UTF-8.
+const UNDECODABLE_BODY: &str = "<undecodable body>";
+
+/// Request bodies at least this large are gzipped under `--compress-request`.
+const COMPRESS_THRESHOLD_BYTES: usize = 1_024;
+
+/// Largest `page[limit]` the events API accepts.
+const EVENTS_MAX_PAGE_SIZE: u32 = 1_000;
+
+#[derive(Clone)]
+pub struct EventsQuery {
+    pub query: Option<String>,
+    pub from: String,
+    pub to: String,
+    /// Total number of events to return across pages.
+    pub limit: u32,
+    /// Events per request; defaults to `limit`, capped at the API maximum.
+    pub page_size: Option<u32>,
+    /// Stop after this many pages even if `limit` is not reached.
+    pub max_pages: Option<u32>,
+    pub sort: String,
+    /// `normal` or `low`.
+    pub priority: Option<String>,
+    /// `error`, `warning`, `info` or `success`.
+    pub alert_type: Option<String>,
+}
+
+#[derive(Clone)]
 pub struct LogsQuery {
     pub query: String,
     pub from: String,
@@ -24,6 +123,231 @@ pub struct LogsQuery {
     pub limit: u32,
     pub sort: String,
     pub cursor: Option<String>,
+    /// Follow `meta.page.after` across pages of `limit` logs each.
+    pub all: bool,
+    /// With `all`, stop after this many pages.
+    pub max_pages: Option<u32>,
+}
+
+/// A logs aggregation: `compute` specs (`count`, `avg:@duration`) over the logs
+/// matching `query`, split by the `group_by` facets.
+pub struct LogsAggregation {
+    pub query: String,
+    pub from: String,
+    pub to: String,
+    pub compute: Vec<String>,
+    pub group_by: Vec<String>,
+    /// Groups returned per facet.
+    pub group_limit: u32,
+}
+
+impl LogsAggregation {
+    /// A single ungrouped count of the logs matching `query`.
+    pub fn count(query: String, from: String, to: String) -> Self {
+        Self {
+            query,
+            from,
+            to,
+            compute: vec!["count".to_string()],
+            group_by: Vec::new(),
+            group_limit: 0,
+        }
+    }
+}
+
+/// A v2 timeseries query: named metric queries combined by optional formulas.
+pub struct TimeseriesQuery {
+    /// Metric queries, each optionally prefixed with a name (`a:avg:system.cpu.user{*}`);
+    /// unnamed ones are called `query1`, `query2`, ... by position.
+    pub queries: Vec<String>,
+    /// Formula expressions over the query names (`a / 100`).
+    pub formulas: Vec<String>,
+    pub from_ms: i64,
+    pub to_ms: i64,
+    pub interval_ms: Option<u64>,
+}
+
+/// The HTTP request a typed command sends, before credentials, retries and
+/// pagination are applied. Printed as-is by `--explain-request`.
+#[derive(Debug)]
+pub struct RequestPlan {
+    pub method: Method,
+    pub path: &'static str,
+    pub params: Vec<(String, String)>,
+    pub body: Option<Value>,
+}
+
+impl RequestPlan {
+    pub fn to_json(&self) -> Value {
+        json!({
+            "method": self.method.as_str(),
+            "path": self.path,
+            "params": self.params,
+            "body": self.body,
+        })
+    }
+}
+
+struct RequestOptions {
+    idempotency_key: Option<String>,
+    /// Whether a request that may already have reached Datadog can be sent again
+    /// after a transport failure, an unreadable body, or a 408/5xx.
+    replayable: bool,
+    /// Send the DD-API-KEY/DD-APPLICATION-KEY headers; off for non-Datadog targets.
+    authenticate: bool,
+    extra_headers: Vec<(String, String)>,
+    /// Non-2xx statuses treated like success: the body is returned, not an error.
+    ignore_statuses: Vec<u16>,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self {
+            idempotency_key: None,
+            replayable: true,
+            authenticate: true,
+            extra_headers: Vec::new(),
+            ignore_statuses: Vec::new(),
+        }
+    }
+}
+
+struct PreparedRequest {
+    url: Url,
+    headers: Vec<(String, String)>,
+    /// The encoded body and whether it was gzipped.
+    payload: Option<(Vec<u8>, bool)>,
+}
+
+/// Adds the milliseconds between `start` and drop to a running total, so every
+/// return path of a request loop is counted.
+struct ElapsedTimer<'a> {
+    started: Instant,
+    total_ms: &'a AtomicU64,
+}
+
+impl<'a> ElapsedTimer<'a> {
+    fn start(total_ms: &'a AtomicU64) -> Self {
+        Self {
+            started: Instant::now(),
+            total_ms,
+        }
+    }
+}
+
+impl Drop for ElapsedTimer<'_> {
+    fn drop(&mut self) {
+        let elapsed = self.started.elapsed().as_millis() as u64;
+        self.total_ms.fetch_add(elapsed, Ordering::Relaxed);
+    }
+}
+
+/// Why a request is being retried, as recorded in `--retry-log`.
+enum RetryTrigger<'a> {
+    Status(StatusCode),
+    Transport(&'a reqwest::Error),
+    IncompleteBody(StatusCode),
+}
+
+impl RetryTrigger<'_> {
+    /// A short cause for trace messages, e.g. `503`.
+    fn label(&self) -> String {
+        match self {
+            Self::Status(status) => status.as_u16().to_string(),
+            Self::Transport(err) if err.is_timeout() => "timeout".to_string(),
+            Self::Transport(_) => "transport error".to_string(),
+            Self::IncompleteBody(status) => format!("incomplete body ({})", status.as_u16()),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Self::Status(status) => json!({ "status": status.as_u16() }),
+            Self::Transport(err) => json!({ "transport_error": err.to_string() }),
+            Self::IncompleteBody(status) => {
+                json!({ "status": status.as_u16(), "incomplete_body": true })
+            }
+        }
+    }
+}
+
+pub struct ProcessesQuery {
+    pub search: Option<String>,
+    pub tags: Option<String>,
+    /// Unix seconds.
+    pub from: i64,
+    /// Unix seconds.
+    pub to: i64,
+    pub limit: u32,
+    pub cursor: Option<String>,
+}
+
+pub struct MonitorsQuery {
+    pub name: Option<String>,
+    pub tags: Option<String>,
+    pub monitor_tags: Option<String>,
+    pub page_size: Option<u32>,
+    pub page: Option<u32>,
+}
+
+pub struct HostsQuery {
+    pub filter: Option<String>,
+    pub sort_field: Option<String>,
+    pub sort_dir: Option<String>,
+    pub count: Option<u32>,
+    pub start: Option<u32>,
+}
+
+pub struct SloQuery {
+    pub query: Option<String>,
+    pub tags_query: Option<String>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// An access-control lookup: roles, or the restriction policy on one resource.
+pub enum RbacQuery {
+    /// Roles, `page_size` per page from the zero-based `page_number`. With `all`,
+    /// later pages are fetched until one comes back short.
+    Roles {
+        filter: Option<String>,
+        page_size: u32,
+        page_number: u32,
+        all: bool,
+        max_pages: Option<u32>,
+    },
+    /// `resource_id` is `<type>:<id>`, e.g. `dashboard:abc-def-ghi`.
+    RestrictionPolicy { resource_id: String },
+}
+
+/// A request to rehydrate logs from an archive into a searchable index.
+pub struct LogsRehydration {
+    pub archive_id: String,
+    pub name: String,
+    pub query: String,
+    pub from: String,
+    pub to: String,
+    /// Poll the job until it leaves a pending state instead of returning the
+    /// creation response.
+    pub wait: Option<AwaitCompletion>,
+}
+
+pub struct LogsMetricDefinition {
+    pub name: String,
+    pub query: String,
+    pub aggregation_type: String,
+    pub path: Option<String>,
+    pub include_percentiles: bool,
+    /// Group-by attribute paths as `PATH` or `PATH:TAG_NAME`.
+    pub group_by: Vec<String>,
+}
+
+pub struct MetricSubmission {
+    pub metric: String,
+    pub value: f64,
+    pub timestamp: i64,
+    pub metric_type: String,
+    pub tags: Vec<String>,
 }
 
 #[derive(Debug)]
@@ -40,11 +364,20 @@ pub enum DatadogError {
     Retryable {
         status: Option<u16>,
         message: String,
+        /// What failed when no response arrived: see `transport_kind`.
+        transport_kind: Option<&'static str>,
     },
     Api {
         status: u16,
         body: String,
     },
+    /// A chunked raw request stopped at chunk `chunk` (1-based); `completed`
+    /// holds the responses of the chunks sent before it.
+    ChunkFailed {
+        completed: Vec<Value>,
+        chunk: usize,
+        source: Box<DatadogError>,
+    },
 }
 
 impl Display for DatadogError {
@@ -67,7 +400,9 @@ impl Display for DatadogError {
                     write!(f, "Datadog rate limited request (429): {body}")
                 }
             }
-            Self::Retryable { status, message } => {
+            Self::Retryable {
+                status, message, ..
+            } => {
                 if let Some(status) = status {
                     write!(f, "Datadog retryable upstream error ({status}): {message}")
                 } else {
@@ -77,6 +412,15 @@ impl Display for DatadogError {
             Self::Api { status, body } => {
                 write!(f, "Datadog API error ({status}): {body}")
             }
+            Self::ChunkFailed {
+                completed,
+                chunk,
+                source,
+            } => write!(
+                f,
+                "Chunk {chunk} failed after {} completed chunk(s): {source}",
+                completed.len()
+            ),
         }
     }
 }
@@ -86,43 +430,87 @@ impl std::error::Error for DatadogError {}
 impl DatadogClient {
     pub fn new(config: Config) -> Self {
         Self {
-            http: reqwest::Client::new(),
+            http: if config.pins.is_empty() {
+                reqwest::Client::new()
+            } else {
+                tls::pinned_http_client(&config.pins)
+            },
+            unpinned_http: reqwest::Client::new(),
             base_url: config.base_url,
             api_key: config.api_key,
             app_key: config.app_key,
             retry: config.retry,
             timeout_seconds: config.timeout_seconds,
+            total_timeout_seconds: config.total_timeout_seconds,
+            compress_request: config.compress_request,
+            duplicate_params: config.duplicate_params,
+            correlation_id: config.correlation_id,
+            deadline: config
+                .total_timeout_seconds
+                .map(|seconds| Instant::now() + Duration::from_secs(seconds)),
+            http_version: Mutex::new(None),
+            dry_run: config.dry_run,
+            verbosity: config.verbosity,
+            max_body_log_bytes: config.max_body_log_bytes,
+            rate_limit_waits: AtomicU32::new(0),
+            requests: AtomicU32::new(0),
+            attempts: AtomicU32::new(0),
+            request_ms: AtomicU64::new(0),
+            warnings: Warnings::default(),
         }
     }
 
-    pub async fn query_logs(&self, query: LogsQuery) -> Result<Value, DatadogError> {
-        let sort = match query.sort.to_ascii_lowercase().as_str() {
-            "asc" => "timestamp",
-            "desc" => "-timestamp",
-            other => {
-                return Err(DatadogError::InvalidRequest(format!(
-                    "Invalid sort `{other}`. Use `asc` or `desc` for logs queries."
-                )));
-            }
-        };
+    /// `{"elapsed_ms", "attempts", "requests"}` over every request sent so far;
+    /// `elapsed_ms` sums each request's time including retries and backoff.
+    pub fn timings(&self) -> Value {
+        json!({
+            "elapsed_ms": self.request_ms.load(Ordering::Relaxed),
+            "attempts": self.attempts.load(Ordering::Relaxed),
+            "requests": self.requests.load(Ordering::Relaxed),
+        })
+    }
 
-        let mut page = json!({ "limit": query.limit });
-        if let Some(cursor) = query.cursor {
-            page["cursor"] = json!(cursor);
+    /// The HTTP version negotiated with Datadog on the latest response, such as
+    /// `HTTP/1.1` or `HTTP/2.0`; `None` before any response has arrived.
+    pub fn http_version(&self) -> Option<String> {
+        self.http_version
+            .lock()
+            .ok()
+            .and_then(|version| version.map(|version| format!("{version:?}")))
+    }
+
+    pub async fn query_logs(&self, mut query: LogsQuery) -> Result<Value, DatadogError> {
+        if !query.all {
+            return self.send_plan(logs_request(&query)?).await;
         }
 
-        let body = json!({
-            "filter": {
-                "query": query.query,
-                "from": query.from,
-                "to": query.to
-            },
-            "sort": sort,
-            "page": page
-        });
+        let mut merged: Option<Value> = None;
+        let mut records = Vec::new();
+        let mut pages = 0;
+        let cursor = loop {
+            let mut page = match self.send_plan(logs_request(&query)?).await {
+                Ok(page) => page,
+                Err(_) if self.cut_short(&mut merged) => break query.cursor.take(),
+                Err(err) => return Err(err),
+            };
+            if let Some(Value::Array(data)) = page.get_mut("data") {
+                records.append(data);
+            }
+            let cursor = next_cursor(&page);
+            pages += 1;
+            let base = merged.get_or_insert(page);
 
-        self.send_json(Method::POST, "/api/v2/logs/events/search", None, Some(body))
-            .await
+            let Some(next) = cursor else {
+                break None;
+            };
+            if query.max_pages.is_some_and(|max| pages >= max) || self.deadline_exceeded() {
+                base["complete"] = json!(false);
+                break Some(next);
+            }
+            query.cursor = Some(next);
+        };
+
+        Ok(merge_pages(merged, records, pages, cursor))
     }
 
     pub async fn query_metrics(
@@ -131,185 +519,840 @@ impl DatadogClient {
         from: i64,
         to: i64,
     ) -> Result<Value, DatadogError> {
-        let params = vec![
-            ("query".to_string(), query.to_string()),
-            ("from".to_string(), from.to_string()),
-            ("to".to_string(), to.to_string()),
-        ];
-
-        self.send_json(Method::GET, "/api/v1/query", Some(params), None)
-            .await
+        self.send_plan(metrics_request(query, from, to)).await
     }
 
-    pub async fn query_events(
+    pub async fn aggregate_logs(
         &self,
-        query: Option<String>,
-        from: String,
-        to: String,
-        limit: u32,
-        sort: String,
+        aggregation: &LogsAggregation,
     ) -> Result<Value, DatadogError> {
-        let sort = match sort.to_ascii_lowercase().as_str() {
-            "asc" => "timestamp",
-            "desc" => "-timestamp",
-            other => {
-                return Err(DatadogError::InvalidRequest(format!(
-                    "Invalid sort `{other}`. Use `asc` or `desc` for events queries."
-                )));
-            }
-        };
+        self.send_plan(logs_aggregate_request(aggregation)?).await
+    }
 
-        let mut params = vec![
-            ("filter[from]".to_string(), from),
-            ("filter[to]".to_string(), to),
-            ("page[limit]".to_string(), limit.to_string()),
-            ("sort".to_string(), sort.to_string()),
-        ];
-        if let Some(query) = query {
-            params.push(("filter[query]".to_string(), query));
-        }
+    /// Runs a [`LogsAggregation::count`] and returns `{"count": N}`; the result is
+    /// exact, unlike the size of a fetched page.
+    pub async fn count_logs(&self, aggregation: &LogsAggregation) -> Result<Value, DatadogError> {
+        let response = self.aggregate_logs(aggregation).await?;
+        let count = aggregate_count(&response).ok_or_else(|| DatadogError::Api {
+            status: 200,
+            body: format!("Logs aggregate response has no count: {response}"),
+        })?;
+        Ok(json!({ "count": count }))
+    }
 
-        self.send_json(Method::GET, "/api/v2/events", Some(params), None)
-            .await
+    pub async fn query_timeseries(&self, query: &TimeseriesQuery) -> Result<Value, DatadogError> {
+        self.send_plan(timeseries_request(query)?).await
     }
 
-    pub async fn raw(
+    /// Adds a `metric_unit` object (`unit`, `per_unit`) to each series of a metrics
+    /// query response, from the metric's metadata. `cache` holds metadata already
+    /// fetched so each metric is looked up once per run.
+    pub async fn annotate_units(
         &self,
-        method: &str,
-        path: &str,
-        params: Vec<(String, String)>,
-        body: Option<Value>,
-    ) -> Result<Value, DatadogError> {
-        let method = Method::from_bytes(method.as_bytes())
-            .context("Invalid HTTP method for raw query.")
-            .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
-        let params = if params.is_empty() {
-            None
-        } else {
-            Some(params)
+        response: &mut Value,
+        cache: &mut HashMap<String, Value>,
+    ) -> Result<(), DatadogError> {
+        let Some(Value::Array(series)) = response.get_mut("series") else {
+            return Ok(());
         };
-        self.send_json(method, path, params, body).await
+        for entry in series {
+            let Some(metric) = entry.get("metric").and_then(Value::as_str) else {
+                continue;
+            };
+            if !cache.contains_key(metric) {
+                let metadata = self
+                    .send_json(
+                        Method::GET,
+                        &format!("/api/v1/metrics/{}", path_segment("metric", metric)?),
+                        None,
+                        None,
+                    )
+                    .await?;
+                let unit = json!({
+                    "unit": metadata.get("unit").cloned().unwrap_or(Value::Null),
+                    "per_unit": metadata.get("per_unit").cloned().unwrap_or(Value::Null),
+                });
+                cache.insert(metric.to_string(), unit);
+            }
+            entry["metric_unit"] = cache[metric].clone();
+        }
+        Ok(())
     }
 
-    async fn send_json(
+    pub async fn submit_metrics(
         &self,
-        method: Method,
-        path: &str,
-        params: Option<Vec<(String, String)>>,
-        body: Option<Value>,
+        submission: MetricSubmission,
     ) -> Result<Value, DatadogError> {
-        let mut attempt: u32 = 0;
+        let body = build_series_body(submission)?;
+        self.send_mutation(Method::POST, "/api/v2/series", body)
+            .await
+    }
 
-        loop {
-            let mut url = self
-                .resolve_url(path)
-                .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
-            if let Some(pairs) = &params {
-                let mut query = url.query_pairs_mut();
-                for (key, value) in pairs {
-                    query.append_pair(key, value);
+    pub async fn rehydrate_logs(&self, request: LogsRehydration) -> Result<Value, DatadogError> {
+        let path = format!(
+            "/api/v2/logs/config/archives/{}/rehydrations",
+            path_segment("archive id", &request.archive_id)?
+        );
+        let body = json!({
+            "data": {
+                "type": "rehydrations",
+                "attributes": {
+                    "name": request.name,
+                    "query": request.query,
+                    "from": request.from,
+                    "to": request.to
                 }
             }
+        });
+        // Not replayable: a resent create would start a second rehydration.
+        let options = RequestOptions {
+            replayable: false,
+            ..RequestOptions::default()
+        };
+        let created = self
+            .send_request(Method::POST, &path, None, Some(body), &options)
+            .await?;
 
-            let mut request = self
-                .http
-                .request(method.clone(), url)
-                .header("DD-API-KEY", &self.api_key)
-                .header("DD-APPLICATION-KEY", &self.app_key)
-                .header("Content-Type", "application/json")
-                .header("Accept", "application/json")
-                .timeout(Duration::from_secs(self.timeout_seconds));
+        // A dry run has no rehydration id to poll.
+        let Some(settings) = request.wait.filter(|_| !self.dry_run) else {
+            return Ok(created);
+        };
+        let job_id = lookup_path(&created, "data.id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| {
+                DatadogError::InvalidRequest(
+                    "Datadog did not return a rehydration id to poll.".to_string(),
+                )
+            })?;
+        let job_id = path_segment("rehydration id", job_id)?;
+        self.await_completion(&format!("{path}/{job_id}"), &settings)
+            .await
+    }
 
-            if let Some(payload) = &body {
-                request = request.json(payload);
-            }
+    pub async fn query_processes(&self, query: ProcessesQuery) -> Result<Value, DatadogError> {
+        let mut params = vec![
+            ("from".to_string(), query.from.to_string()),
+            ("to".to_string(), query.to.to_string()),
+            ("page[limit]".to_string(), query.limit.to_string()),
+        ];
+        if let Some(search) = query.search {
+            params.push(("search".to_string(), search));
+        }
+        if let Some(tags) = query.tags {
+            params.push(("tags".to_string(), tags));
+        }
+        if let Some(cursor) = query.cursor {
+            params.push(("page[cursor]".to_string(), cursor));
+        }
+        self.send_json(Method::GET, "/api/v2/processes", Some(params), None)
+            .await
+    }
 
-            let response = match request.send().await {
-                Ok(response) => response,
-                Err(err) => {
-                    if is_retryable_transport_error(&err) && attempt < self.retry.max_retries {
-                        self.sleep_before_retry(attempt, None).await;
-                        attempt += 1;
-                        continue;
-                    }
+    pub async fn list_monitors(&self, query: MonitorsQuery) -> Result<Value, DatadogError> {
+        let mut params = Vec::new();
+        if let Some(name) = query.name {
+            params.push(("name".to_string(), name));
+        }
+        if let Some(tags) = query.tags {
+            params.push(("tags".to_string(), tags));
+        }
+        if let Some(monitor_tags) = query.monitor_tags {
+            params.push(("monitor_tags".to_string(), monitor_tags));
+        }
+        if let Some(page_size) = query.page_size {
+            params.push(("page_size".to_string(), page_size.to_string()));
+            params.push(("page".to_string(), query.page.unwrap_or(0).to_string()));
+        }
+        let params = (!params.is_empty()).then_some(params);
+        self.send_json(Method::GET, "/api/v1/monitor", params, None)
+            .await
+    }
 
-                    if is_retryable_transport_error(&err) {
-                        return Err(DatadogError::Retryable {
-                            status: None,
-                            message: format!(
-                                "Datadog request failed after {} attempt(s): {}",
-                                attempt + 1,
-                                err
-                            ),
-                        });
-                    }
+    pub async fn monitor(&self, id: u64) -> Result<Value, DatadogError> {
+        self.send_json(Method::GET, &format!("/api/v1/monitor/{id}"), None, None)
+            .await
+    }
 
-                    return Err(DatadogError::InvalidRequest(format!(
-                        "Datadog request setup failed: {err}"
-                    )));
-                }
-            };
+    /// Mutes a monitor until the unix time `end`, or until unmuted when `None`.
+    pub async fn mute_monitor(&self, id: u64, end: Option<i64>) -> Result<Value, DatadogError> {
+        self.send_mutation(
+            Method::POST,
+            &format!("/api/v1/monitor/{id}/mute"),
+            build_mute_body(end),
+        )
+        .await
+    }
 
-            let status = response.status();
-            let retry_after_ms = parse_retry_after_ms(response.headers());
-            let text = match response.text().await {
-                Ok(text) => text,
-                Err(err) => {
-                    if attempt < self.retry.max_retries {
-                        self.sleep_before_retry(attempt, None).await;
-                        attempt += 1;
-                        continue;
-                    }
-                    return Err(DatadogError::Retryable {
-                        status: Some(status.as_u16()),
-                        message: format!(
-                            "Failed to read Datadog response after {} attempt(s): {}",
-                            attempt + 1,
-                            err
-                        ),
-                    });
-                }
-            };
+    pub async fn unmute_monitor(&self, id: u64) -> Result<Value, DatadogError> {
+        self.send_mutation(
+            Method::POST,
+            &format!("/api/v1/monitor/{id}/unmute"),
+            json!({}),
+        )
+        .await
+    }
 
-            if status.is_success() {
-                if text.trim().is_empty() {
-                    return Ok(json!({}));
-                }
+    pub async fn list_hosts(&self, query: HostsQuery) -> Result<Value, DatadogError> {
+        let mut params = Vec::new();
+        if let Some(filter) = query.filter {
+            params.push(("filter".to_string(), filter));
+        }
+        if let Some(sort_field) = query.sort_field {
+            params.push(("sort_field".to_string(), sort_field));
+        }
+        if let Some(sort_dir) = query.sort_dir {
+            params.push(("sort_dir".to_string(), sort_dir));
+        }
+        if let Some(count) = query.count {
+            params.push(("count".to_string(), count.to_string()));
+        }
+        if let Some(start) = query.start {
+            params.push(("start".to_string(), start.to_string()));
+        }
+        let params = (!params.is_empty()).then_some(params);
+        self.send_json(Method::GET, "/api/v1/hosts", params, None)
+            .await
+    }
 
-                return match serde_json::from_str::<Value>(&text) {
-                    Ok(value) => Ok(value),
-                    Err(_) => Ok(json!({ "raw": text })),
-                };
+    pub async fn host_totals(&self) -> Result<Value, DatadogError> {
+        self.send_json(Method::GET, "/api/v1/hosts/totals", None, None)
+            .await
+    }
+
+    pub async fn list_slos(&self, query: SloQuery) -> Result<Value, DatadogError> {
+        let mut params = Vec::new();
+        if let Some(search) = query.query {
+            params.push(("query".to_string(), search));
+        }
+        if let Some(tags_query) = query.tags_query {
+            params.push(("tags_query".to_string(), tags_query));
+        }
+        if let Some(limit) = query.limit {
+            params.push(("limit".to_string(), limit.to_string()));
+            params.push(("offset".to_string(), query.offset.unwrap_or(0).to_string()));
+        }
+        let params = (!params.is_empty()).then_some(params);
+        self.send_json(Method::GET, "/api/v1/slo", params, None)
+            .await
+    }
+
+    pub async fn slo(&self, id: &str) -> Result<Value, DatadogError> {
+        let path = format!("/api/v1/slo/{}", encode_path_segment(id));
+        self.send_json(Method::GET, &path, None, None).await
+    }
+
+    /// Fetches an SLO's status history between the unix times `from` and `to`.
+    pub async fn slo_history(&self, id: &str, from: i64, to: i64) -> Result<Value, DatadogError> {
+        let path = format!("/api/v1/slo/{}/history", encode_path_segment(id));
+        let params = vec![
+            ("from_ts".to_string(), from.to_string()),
+            ("to_ts".to_string(), to.to_string()),
+        ];
+        self.send_json(Method::GET, &path, Some(params), None).await
+    }
+
+    pub async fn query_rbac(&self, query: RbacQuery) -> Result<Value, DatadogError> {
+        let (filter, page_size, mut page_number, all, max_pages) = match query {
+            RbacQuery::RestrictionPolicy { resource_id } => {
+                return self
+                    .send_json(
+                        Method::GET,
+                        &restriction_policy_path(&resource_id),
+                        None,
+                        None,
+                    )
+                    .await;
             }
+            RbacQuery::Roles {
+                filter,
+                page_size,
+                page_number,
+                all,
+                max_pages,
+            } => (filter, page_size, page_number, all, max_pages),
+        };
 
-            let body = truncate_for_error(&text);
-            if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
-                return Err(DatadogError::Auth {
-                    status: status.as_u16(),
-                    body,
-                });
+        let mut merged: Option<Value> = None;
+        let mut records = Vec::new();
+        let mut pages = 0;
+        loop {
+            let mut params = vec![
+                ("page[size]".to_string(), page_size.to_string()),
+                ("page[number]".to_string(), page_number.to_string()),
+            ];
+            if let Some(filter) = &filter {
+                params.push(("filter".to_string(), filter.clone()));
+            }
+            let mut page = match self
+                .send_json(Method::GET, "/api/v2/roles", Some(params), None)
+                .await
+            {
+                Ok(page) => page,
+                Err(_) if all && self.cut_short(&mut merged) => break,
+                Err(err) => return Err(err),
+            };
+            if !all {
+                return Ok(page);
             }
 
-            if status == StatusCode::TOO_MANY_REQUESTS {
-                if self.retry.retry_rate_limit && attempt < self.retry.max_retries {
-                    self.sleep_before_retry(attempt, retry_after_ms).await;
-                    attempt += 1;
-                    continue;
+            let fetched = match page.get_mut("data") {
+                Some(Value::Array(data)) => {
+                    let fetched = data.len();
+                    records.append(data);
+                    fetched
                 }
-                return Err(DatadogError::RateLimited {
-                    body,
-                    retry_after_ms,
-                });
+                _ => 0,
+            };
+            let total = lookup_path(&page, "meta.page.total_filtered_count")
+                .and_then(Value::as_u64)
+                .map(|total| total as usize);
+            pages += 1;
+            let base = merged.get_or_insert(page);
+
+            if fetched < page_size as usize || total.is_some_and(|total| records.len() >= total) {
+                break;
             }
+            if max_pages.is_some_and(|max| pages >= max) || self.deadline_exceeded() {
+                base["complete"] = json!(false);
+                break;
+            }
+            page_number += 1;
+        }
 
-            if is_retryable_status(status) {
-                if attempt < self.retry.max_retries {
-                    self.sleep_before_retry(attempt, None).await;
-                    attempt += 1;
-                    continue;
-                }
+        Ok(merge_pages(merged, records, pages, None))
+    }
+
+    pub async fn host_tags(&self, host: &str) -> Result<Value, DatadogError> {
+        self.send_json(Method::GET, &host_tags_path(host)?, None, None)
+            .await
+    }
+
+    /// Replaces a host's tags with `tags`.
+    pub async fn update_host_tags(
+        &self,
+        host: &str,
+        tags: Vec<String>,
+    ) -> Result<Value, DatadogError> {
+        let body = build_tags_body(tags)?;
+        self.send_mutation(Method::PUT, &host_tags_path(host)?, body)
+            .await
+    }
+
+    /// Adds `tags` to a host, keeping its existing tags.
+    pub async fn add_host_tags(
+        &self,
+        host: &str,
+        tags: Vec<String>,
+    ) -> Result<Value, DatadogError> {
+        let body = build_tags_body(tags)?;
+        self.send_mutation(Method::POST, &host_tags_path(host)?, body)
+            .await
+    }
+
+    /// Lists log-based metrics, or fetches one when `name` is given.
+    pub async fn query_logs_metrics(&self, name: Option<&str>) -> Result<Value, DatadogError> {
+        let path = match name {
+            Some(name) => format!(
+                "/api/v2/logs/config/metrics/{}",
+                path_segment("log-based metric name", name)?
+            ),
+            None => "/api/v2/logs/config/metrics".to_string(),
+        };
+        self.send_json(Method::GET, &path, None, None).await
+    }
+
+    pub async fn create_logs_metric(
+        &self,
+        definition: LogsMetricDefinition,
+    ) -> Result<Value, DatadogError> {
+        let body = build_logs_metric_body(definition)?;
+        self.send_mutation(Method::POST, "/api/v2/logs/config/metrics", body)
+            .await
+    }
+
+    pub async fn query_events(&self, query: EventsQuery) -> Result<Value, DatadogError> {
+        let params = events_request(&query)?.params;
+        let limit = query.limit as usize;
+
+        let mut merged: Option<Value> = None;
+        let mut records = Vec::new();
+        let mut cursor: Option<String> = None;
+        let mut pages = 0;
+        loop {
+            let mut page_params = params.clone();
+            if let Some(cursor) = &cursor {
+                page_params.push(("page[cursor]".to_string(), cursor.clone()));
+            }
+            // Ask only for what --limit still allows, so no fetched record falls
+            // before the cursor handed back for resuming.
+            let remaining = limit - records.len();
+            if remaining < events_page_size(&query) as usize
+                && let Some((_, size)) = page_params
+                    .iter_mut()
+                    .find(|(name, _)| name == "page[limit]")
+            {
+                *size = remaining.to_string();
+            }
+            let mut page = match self
+                .send_json(Method::GET, "/api/v2/events", Some(page_params), None)
+                .await
+            {
+                Ok(page) => page,
+                Err(_) if self.cut_short(&mut merged) => break,
+                Err(err) => return Err(err),
+            };
+
+            if let Some(Value::Array(data)) = page.get_mut("data") {
+                records.append(data);
+            }
+            cursor = next_cursor(&page);
+            pages += 1;
+            let base = merged.get_or_insert(page);
+
+            if records.len() >= limit || cursor.is_none() {
+                break;
+            }
+            if query.max_pages.is_some_and(|max| pages >= max) || self.deadline_exceeded() {
+                base["complete"] = json!(false);
+                break;
+            }
+        }
+
+        records.truncate(limit);
+        Ok(merge_pages(merged, records, pages, cursor))
+    }
+
+    /// Sends a raw request. With `chunk_size` and an array body, the items go out
+    /// in consecutive requests and the responses come back as an array; each chunk
+    /// gets its own idempotency key, `<key>-<chunk number>`.
+    pub async fn raw(&self, mut request: RawRequest) -> Result<Value, DatadogError> {
+        let chunks = match (request.chunk_size, request.body.take()) {
+            (Some(size), Some(Value::Array(items))) => chunk_items(items, size),
+            (_, body) => {
+                let idempotency_key = request.idempotency_key.clone();
+                return self.send_raw(&request, body, idempotency_key).await;
+            }
+        };
+
+        let mut responses = Vec::with_capacity(chunks.len());
+        for (index, chunk) in chunks.into_iter().enumerate() {
+            let idempotency_key = request
+                .idempotency_key
+                .as_ref()
+                .map(|key| format!("{key}-{}", index + 1));
+            match self.send_raw(&request, Some(chunk), idempotency_key).await {
+                Ok(response) => responses.push(response),
+                Err(err) => {
+                    return Err(DatadogError::ChunkFailed {
+                        completed: responses,
+                        chunk: index + 1,
+                        source: Box::new(err),
+                    });
+                }
+            }
+        }
+        Ok(Value::Array(responses))
+    }
+
+    async fn send_raw(
+        &self,
+        request: &RawRequest,
+        body: Option<Value>,
+        idempotency_key: Option<String>,
+    ) -> Result<Value, DatadogError> {
+        let method = Method::from_bytes(request.method.as_bytes())
+            .context("Invalid HTTP method for raw query.")
+            .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
+        let params = if request.params.is_empty() {
+            None
+        } else {
+            Some(request.params.clone())
+        };
+        let options = RequestOptions {
+            replayable: is_safe_method(&method) || idempotency_key.is_some(),
+            idempotency_key,
+            ignore_statuses: request.ignore_statuses.clone(),
+            ..RequestOptions::default()
+        };
+        let response = self
+            .send_response(method, &request.path, params, body, &options)
+            .await?;
+
+        match &request.await_completion {
+            Some(settings) if response.status == StatusCode::ACCEPTED => {
+                let location = response.location.ok_or_else(|| {
+                    DatadogError::InvalidRequest(
+                        "Datadog returned 202 Accepted without a Location header to poll."
+                            .to_string(),
+                    )
+                })?;
+                self.await_completion(&location, settings).await
+            }
+            _ => Ok(response.body),
+        }
+    }
+
+    /// POSTs `body` to a webhook URL with the same retry policy as Datadog calls,
+    /// but without Datadog credentials.
+    pub async fn post_webhook(
+        &self,
+        url: &str,
+        headers: Vec<(String, String)>,
+        body: Value,
+    ) -> Result<Value, DatadogError> {
+        if !url.starts_with("http://") && !url.starts_with("https://") {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Invalid webhook URL `{url}`. Expected an http:// or https:// URL."
+            )));
+        }
+        let options = RequestOptions {
+            authenticate: false,
+            extra_headers: headers,
+            ..RequestOptions::default()
+        };
+        self.send_request(Method::POST, url, None, Some(body), &options)
+            .await
+    }
+
+    /// Polls `location` until it stops answering 202 and its status field no
+    /// longer reports a pending state, then returns the final body. The location
+    /// must be on the Datadog site, since it is polled with the API keys.
+    async fn await_completion(
+        &self,
+        location: &str,
+        settings: &AwaitCompletion,
+    ) -> Result<Value, DatadogError> {
+        let base = self
+            .resolve_url("/")
+            .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
+        let target = self
+            .resolve_url(location)
+            .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
+        if target.origin() != base.origin() {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Refusing to poll `{location}`: the Location header points outside {}.",
+                base.origin().ascii_serialization()
+            )));
+        }
+
+        let started = Instant::now();
+        let timeout = Duration::from_secs(settings.timeout_seconds);
+
+        loop {
+            sleep(self.clamp_to_deadline(Duration::from_millis(settings.poll_interval_ms))).await;
+            let response = self
+                .send_response(
+                    Method::GET,
+                    location,
+                    None,
+                    None,
+                    &RequestOptions::default(),
+                )
+                .await?;
+
+            let status_value = lookup_path(&response.body, &settings.status_field)
+                .and_then(Value::as_str)
+                .map(str::to_ascii_lowercase);
+            let pending = response.status == StatusCode::ACCEPTED
+                || status_value.as_deref().is_some_and(is_pending_status);
+            if !pending {
+                return Ok(response.body);
+            }
+
+            if started.elapsed() >= timeout {
+                return Err(DatadogError::Retryable {
+                    status: Some(response.status.as_u16()),
+                    transport_kind: None,
+                    message: format!(
+                        "Operation at `{location}` still pending after {}s (status: {}).",
+                        settings.timeout_seconds,
+                        status_value.as_deref().unwrap_or("unknown")
+                    ),
+                });
+            }
+        }
+    }
+
+    async fn send_plan(&self, plan: RequestPlan) -> Result<Value, DatadogError> {
+        let params = (!plan.params.is_empty()).then_some(plan.params);
+        self.send_json(plan.method, plan.path, params, plan.body)
+            .await
+    }
+
+    async fn send_json(
+        &self,
+        method: Method,
+        path: &str,
+        params: Option<Vec<(String, String)>>,
+        body: Option<Value>,
+    ) -> Result<Value, DatadogError> {
+        self.send_request(method, path, params, body, &RequestOptions::default())
+            .await
+    }
+
+    /// Sends a request that changes state in Datadog. It is not resent once it
+    /// may have reached Datadog, since a repeat could apply the change twice.
+    async fn send_mutation(
+        &self,
+        method: Method,
+        path: &str,
+        body: Value,
+    ) -> Result<Value, DatadogError> {
+        let options = RequestOptions {
+            replayable: false,
+            ..RequestOptions::default()
+        };
+        self.send_request(method, path, None, Some(body), &options)
+            .await
+    }
+
+    async fn send_request(
+        &self,
+        method: Method,
+        path: &str,
+        params: Option<Vec<(String, String)>>,
+        body: Option<Value>,
+        options: &RequestOptions,
+    ) -> Result<Value, DatadogError> {
+        self.send_response(method, path, params, body, options)
+            .await
+            .map(|response| response.body)
+    }
+
+    async fn send_response(
+        &self,
+        method: Method,
+        path: &str,
+        params: Option<Vec<(String, String)>>,
+        body: Option<Value>,
+        options: &RequestOptions,
+    ) -> Result<ApiResponse, DatadogError> {
+        let PreparedRequest {
+            url,
+            headers,
+            payload,
+        } = self.prepare_request(path, params, body.as_ref(), options)?;
+        if self.dry_run {
+            return Ok(ApiResponse {
+                status: StatusCode::OK,
+                location: None,
+                body: describe_request(&method, &url, &headers, body.as_ref()),
+            });
+        }
+        self.requests.fetch_add(1, Ordering::Relaxed);
+        let _timer = ElapsedTimer::start(&self.request_ms);
+        let mut attempt: u32 = 0;
+
+        loop {
+            let timeout = self
+                .request_timeout()
+                .ok_or_else(|| DatadogError::Retryable {
+                    status: None,
+                    transport_kind: Some("timeout"),
+                    message: format!(
+                        "Total timeout of {}s exceeded after {} attempt(s).",
+                        self.total_timeout_seconds.unwrap_or_default(),
+                        attempt
+                    ),
+                })?;
+
+            let http = if options.authenticate {
+                &self.http
+            } else {
+                &self.unpinned_http
+            };
+            let mut request = http.request(method.clone(), url.clone()).timeout(timeout);
+            for (name, value) in &headers {
+                request = request.header(name, value);
+            }
+            if let Some((bytes, _)) = &payload {
+                request = request.body(bytes.clone());
+            }
+
+            let sent_at = Instant::now();
+            self.attempts.fetch_add(1, Ordering::Relaxed);
+            let response = match request.send().await {
+                Ok(response) => response,
+                Err(err) => {
+                    self.trace(
+                        1,
+                        json!({
+                            "attempt": attempt + 1,
+                            "method": method.as_str(),
+                            "url": url.as_str(),
+                            "error": err.to_string(),
+                            "elapsed_ms": sent_at.elapsed().as_millis() as u64,
+                        }),
+                    );
+                    // Connect failures never reached Datadog, so they are always safe to
+                    // resend; other transport failures may have been partially processed.
+                    let may_resend = options.replayable || err.is_connect();
+                    if is_retryable_transport_error(&err)
+                        && may_resend
+                        && attempt < self.retry.max_retries
+                    {
+                        self.sleep_before_retry(attempt, None, RetryTrigger::Transport(&err))
+                            .await;
+                        attempt += 1;
+                        continue;
+                    }
+
+                    // Transport errors quote the request URL, which may carry a key.
+                    if is_retryable_transport_error(&err) {
+                        return Err(DatadogError::Retryable {
+                            status: None,
+                            transport_kind: Some(transport_kind(&err)),
+                            message: self.redact(&format!(
+                                "Datadog request failed after {} attempt(s): {}",
+                                attempt + 1,
+                                err
+                            )),
+                        });
+                    }
+
+                    return Err(DatadogError::InvalidRequest(
+                        self.redact(&format!("Datadog request setup failed: {err}")),
+                    ));
+                }
+            };
+
+            if options.authenticate
+                && let Ok(mut version) = self.http_version.lock()
+            {
+                *version = Some(response.version());
+            }
+            let status = response.status();
+            self.trace(
+                1,
+                json!({
+                    "attempt": attempt + 1,
+                    "method": method.as_str(),
+                    "url": url.as_str(),
+                    "status": status.as_u16(),
+                    "elapsed_ms": sent_at.elapsed().as_millis() as u64,
+                }),
+            );
+            let declares_json = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .is_some_and(|value| value.to_ascii_lowercase().contains("json"));
+            let retry_after_ms = parse_retry_after_ms(response.headers());
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let accepted =
+                status.is_success() || options.ignore_statuses.contains(&status.as_u16());
+            let bytes = match response.bytes().await {
+                Ok(bytes) => Some(bytes),
+                // A client error stays a client error however its body fails to read;
+                // retrying would only repeat it.
+                Err(_) if status.is_client_error() => None,
+                Err(err) => {
+                    if options.replayable && attempt < self.retry.max_retries {
+                        self.sleep_before_retry(attempt, None, RetryTrigger::Transport(&err))
+                            .await;
+                        attempt += 1;
+                        continue;
+                    }
+                    return Err(DatadogError::Retryable {
+                        status: Some(status.as_u16()),
+                        transport_kind: Some(transport_kind(&err)),
+                        message: self.redact(&format!(
+                            "Failed to read Datadog response after {} attempt(s): {}",
+                            attempt + 1,
+                            err
+                        )),
+                    });
+                }
+            };
+            let text = match bytes {
+                Some(bytes) if accepted => String::from_utf8_lossy(&bytes).into_owned(),
+                Some(bytes) => String::from_utf8(bytes.to_vec())
+                    .unwrap_or_else(|_| UNDECODABLE_BODY.to_string()),
+                None => UNDECODABLE_BODY.to_string(),
+            };
+
+            if accepted {
+                let success = |body| ApiResponse {
+                    status,
+                    location: location.clone(),
+                    body,
+                };
+                if text.trim().is_empty() {
+                    return Ok(success(json!({})));
+                }
+
+                match serde_json::from_str::<Value>(&text) {
+                    Ok(value) => return Ok(success(value)),
+                    Err(err) if declares_json => {
+                        // A JSON content-type with an unparseable body usually means the
+                        // connection dropped mid-body, so treat it like a failed read.
+                        if options.replayable && attempt < self.retry.max_retries {
+                            self.sleep_before_retry(
+                                attempt,
+                                None,
+                                RetryTrigger::IncompleteBody(status),
+                            )
+                            .await;
+                            attempt += 1;
+                            continue;
+                        }
+                        return Err(DatadogError::Retryable {
+                            status: Some(status.as_u16()),
+                            transport_kind: None,
+                            message: format!(
+                                "Datadog returned incomplete JSON after {} attempt(s): {}",
+                                attempt + 1,
+                                err
+                            ),
+                        });
+                    }
+                    Err(_) => return Ok(success(json!({ "raw": text }))),
+                }
+            }
+
+            let body = truncate_for_error(&self.redact(&text));
+            self.trace(
+                2,
+                json!({ "attempt": attempt + 1, "body": self.traced_body(&text) }),
+            );
+            if matches!(status, StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) {
+                return Err(DatadogError::Auth {
+                    status: status.as_u16(),
+                    body,
+                });
+            }
+
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                if (self.retry.retry_rate_limit && attempt < self.retry.max_retries)
+                    || self.take_rate_limit_wait()
+                {
+                    self.sleep_before_retry(attempt, retry_after_ms, RetryTrigger::Status(status))
+                        .await;
+                    attempt += 1;
+                    continue;
+                }
+                return Err(DatadogError::RateLimited {
+                    body,
+                    retry_after_ms,
+                });
+            }
+
+            // Only a resendable request is retried: a 5xx does not say whether
+            // Datadog already applied it.
+            if is_retryable_status(status) {
+                if options.replayable && attempt < self.retry.max_retries {
+                    self.sleep_before_retry(attempt, None, RetryTrigger::Status(status))
+                        .await;
+                    attempt += 1;
+                    continue;
+                }
                 return Err(DatadogError::Retryable {
                     status: Some(status.as_u16()),
+                    transport_kind: None,
                     message: format!(
                         "Datadog API returned {} after {} attempt(s): {}",
                         status.as_u16(),
@@ -326,6 +1369,90 @@ impl DatadogClient {
         }
     }
 
+    /// The URL, headers and encoded body `send_response` would send.
+    fn prepare_request(
+        &self,
+        path: &str,
+        params: Option<Vec<(String, String)>>,
+        body: Option<&Value>,
+        options: &RequestOptions,
+    ) -> Result<PreparedRequest, DatadogError> {
+        // Webhook targets are not Datadog and may not accept gzip.
+        let payload = body
+            .map(|body| encode_body(body, self.compress_request && options.authenticate))
+            .transpose()?;
+        let params = params
+            .map(|pairs| apply_duplicate_policy(pairs, self.duplicate_params))
+            .transpose()?;
+        let url = self.request_url(path, params.as_deref())?;
+        let gzipped = payload.as_ref().is_some_and(|(_, gzipped)| *gzipped);
+        Ok(PreparedRequest {
+            url,
+            headers: self.request_headers(options, gzipped),
+            payload,
+        })
+    }
+
+    /// The `--dry-run` description of `plan`: method, full URL, headers with the
+    /// keys masked, and body.
+    pub fn describe_plan(&self, plan: &RequestPlan) -> Result<Value, DatadogError> {
+        let params = (!plan.params.is_empty()).then(|| plan.params.clone());
+        let prepared = self.prepare_request(
+            plan.path,
+            params,
+            plan.body.as_ref(),
+            &RequestOptions::default(),
+        )?;
+        Ok(describe_request(
+            &plan.method,
+            &prepared.url,
+            &prepared.headers,
+            plan.body.as_ref(),
+        ))
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Warnings raised so far, by the client or the command using it; checked by
+    /// `--fail-on-warnings`.
+    pub fn warnings(&self) -> &Warnings {
+        &self.warnings
+    }
+
+    /// Claims one of the `--max-rate-limit-waits` extra waits, if waiting on 429s
+    /// is enabled, any remain, and the total timeout still allows it.
+    fn take_rate_limit_wait(&self) -> bool {
+        self.retry.on_rate_limit == RateLimitPolicy::Wait
+            && !self.deadline_exceeded()
+            && self
+                .rate_limit_waits
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |used| {
+                    (used < self.retry.max_rate_limit_waits).then_some(used + 1)
+                })
+                .is_ok()
+    }
+
+    /// Prints `entry` to stderr as a `{"trace": ...}` line, keys masked, when `-v`
+    /// was given at least `level` times.
+    fn trace(&self, level: u8, entry: Value) {
+        if self.verbosity >= level {
+            eprintln!("{}", self.redact(&json!({ "trace": entry }).to_string()));
+        }
+    }
+
+    /// A response body as it appears in the trace: keys masked, cut to
+    /// `--max-body-log-bytes`.
+    fn traced_body(&self, text: &str) -> String {
+        truncate_body(&self.redact(text), self.max_body_log_bytes)
+    }
+
+    /// Masks the configured API and application keys in text bound for an error.
+    fn redact(&self, text: &str) -> String {
+        mask_secrets(text, &[&self.api_key, &self.app_key])
+    }
+
     fn resolve_url(&self, path: &str) -> anyhow::Result<Url> {
         if path.starts_with("http://") || path.starts_with("https://") {
             return Url::parse(path).context("Invalid raw URL.");
@@ -340,52 +1467,966 @@ impl DatadogClient {
         Url::parse(&url).with_context(|| format!("Invalid Datadog URL built from `{url}`"))
     }
 
-    async fn sleep_before_retry(&self, attempt: u32, retry_after_ms: Option<u64>) {
-        let delay_ms = retry_after_ms.unwrap_or_else(|| self.backoff_ms(attempt));
+    async fn sleep_before_retry(
+        &self,
+        attempt: u32,
+        retry_after_ms: Option<u64>,
+        trigger: RetryTrigger<'_>,
+    ) {
+        let delay_ms = self.retry_delay_ms(attempt, retry_after_ms);
+        self.trace(
+            1,
+            json!({
+                "retry": {
+                    "next_attempt": attempt + 2,
+                    "sleep_ms": delay_ms,
+                    "trigger": trigger.to_json(),
+                    "server_dictated": retry_after_ms.is_some(),
+                },
+                "message": retry_message(attempt + 2, delay_ms, &trigger),
+            }),
+        );
+        if let Some(path) = &self.retry.retry_log {
+            let entry = json!({
+                "timestamp": Utc::now().to_rfc3339(),
+                "attempt": attempt + 1,
+                "trigger": trigger.to_json(),
+                "sleep_ms": delay_ms,
+                "server_dictated": retry_after_ms.is_some(),
+            });
+            if let Err(err) = append_json_line(path, &entry) {
+                self.warnings.warn(&format!(
+                    "Failed writing retry log `{}`: {err}",
+                    path.display()
+                ));
+            }
+        }
         sleep(Duration::from_millis(delay_ms)).await;
     }
 
-    fn backoff_ms(&self, attempt: u32) -> u64 {
-        let multiplier = 1u64 << attempt.min(16);
-        self.retry
-            .backoff_ms
-            .saturating_mul(multiplier)
-            .min(self.retry.max_backoff_ms)
+    /// Whether `--total-timeout-seconds` has elapsed; page loops stop early on it.
+    fn deadline_exceeded(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
     }
-}
 
-fn truncate_for_error(text: &str) -> String {
-    const MAX_ERROR_BODY_BYTES: usize = 2_048;
-    if text.len() <= MAX_ERROR_BODY_BYTES {
-        return text.to_string();
+    /// Whether a failed later page should end pagination instead of failing it:
+    /// the overall deadline ran out after some pages arrived. If so, marks the
+    /// merged pages `"complete": false`.
+    fn cut_short(&self, merged: &mut Option<Value>) -> bool {
+        match merged {
+            Some(base) if self.deadline_exceeded() => {
+                base["complete"] = json!(false);
+                true
+            }
+            _ => false,
+        }
     }
 
-    let mut end = MAX_ERROR_BODY_BYTES;
-    while !text.is_char_boundary(end) {
-        end -= 1;
+    /// Per-request timeout, shortened to the time left before the overall
+    /// deadline. `None` once the deadline has passed.
+    fn request_timeout(&self) -> Option<Duration> {
+        let per_request = Duration::from_secs(self.timeout_seconds);
+        match self.deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                (!remaining.is_zero()).then(|| per_request.min(remaining))
+            }
+            None => Some(per_request),
+        }
     }
 
-    format!("{}...(truncated)", &text[..end])
-}
+    /// The URL for `path` with `params` appended as query pairs.
+    fn request_url(
+        &self,
+        path: &str,
+        params: Option<&[(String, String)]>,
+    ) -> Result<Url, DatadogError> {
+        let mut url = self
+            .resolve_url(path)
+            .map_err(|err| DatadogError::InvalidRequest(err.to_string()))?;
+        if let Some(pairs) = params {
+            let mut query = url.query_pairs_mut();
+            for (key, value) in pairs {
+                query.append_pair(key, value);
+            }
+        }
+        Ok(url)
+    }
 
-fn parse_retry_after_ms(headers: &reqwest::header::HeaderMap) -> Option<u64> {
+    /// Every header sent with a request, credentials included.
+    fn request_headers(&self, options: &RequestOptions, gzipped: bool) -> Vec<(String, String)> {
+        let mut headers = vec![
+            ("Content-Type".to_string(), "application/json".to_string()),
+            ("Accept".to_string(), "application/json".to_string()),
+        ];
+        if options.authenticate {
+            headers.push((API_KEY_HEADER.to_string(), self.api_key.clone()));
+            headers.push((APP_KEY_HEADER.to_string(), self.app_key.clone()));
+        }
+        headers.extend(options.extra_headers.iter().cloned());
+        if let Some(id) = &self.correlation_id {
+            headers.push(("X-Correlation-Id".to_string(), id.clone()));
+        }
+        if let Some(key) = &options.idempotency_key {
+            headers.push(("Idempotency-Key".to_string(), key.clone()));
+        }
+        if gzipped {
+            headers.push(("Content-Encoding".to_string(), "gzip".to_string()));
+        }
+        headers
+    }
+
+    /// How long to wait before retry `attempt`: the server's Retry-After (capped at
+    /// `retry_after_max_ms`) or the jittered backoff, never past the deadline.
+    fn retry_delay_ms(&self, attempt: u32, retry_after_ms: Option<u64>) -> u64 {
+        let delay_ms = match retry_after_ms {
+            Some(retry_after_ms) => retry_after_ms.min(self.retry.retry_after_max_ms),
+            None => jittered_ms(
+                self.backoff_ms(attempt),
+                self.retry.jitter,
+                &mut rand::make_rng::<StdRng>(),
+            ),
+        };
+        // Never sleep past the overall deadline; the next attempt reports it.
+        // Round up so a clamped sleep does not end just short of the deadline.
+        self.clamp_to_deadline(Duration::from_millis(delay_ms))
+            .as_micros()
+            .div_ceil(1000) as u64
+    }
+
+    /// `delay`, shortened to the time left before the overall deadline.
+    fn clamp_to_deadline(&self, delay: Duration) -> Duration {
+        match self.deadline {
+            Some(deadline) => delay.min(deadline.saturating_duration_since(Instant::now())),
+            None => delay,
+        }
+    }
+
+    fn backoff_ms(&self, attempt: u32) -> u64 {
+        let multiplier = match self.retry.strategy {
+            BackoffStrategy::Exponential => 1u64 << attempt.min(16),
+            BackoffStrategy::Constant => 1,
+        };
+        self.retry
+            .backoff_ms
+            .saturating_mul(multiplier)
+            .min(self.retry.max_backoff_ms)
+    }
+}
+
+/// Applies `jitter` to a computed backoff delay, drawing from `rng` so tests can
+/// seed it.
+fn jittered_ms(delay_ms: u64, jitter: RetryJitter, rng: &mut impl Rng) -> u64 {
+    match jitter {
+        RetryJitter::Off => delay_ms,
+        RetryJitter::Full => rng.random_range(0..=delay_ms),
+        RetryJitter::Equal => {
+            let half = delay_ms / 2;
+            half + rng.random_range(0..=delay_ms - half)
+        }
+    }
+}
+
+pub fn logs_request(query: &LogsQuery) -> Result<RequestPlan, DatadogError> {
+    let sort = match query.sort.to_ascii_lowercase().as_str() {
+        "asc" => "timestamp",
+        "desc" => "-timestamp",
+        other => {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Invalid sort `{other}`. Use `asc` or `desc` for logs queries."
+            )));
+        }
+    };
+
+    let mut page = json!({ "limit": query.limit });
+    if let Some(cursor) = &query.cursor {
+        page["cursor"] = json!(cursor);
+    }
+
+    Ok(RequestPlan {
+        method: Method::POST,
+        path: "/api/v2/logs/events/search",
+        params: Vec::new(),
+        body: Some(json!({
+            "filter": {
+                "query": query.query,
+                "from": query.from,
+                "to": query.to
+            },
+            "sort": sort,
+            "page": page
+        })),
+    })
+}
+
+pub fn metrics_request(query: &str, from: i64, to: i64) -> RequestPlan {
+    RequestPlan {
+        method: Method::GET,
+        path: "/api/v1/query",
+        params: vec![
+            ("query".to_string(), query.to_string()),
+            ("from".to_string(), from.to_string()),
+            ("to".to_string(), to.to_string()),
+        ],
+        body: None,
+    }
+}
+
+/// Aggregations accepted by the logs aggregate API; all but `count` need a metric.
+const LOGS_AGGREGATIONS: [&str; 12] = [
+    "count",
+    "cardinality",
+    "sum",
+    "min",
+    "max",
+    "avg",
+    "median",
+    "pc75",
+    "pc90",
+    "pc95",
+    "pc98",
+    "pc99",
+];
+
+pub fn logs_aggregate_request(aggregation: &LogsAggregation) -> Result<RequestPlan, DatadogError> {
+    let specs = if aggregation.compute.is_empty() {
+        vec!["count".to_string()]
+    } else {
+        aggregation.compute.clone()
+    };
+    let compute = specs
+        .iter()
+        .map(|spec| logs_compute(spec))
+        .collect::<Result<Vec<_>, _>>()?;
+    let group_by: Vec<Value> = aggregation
+        .group_by
+        .iter()
+        .map(|facet| json!({ "facet": facet, "limit": aggregation.group_limit }))
+        .collect();
+
+    let mut body = json!({
+        "filter": {
+            "query": aggregation.query,
+            "from": aggregation.from,
+            "to": aggregation.to
+        },
+        "compute": compute
+    });
+    if !group_by.is_empty() {
+        body["group_by"] = Value::Array(group_by);
+    }
+
+    Ok(RequestPlan {
+        method: Method::POST,
+        path: "/api/v2/logs/analytics/aggregate",
+        params: Vec::new(),
+        body: Some(body),
+    })
+}
+
+/// The `c0` compute of an ungrouped logs aggregate response; no buckets means no
+/// matching logs.
+fn aggregate_count(response: &Value) -> Option<u64> {
+    let buckets = response.pointer("/data/buckets")?.as_array()?;
+    match buckets.first() {
+        Some(bucket) => bucket
+            .pointer("/computes/c0")?
+            .as_f64()
+            .map(|count| count as u64),
+        None => Some(0),
+    }
+}
+
+/// Parses one `--compute` spec, `AGG` or `AGG:METRIC`.
+fn logs_compute(spec: &str) -> Result<Value, DatadogError> {
+    let (aggregation, metric) = match spec.split_once(':') {
+        Some((aggregation, metric)) => (aggregation.to_ascii_lowercase(), Some(metric)),
+        None => (spec.to_ascii_lowercase(), None),
+    };
+    if !LOGS_AGGREGATIONS.contains(&aggregation.as_str()) {
+        return Err(DatadogError::InvalidRequest(format!(
+            "Invalid aggregation `{spec}`. Use one of: {}.",
+            LOGS_AGGREGATIONS.join(", ")
+        )));
+    }
+    match metric.filter(|metric| !metric.is_empty()) {
+        Some(metric) => {
+            Ok(json!({ "aggregation": aggregation, "metric": metric, "type": "total" }))
+        }
+        None if aggregation == "count" => Ok(json!({ "aggregation": "count", "type": "total" })),
+        None => Err(DatadogError::InvalidRequest(format!(
+            "Aggregation `{aggregation}` needs a metric, e.g. {aggregation}:@duration."
+        ))),
+    }
+}
+
+/// Aggregators a v2 metric query may start with; a leading `name:` never uses one.
+const METRIC_AGGREGATORS: [&str; 10] = [
+    "avg", "sum", "min", "max", "count", "p50", "p75", "p90", "p95", "p99",
+];
+
+pub fn timeseries_request(query: &TimeseriesQuery) -> Result<RequestPlan, DatadogError> {
+    let mut queries = Vec::new();
+    let mut names = HashSet::new();
+    for (index, expression) in query.queries.iter().enumerate() {
+        let (name, metric_query) = split_query_name(expression)
+            .unwrap_or_else(|| (format!("query{}", index + 1), expression.clone()));
+        if !names.insert(name.clone()) {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Query name `{name}` is used more than once."
+            )));
+        }
+        queries.push(json!({
+            "data_source": "metrics",
+            "name": name,
+            "query": metric_query,
+        }));
+    }
+
+    let mut formulas = Vec::new();
+    for formula in &query.formulas {
+        if let Some(unknown) = formula_references(formula)
+            .into_iter()
+            .find(|reference| !names.contains(reference))
+        {
+            let mut known: Vec<&str> = names.iter().map(String::as_str).collect();
+            known.sort_unstable();
+            return Err(DatadogError::InvalidRequest(format!(
+                "Formula `{formula}` references undefined query `{unknown}`. Named queries: [{}].",
+                known.join(", ")
+            )));
+        }
+        formulas.push(json!({ "formula": formula }));
+    }
+
+    let mut attributes = json!({
+        "from": query.from_ms,
+        "to": query.to_ms,
+        "queries": queries,
+    });
+    if !formulas.is_empty() {
+        attributes["formulas"] = Value::Array(formulas);
+    }
+    if let Some(interval) = query.interval_ms {
+        attributes["interval"] = json!(interval);
+    }
+
+    Ok(RequestPlan {
+        method: Method::POST,
+        path: "/api/v2/query/timeseries",
+        params: Vec::new(),
+        body: Some(json!({
+            "data": {
+                "type": "timeseries_request",
+                "attributes": attributes,
+            }
+        })),
+    })
+}
+
+/// Splits `name:query` when the prefix is an identifier other than an aggregator,
+/// so `a:avg:system.cpu.user{*}` is named `a` but `avg:system.cpu.user{*}` is not.
+fn split_query_name(expression: &str) -> Option<(String, String)> {
+    let (name, rest) = expression.split_once(':')?;
+    let is_identifier = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    (is_identifier && !rest.is_empty() && !METRIC_AGGREGATORS.contains(&name))
+        .then(|| (name.to_string(), rest.to_string()))
+}
+
+/// Identifiers a formula uses as query references; names called as functions
+/// (`abs(a)`) are skipped.
+fn formula_references(formula: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    let mut chars = formula.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_ascii_digit() || c == '.' {
+            while chars
+                .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '.')
+                .is_some()
+            {}
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start + c.len_utf8();
+            while let Some((index, c)) =
+                chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+            {
+                end = index + c.len_utf8();
+            }
+            let is_call = formula[end..].trim_start().starts_with('(');
+            if !is_call {
+                references.push(formula[start..end].to_string());
+            }
+        }
+    }
+    references
+}
+
+/// The first page request of an events query; later pages add `page[cursor]`.
+/// Records requested per events page: `--page-size`, else `--limit`, within the
+/// API's bounds.
+fn events_page_size(query: &EventsQuery) -> u32 {
+    query
+        .page_size
+        .unwrap_or(query.limit)
+        .clamp(1, EVENTS_MAX_PAGE_SIZE)
+}
+
+pub fn events_request(query: &EventsQuery) -> Result<RequestPlan, DatadogError> {
+    let sort = match query.sort.to_ascii_lowercase().as_str() {
+        "asc" => "timestamp",
+        "desc" => "-timestamp",
+        other => {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Invalid sort `{other}`. Use `asc` or `desc` for events queries."
+            )));
+        }
+    };
+    let page_size = events_page_size(query);
+
+    let mut params = vec![
+        ("filter[from]".to_string(), query.from.clone()),
+        ("filter[to]".to_string(), query.to.clone()),
+        ("page[limit]".to_string(), page_size.to_string()),
+        ("sort".to_string(), sort.to_string()),
+    ];
+    if let Some(filter) = &query.query {
+        params.push(("filter[query]".to_string(), filter.clone()));
+    }
+    if let Some(priority) = &query.priority {
+        let priority = event_filter("priority", priority, &["normal", "low"])?;
+        params.push(("filter[priority]".to_string(), priority));
+    }
+    if let Some(alert_type) = &query.alert_type {
+        let alert_type = event_filter(
+            "alert type",
+            alert_type,
+            &["error", "warning", "info", "success"],
+        )?;
+        params.push(("filter[alert_type]".to_string(), alert_type));
+    }
+
+    Ok(RequestPlan {
+        method: Method::GET,
+        path: "/api/v2/events",
+        params,
+        body: None,
+    })
+}
+
+fn build_series_body(submission: MetricSubmission) -> Result<Value, DatadogError> {
+    let metric_type = match submission.metric_type.to_ascii_lowercase().as_str() {
+        "unspecified" => 0,
+        "count" => 1,
+        "rate" => 2,
+        "gauge" => 3,
+        other => {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Invalid metric type `{other}`. Use `gauge`, `count`, `rate`, or `unspecified`."
+            )));
+        }
+    };
+
+    Ok(json!({
+        "series": [{
+            "metric": submission.metric,
+            "type": metric_type,
+            "points": [{
+                "timestamp": submission.timestamp,
+                "value": submission.value
+            }],
+            "tags": submission.tags
+        }]
+    }))
+}
+
+/// Serializes a JSON body, gzipping it when `compress` is set and the body
+/// reaches [`COMPRESS_THRESHOLD_BYTES`]. Returns the bytes and whether they are
+/// gzip-encoded.
+fn encode_body(body: &Value, compress: bool) -> Result<(Vec<u8>, bool), DatadogError> {
+    let json = serde_json::to_vec(body)
+        .map_err(|err| DatadogError::InvalidRequest(format!("Invalid request body: {err}")))?;
+    if !compress || json.len() < COMPRESS_THRESHOLD_BYTES {
+        return Ok((json, false));
+    }
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder
+        .write_all(&json)
+        .and_then(|()| encoder.finish())
+        .map(|gzipped| (gzipped, true))
+        .map_err(|err| DatadogError::InvalidRequest(format!("Failed compressing body: {err}")))
+}
+
+fn build_mute_body(end: Option<i64>) -> Value {
+    match end {
+        Some(end) => json!({ "end": end }),
+        None => json!({}),
+    }
+}
+
+fn build_tags_body(tags: Vec<String>) -> Result<Value, DatadogError> {
+    if let Some(invalid) = tags.iter().find(|tag| {
+        tag.split_once(':')
+            .is_none_or(|(key, value)| key.is_empty() || value.is_empty())
+    }) {
+        return Err(DatadogError::InvalidRequest(format!(
+            "Invalid tag `{invalid}`. Expected key:value."
+        )));
+    }
+    Ok(json!({ "tags": tags }))
+}
+
+fn build_logs_metric_body(definition: LogsMetricDefinition) -> Result<Value, DatadogError> {
+    let mut compute = match definition.aggregation_type.to_ascii_lowercase().as_str() {
+        "count" if definition.path.is_none() => json!({ "aggregation_type": "count" }),
+        "count" => {
+            return Err(DatadogError::InvalidRequest(
+                "--path is only valid for distribution log-based metrics.".to_string(),
+            ));
+        }
+        "distribution" => {
+            let path = definition.path.ok_or_else(|| {
+                DatadogError::InvalidRequest(
+                    "Distribution log-based metrics need --path (e.g. @duration).".to_string(),
+                )
+            })?;
+            json!({ "aggregation_type": "distribution", "path": path })
+        }
+        other => {
+            return Err(DatadogError::InvalidRequest(format!(
+                "Invalid aggregation type `{other}`. Use `count` or `distribution`."
+            )));
+        }
+    };
+    if definition.include_percentiles {
+        compute["include_percentiles"] = json!(true);
+    }
+
+    let group_by: Vec<Value> = definition
+        .group_by
+        .iter()
+        .map(|entry| {
+            let (path, tag_name) = entry.split_once(':').unwrap_or((entry, entry));
+            json!({ "path": path, "tag_name": tag_name.trim_start_matches('@') })
+        })
+        .collect();
+
+    Ok(json!({
+        "data": {
+            "id": definition.name,
+            "type": "logs_metrics",
+            "attributes": {
+                "compute": compute,
+                "filter": { "query": definition.query },
+                "group_by": group_by
+            }
+        }
+    }))
+}
+
+fn apply_duplicate_policy(
+    pairs: Vec<(String, String)>,
+    policy: DuplicateParamPolicy,
+) -> Result<Vec<(String, String)>, DatadogError> {
+    match policy {
+        DuplicateParamPolicy::KeepAll => Ok(pairs),
+        DuplicateParamPolicy::LastWins => {
+            let mut kept: Vec<(String, String)> = Vec::with_capacity(pairs.len());
+            for (key, value) in pairs {
+                match kept.iter_mut().find(|(existing, _)| *existing == key) {
+                    Some(entry) => entry.1 = value,
+                    None => kept.push((key, value)),
+                }
+            }
+            Ok(kept)
+        }
+        DuplicateParamPolicy::Error => {
+            let mut seen = HashSet::new();
+            match pairs.iter().find(|(key, _)| !seen.insert(key.as_str())) {
+                Some((key, _)) => Err(DatadogError::InvalidRequest(format!(
+                    "Duplicate query param `{key}`. Pass it once or use --on-duplicate-param keep-all/last-wins."
+                ))),
+                None => Ok(pairs),
+            }
+        }
+    }
+}
+
+/// E.g. `attempt 2 after 500ms backoff due to 503`.
+fn retry_message(next_attempt: u32, delay_ms: u64, trigger: &RetryTrigger<'_>) -> String {
+    format!(
+        "attempt {next_attempt} after {delay_ms}ms backoff due to {}",
+        trigger.label()
+    )
+}
+
+fn append_json_line(path: &Path, entry: &Value) -> std::io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{entry}")
+}
+
+/// Stands in for a credential wherever one would otherwise be printed.
+const REDACTED: &str = "***REDACTED***";
+
+/// Secrets shorter than this are not masked: no real key is that short, and
+/// masking a placeholder like `x` would mangle every word containing it.
+const MIN_MASKED_SECRET_LEN: usize = 8;
+
+/// Replaces every occurrence of the given secrets with `***REDACTED***`, so an
+/// endpoint that echoes request headers cannot leak credentials into error output.
+fn mask_secrets(text: &str, secrets: &[&str]) -> String {
+    secrets
+        .iter()
+        .filter(|secret| secret.len() >= MIN_MASKED_SECRET_LEN)
+        .fold(text.to_string(), |masked, secret| {
+            masked.replace(secret, REDACTED)
+        })
+}
+
+fn truncate_for_error(text: &str) -> String {
+    const MAX_ERROR_BODY_BYTES: usize = 2_048;
+    truncate_body(text, MAX_ERROR_BODY_BYTES)
+}
+
+/// Cuts `text` to at most `max_bytes` on a character boundary, marking the cut.
+fn truncate_body(text: &str, max_bytes: usize) -> String {
+    if text.len() <= max_bytes {
+        return text.to_string();
+    }
+
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    format!("{}...(truncated)", &text[..end])
+}
+
+/// The `--dry-run` description of a request: method, full URL, headers with the
+/// credentials masked, and the JSON body before any gzip encoding.
+fn describe_request(
+    method: &Method,
+    url: &Url,
+    headers: &[(String, String)],
+    body: Option<&Value>,
+) -> Value {
+    let headers: serde_json::Map<String, Value> = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if name == API_KEY_HEADER || name == APP_KEY_HEADER {
+                REDACTED
+            } else {
+                value
+            };
+            (name.clone(), json!(value))
+        })
+        .collect();
+    json!({
+        "dry_run": true,
+        "method": method.as_str(),
+        "url": url.as_str(),
+        "headers": headers,
+        "body": body,
+    })
+}
+
+fn parse_retry_after_ms(headers: &reqwest::header::HeaderMap) -> Option<u64> {
     let value = headers.get("Retry-After")?;
-    let as_text = value.to_str().ok()?.trim();
-    let seconds = as_text.parse::<u64>().ok()?;
-    Some(seconds.saturating_mul(1_000))
+    retry_after_value_ms(value.to_str().ok()?, Utc::now())
+}
+
+/// Reads a Retry-After value given as delay seconds or as an HTTP-date
+/// (`Wed, 21 Oct 2015 07:28:00 GMT`); a date already past means no wait.
+fn retry_after_value_ms(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(seconds.saturating_mul(1_000));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let wait_ms = (date.with_timezone(&Utc) - now).num_milliseconds();
+    Some(u64::try_from(wait_ms).unwrap_or(0))
+}
+
+/// Labels a failure where no usable response arrived: `timeout`, `dns`,
+/// `connect`, `body` (the response broke off mid-read), or `other`.
+fn transport_kind(err: &reqwest::Error) -> &'static str {
+    if err.is_timeout() {
+        "timeout"
+    } else if err.is_connect() && is_dns_failure(err) {
+        "dns"
+    } else if err.is_connect() {
+        "connect"
+    } else if err.is_body() || err.is_decode() {
+        "body"
+    } else {
+        "other"
+    }
+}
+
+/// Whether a connect error failed resolving the host; the connector reports
+/// this as a `dns error` somewhere in the source chain.
+fn is_dns_failure(err: &reqwest::Error) -> bool {
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if cause.to_string().starts_with("dns error") {
+            return true;
+        }
+        source = cause.source();
+    }
+    false
 }
 
 fn is_retryable_transport_error(err: &reqwest::Error) -> bool {
     err.is_timeout() || err.is_connect() || err.is_body() || err.is_request()
 }
 
+/// Lowercases an events filter value and checks it against the values Datadog
+/// accepts for it.
+fn event_filter(name: &str, value: &str, allowed: &[&str]) -> Result<String, DatadogError> {
+    let value = value.to_ascii_lowercase();
+    if allowed.contains(&value.as_str()) {
+        Ok(value)
+    } else {
+        Err(DatadogError::InvalidRequest(format!(
+            "Invalid event {name} `{value}`. Use one of {}.",
+            allowed.join(", ")
+        )))
+    }
+}
+
+/// Splits `items` into consecutive JSON arrays of at most `size` items.
+fn chunk_items(items: Vec<Value>, size: usize) -> Vec<Value> {
+    items
+        .chunks(size.max(1))
+        .map(|chunk| Value::Array(chunk.to_vec()))
+        .collect()
+}
+
+/// Builds one response from paged results: the first page with `data` replaced
+/// by every page's records and `meta.pages_fetched` set. `cursor` is what follows
+/// the last page fetched, if anything.
+fn merge_pages(
+    merged: Option<Value>,
+    records: Vec<Value>,
+    pages: u32,
+    cursor: Option<String>,
+) -> Value {
+    let mut response = merged.unwrap_or_else(|| json!({}));
+    response["data"] = Value::Array(records);
+    response["meta"]["pages_fetched"] = json!(pages);
+    if pages > 1 {
+        // The merged response keeps the first page's metadata; point its cursor
+        // at whatever follows the last page fetched.
+        match cursor {
+            Some(after) => response["meta"]["page"]["after"] = json!(after),
+            None => {
+                if let Some(Value::Object(page_meta)) = response.pointer_mut("/meta/page") {
+                    page_meta.remove("after");
+                }
+            }
+        }
+    }
+    response
+}
+
+/// The restriction policy path for `resource_id`, encoded as a single path
+/// segment so a `/`, `?` or space in the id cannot change the route.
+fn restriction_policy_path(resource_id: &str) -> String {
+    format!(
+        "/api/v2/restriction_policy/{}",
+        encode_path_segment(resource_id)
+    )
+}
+
+/// The host tags path for `host`, encoded as a single path segment so the host
+/// name cannot point a tag update at another endpoint.
+fn host_tags_path(host: &str) -> Result<String, DatadogError> {
+    Ok(format!(
+        "/api/v1/tags/hosts/{}",
+        path_segment("host", host)?
+    ))
+}
+
+/// `value` encoded as one path segment. Empty, `.` and `..` are rejected:
+/// URL parsing would drop or resolve them (even percent-encoded), moving the
+/// request to a parent path.
+fn path_segment(what: &str, value: &str) -> Result<String, DatadogError> {
+    if matches!(value, "" | "." | "..") {
+        return Err(DatadogError::InvalidRequest(format!(
+            "Invalid {what} `{value}`: it must name a single path segment."
+        )));
+    }
+    Ok(encode_path_segment(value))
+}
+
+/// Percent-encodes everything but unreserved characters and `:`/`@`, which are
+/// valid in a path segment and appear in Datadog resource ids.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| {
+            if byte.is_ascii_alphanumeric() || b"-._~:@".contains(&byte) {
+                (byte as char).to_string()
+            } else {
+                format!("%{byte:02X}")
+            }
+        })
+        .collect()
+}
+
+fn next_cursor(page: &Value) -> Option<String> {
+    lookup_path(page, "meta.page.after")
+        .and_then(Value::as_str)
+        .filter(|cursor| !cursor.is_empty())
+        .map(str::to_string)
+}
+
+fn is_pending_status(status: &str) -> bool {
+    matches!(
+        status,
+        "accepted" | "pending" | "queued" | "running" | "in_progress" | "processing"
+    )
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
 fn is_retryable_status(status: StatusCode) -> bool {
     status == StatusCode::REQUEST_TIMEOUT || status.is_server_error()
 }
 
 #[cfg(test)]
 mod tests {
-    use super::truncate_for_error;
+    use std::collections::HashMap;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    use flate2::read::GzDecoder;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use reqwest::dns::{Name, Resolve, Resolving};
+    use reqwest::{Method, StatusCode};
+    use serde_json::json;
+
+    use super::{
+        AwaitCompletion, DatadogClient, DatadogError, EventsQuery, LogsAggregation,
+        LogsMetricDefinition, LogsQuery, LogsRehydration, MetricSubmission, MonitorsQuery,
+        ProcessesQuery, RawRequest, RbacQuery, RetryTrigger, TimeseriesQuery,
+        apply_duplicate_policy, build_logs_metric_body, build_mute_body, build_series_body,
+        build_tags_body, encode_body, events_request, host_tags_path, jittered_ms,
+        logs_aggregate_request, logs_request, mask_secrets, metrics_request,
+        restriction_policy_path, retry_after_value_ms, retry_message, timeseries_request,
+        transport_kind, truncate_for_error,
+    };
+    use crate::app_error::AppError;
+    use crate::cli::{BackoffStrategy, DuplicateParamPolicy, RateLimitPolicy, RetryJitter};
+    use crate::config::{Config, RetryConfig};
+    use crate::time_expr::parse_to_unix;
+
+    /// Serves one canned HTTP response per connection, in order, and records each
+    /// raw request (head and body) for assertions.
+    fn mock_server<R: AsRef<[u8]> + Send + 'static>(
+        responses: Vec<R>,
+    ) -> (String, Arc<Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+
+        thread::spawn(move || {
+            for response in responses {
+                let Ok((mut stream, _)) = listener.accept() else {
+                    return;
+                };
+                recorded.lock().unwrap().push(read_request(&stream));
+
+                // An empty canned response drops the connection without replying.
+                if !response.as_ref().is_empty() {
+                    stream.write_all(response.as_ref()).unwrap();
+                }
+            }
+        });
+
+        (base_url, requests)
+    }
+
+    /// Reads one HTTP request (head and body) from `stream`.
+    fn read_request(stream: &TcpStream) -> String {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut request = String::new();
+        let mut content_length = 0;
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+            request.push_str(&line);
+        }
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body).unwrap();
+        request.push_str("\r\n");
+        request.push_str(&String::from_utf8_lossy(&body));
+        request
+    }
+
+    fn http_response(status: &str, content_type: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn raw_post(idempotency_key: Option<String>) -> RawRequest {
+        RawRequest {
+            method: "POST".to_string(),
+            path: "/api/v2/things".to_string(),
+            params: Vec::new(),
+            body: Some(json!({})),
+            idempotency_key,
+            await_completion: None,
+            ignore_statuses: Vec::new(),
+            chunk_size: None,
+        }
+    }
+
+    fn test_client(base_url: &str, max_retries: u32) -> DatadogClient {
+        DatadogClient::new(Config {
+            api_key: "test-api-key".to_string(),
+            app_key: "test-app-key".to_string(),
+            base_url: base_url.to_string(),
+            retry: RetryConfig {
+                max_retries,
+                backoff_ms: 1,
+                max_backoff_ms: 1,
+                strategy: BackoffStrategy::Exponential,
+                jitter: RetryJitter::Off,
+                retry_after_max_ms: 60_000,
+                retry_rate_limit: true,
+                on_rate_limit: RateLimitPolicy::Fail,
+                max_rate_limit_waits: 10,
+                retry_log: None,
+            },
+            timeout_seconds: 5,
+            total_timeout_seconds: None,
+            compress_request: false,
+            duplicate_params: DuplicateParamPolicy::KeepAll,
+            pins: Vec::new(),
+            correlation_id: None,
+            dry_run: false,
+            verbosity: 0,
+            max_body_log_bytes: 2_048,
+        })
+    }
 
     #[test]
     fn truncate_for_error_keeps_short_text() {
@@ -403,4 +2444,1640 @@ mod tests {
         assert!(truncated.ends_with(suffix));
         assert!(prefix.len() <= 2_048);
     }
+
+    #[test]
+    fn traced_bodies_are_cut_to_max_body_log_bytes() {
+        let mut client = test_client("http://127.0.0.1:1", 0);
+        client.max_body_log_bytes = 10;
+
+        assert_eq!(
+            client.traced_body(&"x".repeat(100)),
+            "xxxxxxxxxx...(truncated)"
+        );
+        assert_eq!(client.traced_body("short"), "short");
+        assert_eq!(truncate_for_error(&"x".repeat(100)), "x".repeat(100));
+    }
+
+    #[test]
+    fn build_series_body_uses_v2_point_shape() {
+        let body = build_series_body(MetricSubmission {
+            metric: "custom.exporter.lag".to_string(),
+            value: 1.5,
+            timestamp: 1_700_000_000,
+            metric_type: "Count".to_string(),
+            tags: vec!["env:prod".to_string()],
+        })
+        .unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "series": [{
+                    "metric": "custom.exporter.lag",
+                    "type": 1,
+                    "points": [{ "timestamp": 1_700_000_000, "value": 1.5 }],
+                    "tags": ["env:prod"]
+                }]
+            })
+        );
+    }
+
+    #[test]
+    fn build_series_body_rejects_unknown_type() {
+        let result = build_series_body(MetricSubmission {
+            metric: "custom.metric".to_string(),
+            value: 1.0,
+            timestamp: 0,
+            metric_type: "histogram".to_string(),
+            tags: Vec::new(),
+        });
+
+        assert!(matches!(result, Err(DatadogError::InvalidRequest(_))));
+    }
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn duplicate_param_policies() {
+        let pairs = params(&[("tag", "a"), ("q", "x"), ("tag", "b")]);
+
+        assert_eq!(
+            apply_duplicate_policy(pairs.clone(), DuplicateParamPolicy::KeepAll).unwrap(),
+            pairs
+        );
+        assert_eq!(
+            apply_duplicate_policy(pairs.clone(), DuplicateParamPolicy::LastWins).unwrap(),
+            params(&[("tag", "b"), ("q", "x")])
+        );
+        assert!(matches!(
+            apply_duplicate_policy(pairs, DuplicateParamPolicy::Error),
+            Err(DatadogError::InvalidRequest(message)) if message.contains("`tag`")
+        ));
+        let unique = params(&[("a", "1"), ("b", "2")]);
+        assert_eq!(
+            apply_duplicate_policy(unique.clone(), DuplicateParamPolicy::Error).unwrap(),
+            unique
+        );
+    }
+
+    #[test]
+    fn build_mute_body_sets_end_only_when_given() {
+        assert_eq!(
+            build_mute_body(Some(1_714_564_800)),
+            json!({ "end": 1_714_564_800 })
+        );
+        assert_eq!(build_mute_body(None), json!({}));
+    }
+
+    #[test]
+    fn build_tags_body_wraps_tags() {
+        let body = build_tags_body(vec!["env:prod".to_string(), "role:db".to_string()]).unwrap();
+
+        assert_eq!(body, json!({ "tags": ["env:prod", "role:db"] }));
+    }
+
+    #[test]
+    fn build_tags_body_rejects_tags_without_key_and_value() {
+        for invalid in ["prod", ":prod", "env:"] {
+            let result = build_tags_body(vec!["env:prod".to_string(), invalid.to_string()]);
+            assert!(matches!(
+                result,
+                Err(DatadogError::InvalidRequest(message)) if message.contains(invalid)
+            ));
+        }
+    }
+
+    #[test]
+    fn build_logs_metric_body_uses_compute_filter_and_group_by() {
+        let body = build_logs_metric_body(LogsMetricDefinition {
+            name: "web.request.duration".to_string(),
+            query: "service:web".to_string(),
+            aggregation_type: "Distribution".to_string(),
+            path: Some("@duration".to_string()),
+            include_percentiles: true,
+            group_by: vec![
+                "@http.status_code:status_code".to_string(),
+                "@env".to_string(),
+            ],
+        })
+        .unwrap();
+
+        assert_eq!(
+            body,
+            json!({
+                "data": {
+                    "id": "web.request.duration",
+                    "type": "logs_metrics",
+                    "attributes": {
+                        "compute": {
+                            "aggregation_type": "distribution",
+                            "path": "@duration",
+                            "include_percentiles": true
+                        },
+                        "filter": { "query": "service:web" },
+                        "group_by": [
+                            { "path": "@http.status_code", "tag_name": "status_code" },
+                            { "path": "@env", "tag_name": "env" }
+                        ]
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn build_logs_metric_body_requires_path_only_for_distribution() {
+        let definition = |aggregation_type: &str, path: Option<&str>| LogsMetricDefinition {
+            name: "m".to_string(),
+            query: "*".to_string(),
+            aggregation_type: aggregation_type.to_string(),
+            path: path.map(str::to_string),
+            include_percentiles: false,
+            group_by: Vec::new(),
+        };
+
+        assert!(build_logs_metric_body(definition("distribution", None)).is_err());
+        assert!(build_logs_metric_body(definition("count", Some("@duration"))).is_err());
+        assert!(build_logs_metric_body(definition("count", None)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn truncated_json_body_is_retried() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("200 OK", "application/json", r#"{"data":[{"id":"#),
+            http_response("200 OK", "application/json", r#"{"data":[]}"#),
+        ]);
+        let client = test_client(&base_url, 1);
+
+        let value = client
+            .send_json(Method::GET, "/api/v2/events", None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(value, json!({ "data": [] }));
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn non_json_body_is_wrapped_without_retry() {
+        let (base_url, requests) = mock_server(vec![http_response("200 OK", "text/plain", "pong")]);
+        let client = test_client(&base_url, 1);
+
+        let value = client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(value, json!({ "raw": "pong" }));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn raw_post_without_idempotency_key_does_not_resend_after_transport_error() {
+        let (base_url, requests) = mock_server(vec![
+            String::new(),
+            http_response("200 OK", "application/json", "{}"),
+        ]);
+        let client = test_client(&base_url, 2);
+
+        let result = client.raw(raw_post(None)).await;
+
+        assert!(matches!(
+            result,
+            Err(DatadogError::Retryable { status: None, .. })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn post_answered_with_503_is_sent_once() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("202 Accepted", "application/json", "{}"),
+        ]);
+        let client = test_client(&base_url, 2);
+
+        let result = client
+            .submit_metrics(MetricSubmission {
+                metric: "custom.exporter.lag".to_string(),
+                value: 1.5,
+                timestamp: 1_700_000_000,
+                metric_type: "gauge".to_string(),
+                tags: Vec::new(),
+            })
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(DatadogError::Retryable {
+                status: Some(503),
+                ..
+            })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(client.raw(raw_post(None)).await.is_err());
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn raw_post_with_idempotency_key_resends_after_transport_error() {
+        let (base_url, requests) = mock_server(vec![
+            String::new(),
+            http_response("200 OK", "application/json", r#"{"ok":true}"#),
+        ]);
+        let client = test_client(&base_url, 2);
+
+        let value = client
+            .raw(raw_post(Some("create-42".to_string())))
+            .await
+            .unwrap();
+
+        assert_eq!(value, json!({ "ok": true }));
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(
+            requests[1]
+                .to_ascii_lowercase()
+                .contains("idempotency-key: create-42")
+        );
+    }
+
+    #[tokio::test]
+    async fn retry_log_records_each_retry() {
+        let (base_url, _requests) = mock_server(vec![
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("200 OK", "application/json", r#"{"ok":true}"#),
+        ]);
+        let log_path = std::env::temp_dir().join(format!(
+            "ddq-retry-log-{}-{}.jsonl",
+            std::process::id(),
+            line!()
+        ));
+        let _ = std::fs::remove_file(&log_path);
+        let mut client = test_client(&base_url, 3);
+        client.retry.retry_log = Some(log_path.clone());
+
+        client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await
+            .unwrap();
+
+        let contents = std::fs::read_to_string(&log_path).unwrap();
+        std::fs::remove_file(&log_path).unwrap();
+        let entries: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries.len(), 2);
+        for (index, entry) in entries.iter().enumerate() {
+            assert_eq!(entry["attempt"], index as u64 + 1);
+            assert_eq!(entry["trigger"], json!({ "status": 503 }));
+            assert_eq!(entry["sleep_ms"], 1);
+            assert_eq!(entry["server_dictated"], false);
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_log_write_failure_is_a_warning() {
+        let (base_url, _requests) = mock_server(vec![
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("200 OK", "application/json", r#"{"ok":true}"#),
+        ]);
+        let mut client = test_client(&base_url, 3);
+        // A directory cannot be opened for appending.
+        client.retry.retry_log = Some(std::env::temp_dir());
+
+        client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await
+            .unwrap();
+
+        assert!(client.warnings().failure(false).is_none());
+        assert!(matches!(
+            client.warnings().failure(true),
+            Some(AppError::WarningsPresent(warnings)) if warnings[0].contains("retry log")
+        ));
+    }
+
+    #[test]
+    fn constant_backoff_sleeps_the_base_delay_every_retry() {
+        let mut client = test_client("https://api.invalid", 3);
+        client.retry.backoff_ms = 100;
+        client.retry.max_backoff_ms = 1_000;
+        assert_eq!(
+            (0..3)
+                .map(|attempt| client.backoff_ms(attempt))
+                .collect::<Vec<_>>(),
+            [100, 200, 400]
+        );
+
+        client.retry.strategy = BackoffStrategy::Constant;
+        assert_eq!(
+            (0..3)
+                .map(|attempt| client.backoff_ms(attempt))
+                .collect::<Vec<_>>(),
+            [100, 100, 100]
+        );
+    }
+
+    #[test]
+    fn retry_message_names_the_next_attempt_delay_and_cause() {
+        assert_eq!(
+            retry_message(
+                2,
+                500,
+                &RetryTrigger::Status(StatusCode::SERVICE_UNAVAILABLE)
+            ),
+            "attempt 2 after 500ms backoff due to 503"
+        );
+        assert_eq!(
+            retry_message(3, 40, &RetryTrigger::IncompleteBody(StatusCode::OK)),
+            "attempt 3 after 40ms backoff due to incomplete body (200)"
+        );
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        let now = chrono::DateTime::parse_from_rfc3339("2015-10-21T07:27:30Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+
+        assert_eq!(retry_after_value_ms(" 2 ", now), Some(2_000));
+        assert_eq!(
+            retry_after_value_ms("Wed, 21 Oct 2015 07:28:00 GMT", now),
+            Some(30_000)
+        );
+        assert_eq!(
+            retry_after_value_ms("Wed, 21 Oct 2015 07:27:00 GMT", now),
+            Some(0)
+        );
+        assert_eq!(retry_after_value_ms("soon", now), None);
+    }
+
+    #[test]
+    fn retry_after_delay_is_capped() {
+        let mut client = test_client("https://api.invalid", 3);
+        client.retry.retry_after_max_ms = 60_000;
+
+        assert_eq!(client.retry_delay_ms(0, Some(3_600_000)), 60_000);
+        assert_eq!(client.retry_delay_ms(0, Some(2_000)), 2_000);
+        assert_eq!(client.retry_delay_ms(0, None), 1);
+    }
+
+    #[test]
+    fn jitter_stays_within_its_range() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..100 {
+            assert_eq!(jittered_ms(400, RetryJitter::Off, &mut rng), 400);
+            assert!(jittered_ms(400, RetryJitter::Full, &mut rng) <= 400);
+            assert!((200..=400).contains(&jittered_ms(400, RetryJitter::Equal, &mut rng)));
+        }
+
+        let draws = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..5)
+                .map(|_| jittered_ms(1_000, RetryJitter::Full, &mut rng))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(draws(42), draws(42));
+    }
+
+    #[tokio::test]
+    async fn total_timeout_stops_retrying_slow_failures() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("200 OK", "application/json", "{}"),
+        ]);
+        let mut client = test_client(&base_url, 5);
+        client.retry.backoff_ms = 200;
+        client.retry.max_backoff_ms = 200;
+        client.total_timeout_seconds = Some(1);
+        client.deadline = Some(Instant::now() + Duration::from_millis(100));
+
+        let result = client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(DatadogError::Retryable { status: None, message, .. }) if message.contains("Total timeout")
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn await_completion_polls_location_until_done() {
+        let accepted = "HTTP/1.1 202 Accepted\r\nLocation: /api/v2/jobs/7\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+        let (base_url, requests) = mock_server(vec![
+            accepted.to_string(),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"attributes":{"status":"pending"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"attributes":{"status":"done"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 0);
+        let mut request = raw_post(None);
+        request.await_completion = Some(AwaitCompletion {
+            status_field: "data.attributes.status".to_string(),
+            poll_interval_ms: 1,
+            timeout_seconds: 5,
+        });
+
+        let value = client.raw(request).await.unwrap();
+
+        assert_eq!(value["data"]["attributes"]["status"], "done");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].starts_with("GET /api/v2/jobs/7 "));
+    }
+
+    #[tokio::test]
+    async fn await_completion_refuses_a_location_on_another_host() {
+        let accepted = "HTTP/1.1 202 Accepted\r\nLocation: http://collector.example/jobs/7\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+        let (base_url, requests) = mock_server(vec![accepted.to_string()]);
+        let client = test_client(&base_url, 0);
+        let mut request = raw_post(None);
+        request.await_completion = Some(AwaitCompletion {
+            status_field: "data.attributes.status".to_string(),
+            poll_interval_ms: 1,
+            timeout_seconds: 5,
+        });
+
+        let err = client.raw(request).await.unwrap_err();
+
+        assert!(err.to_string().contains("Refusing to poll"));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn await_completion_stops_polling_at_the_total_timeout() {
+        let accepted = "HTTP/1.1 202 Accepted\r\nLocation: /api/v2/jobs/7\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{}";
+        let (base_url, requests) = mock_server(vec![accepted.to_string()]);
+        let mut client = test_client(&base_url, 0);
+        client.total_timeout_seconds = Some(1);
+        client.deadline = Some(Instant::now() + Duration::from_millis(200));
+        let mut request = raw_post(None);
+        request.await_completion = Some(AwaitCompletion {
+            status_field: "data.attributes.status".to_string(),
+            poll_interval_ms: 60_000,
+            timeout_seconds: 600,
+        });
+
+        let started = Instant::now();
+        let result = client.raw(request).await;
+
+        assert!(result.is_err());
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn rehydrate_logs_with_wait_polls_until_done() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"id":"r1","attributes":{"status":"pending"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"id":"r1","attributes":{"status":"in_progress"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"id":"r1","attributes":{"status":"done"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        let value = client
+            .rehydrate_logs(LogsRehydration {
+                archive_id: "arch-1".to_string(),
+                name: "incident".to_string(),
+                query: "service:web".to_string(),
+                from: "2024-05-01T00:00:00Z".to_string(),
+                to: "2024-05-01T06:00:00Z".to_string(),
+                wait: Some(AwaitCompletion {
+                    status_field: "data.attributes.status".to_string(),
+                    poll_interval_ms: 1,
+                    timeout_seconds: 5,
+                }),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value["data"]["attributes"]["status"], "done");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[0].starts_with("POST /api/v2/logs/config/archives/arch-1/rehydrations "));
+        assert!(requests[0].contains(r#""query":"service:web""#));
+        assert!(
+            requests[2].starts_with("GET /api/v2/logs/config/archives/arch-1/rehydrations/r1 ")
+        );
+    }
+
+    #[test]
+    fn encode_body_gzips_only_large_bodies_when_enabled() {
+        let large = json!({ "data": "x".repeat(4_096) });
+
+        let (bytes, gzipped) = encode_body(&large, true).unwrap();
+        assert!(gzipped);
+        let mut decoded = String::new();
+        GzDecoder::new(bytes.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&decoded).unwrap(),
+            large
+        );
+
+        assert!(!encode_body(&large, false).unwrap().1);
+        assert!(!encode_body(&json!({ "small": true }), true).unwrap().1);
+    }
+
+    #[tokio::test]
+    async fn compress_request_sets_content_encoding_header() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "202 Accepted",
+            "application/json",
+            "{}",
+        )]);
+        let mut client = test_client(&base_url, 0);
+        client.compress_request = true;
+        let mut request = raw_post(None);
+        request.body = Some(json!({ "data": "x".repeat(4_096) }));
+
+        client.raw(request).await.unwrap();
+
+        let requests = requests.lock().unwrap();
+        let head = requests[0].to_ascii_lowercase();
+        assert!(head.contains("content-encoding: gzip"));
+        assert!(!head.contains(&"x".repeat(64)));
+    }
+
+    #[tokio::test]
+    async fn annotate_units_attaches_cached_metadata_unit() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            "application/json",
+            r#"{"type":"gauge","unit":"byte","per_unit":"second"}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+        let mut response = json!({
+            "series": [
+                { "metric": "system.net.bytes_rcvd", "scope": "host:a" },
+                { "metric": "system.net.bytes_rcvd", "scope": "host:b" }
+            ]
+        });
+
+        client
+            .annotate_units(&mut response, &mut HashMap::new())
+            .await
+            .unwrap();
+
+        for series in response["series"].as_array().unwrap() {
+            assert_eq!(
+                series["metric_unit"],
+                json!({ "unit": "byte", "per_unit": "second" })
+            );
+        }
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].starts_with("GET /api/v1/metrics/system.net.bytes_rcvd "));
+    }
+
+    #[test]
+    fn mask_secrets_hides_keys_and_ignores_too_short_values() {
+        let body = r#"{"errors":["bad header DD-API-KEY=abc12345 app=xyz78901"]}"#;
+
+        assert_eq!(
+            mask_secrets(body, &["abc12345", "xyz78901", "", "a"]),
+            r#"{"errors":["bad header DD-API-KEY=***REDACTED*** app=***REDACTED***"]}"#
+        );
+    }
+
+    #[tokio::test]
+    async fn error_bodies_echoing_keys_are_masked() {
+        let (base_url, _requests) = mock_server(vec![http_response(
+            "400 Bad Request",
+            "application/json",
+            r#"{"errors":["unexpected header value test-api-key"]}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+
+        let result = client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await;
+
+        match result {
+            Err(DatadogError::Api { status, body }) => {
+                assert_eq!(status, 400);
+                assert!(!body.contains("test-api-key"));
+                assert!(body.contains("***REDACTED***"));
+            }
+            _ => panic!("expected an API error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn transport_errors_quoting_a_key_in_the_url_are_masked() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        drop(listener);
+        let client = test_client(&base_url, 0);
+
+        let result = client
+            .send_json(
+                Method::GET,
+                &format!("{base_url}/api/v1/validate?api_key=test-api-key"),
+                None,
+                None,
+            )
+            .await;
+
+        match result {
+            Err(DatadogError::Retryable { message, .. }) => {
+                assert!(!message.contains("test-api-key"));
+                assert!(message.contains("***REDACTED***"));
+            }
+            other => panic!("expected a transport error, got {other:?}"),
+        }
+    }
+
+    fn events_query(limit: u32, page_size: Option<u32>) -> EventsQuery {
+        EventsQuery {
+            query: None,
+            from: "now-1h".to_string(),
+            to: "now".to_string(),
+            limit,
+            page_size,
+            max_pages: None,
+            sort: "desc".to_string(),
+            priority: None,
+            alert_type: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn events_size_the_last_page_to_the_limit() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"a"},{"id":"b"}],"meta":{"page":{"after":"c1"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"c"}],"meta":{"page":{"after":"c2"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        let value = client.query_events(events_query(3, Some(2))).await.unwrap();
+
+        assert_eq!(
+            value["data"],
+            json!([{ "id": "a" }, { "id": "b" }, { "id": "c" }])
+        );
+        assert_eq!(value["meta"]["page"]["after"], "c2");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains("page%5Blimit%5D=2"));
+        assert!(requests[1].contains("page%5Blimit%5D=1"));
+        assert!(requests[1].contains("page%5Bcursor%5D=c1"));
+    }
+
+    #[tokio::test]
+    async fn processes_query_sends_filters_and_unix_times() {
+        let (base_url, requests) =
+            mock_server(vec![http_response("200 OK", "application/json", "{}")]);
+        let client = test_client(&base_url, 0);
+        let now = chrono::Utc::now();
+        let from = parse_to_unix("2024-05-01T00:00:00Z", now, chrono_tz::UTC).unwrap();
+        let to = parse_to_unix("2024-05-01T01:00:00+01:00", now, chrono_tz::UTC).unwrap();
+
+        client
+            .query_processes(ProcessesQuery {
+                search: Some("postgres".to_string()),
+                tags: Some("env:prod,role:db".to_string()),
+                from,
+                to,
+                limit: 100,
+                cursor: None,
+            })
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        let request_line = requests[0].lines().next().unwrap();
+        assert!(request_line.starts_with("GET /api/v2/processes?"));
+        assert!(request_line.contains("from=1714521600&to=1714521600&page%5Blimit%5D=100"));
+        assert!(request_line.contains("&search=postgres&tags=env%3Aprod%2Crole%3Adb"));
+    }
+
+    #[tokio::test]
+    async fn events_stop_at_page_cap_with_next_cursor() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"a"}],"meta":{"page":{"after":"c1"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"b"}],"meta":{"page":{"after":"c2"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 0);
+        let mut query = events_query(10, Some(1));
+        query.max_pages = Some(2);
+
+        let value = client.query_events(query).await.unwrap();
+
+        assert_eq!(value["data"], json!([{ "id": "a" }, { "id": "b" }]));
+        assert_eq!(value["complete"], false);
+        assert_eq!(value["meta"]["pages_fetched"], 2);
+        assert_eq!(value["meta"]["page"]["after"], "c2");
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn auth_error_on_the_first_page_stops_pagination() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "403 Forbidden",
+                "application/json",
+                r#"{"errors":["Forbidden"]}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"a"}],"meta":{"page":{"after":"c1"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 2);
+
+        let result = client.query_events(events_query(10, Some(1))).await;
+
+        assert!(matches!(
+            result,
+            Err(DatadogError::Auth { status: 403, .. })
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    /// Resolves no host, so DNS failures can be tested without a network.
+    struct FailingResolver;
+
+    impl Resolve for FailingResolver {
+        fn resolve(&self, _name: Name) -> Resolving {
+            Box::pin(async { Err(io::Error::new(io::ErrorKind::NotFound, "no such host").into()) })
+        }
+    }
+
+    #[tokio::test]
+    async fn transport_errors_are_labelled_by_kind() {
+        let http = reqwest::Client::new();
+
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_url = format!("http://{}", closed.local_addr().unwrap());
+        drop(closed);
+        let err = http.get(&closed_url).send().await.unwrap_err();
+        assert_eq!(transport_kind(&err), "connect");
+
+        let unresolvable = reqwest::Client::builder()
+            .dns_resolver(Arc::new(FailingResolver))
+            .build()
+            .unwrap();
+        let err = unresolvable
+            .get("http://api.datadoghq.test/")
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(transport_kind(&err), "dns");
+
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let silent_url = format!("http://{}", silent.local_addr().unwrap());
+        let err = http
+            .get(&silent_url)
+            .timeout(Duration::from_millis(50))
+            .send()
+            .await
+            .unwrap_err();
+        assert_eq!(transport_kind(&err), "timeout");
+        drop(silent);
+
+        let (base_url, _requests) = mock_server(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 100\r\nConnection: close\r\n\r\n{\"da",
+        ]);
+        let response = http.get(&base_url).send().await.unwrap();
+        let err = response.bytes().await.unwrap_err();
+        assert_eq!(transport_kind(&err), "body");
+    }
+
+    #[tokio::test]
+    async fn timings_count_every_attempt_of_every_request() {
+        let (base_url, _requests) = mock_server(vec![
+            http_response("503 Service Unavailable", "application/json", "{}"),
+            http_response("200 OK", "application/json", r#"{"data":[]}"#),
+            http_response("200 OK", "application/json", r#"{"data":[]}"#),
+        ]);
+        let client = test_client(&base_url, 1);
+
+        for _ in 0..2 {
+            client
+                .send_json(Method::GET, "/api/v2/events", None, None)
+                .await
+                .unwrap();
+        }
+
+        let timings = client.timings();
+        assert_eq!(timings["requests"], 2);
+        assert_eq!(timings["attempts"], 3);
+        assert!(timings["elapsed_ms"].is_u64());
+    }
+
+    #[tokio::test]
+    async fn rate_limited_page_is_waited_out_when_retries_are_exhausted() {
+        let responses = || {
+            vec![
+                http_response(
+                    "200 OK",
+                    "application/json",
+                    r#"{"data":[{"id":"a"}],"meta":{"page":{"after":"c1"}}}"#,
+                ),
+                http_response(
+                    "429 Too Many Requests",
+                    "application/json",
+                    r#"{"errors":["slow down"]}"#,
+                ),
+                http_response("200 OK", "application/json", r#"{"data":[{"id":"b"}]}"#),
+            ]
+        };
+
+        let (base_url, requests) = mock_server(responses());
+        let mut client = test_client(&base_url, 0);
+        client.retry.on_rate_limit = RateLimitPolicy::Wait;
+        let value = client
+            .query_events(events_query(10, Some(1)))
+            .await
+            .unwrap();
+        assert_eq!(value["data"], json!([{ "id": "a" }, { "id": "b" }]));
+        assert_eq!(requests.lock().unwrap().len(), 3);
+
+        let (base_url, _requests) = mock_server(responses());
+        let client = test_client(&base_url, 0);
+        let result = client.query_events(events_query(10, Some(1))).await;
+        assert!(matches!(result, Err(DatadogError::RateLimited { .. })));
+    }
+
+    #[tokio::test]
+    async fn events_stop_when_no_cursor_remains() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            "application/json",
+            r#"{"data":[{"id":"a"}],"meta":{"page":{}}}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+
+        let value = client.query_events(events_query(50, None)).await.unwrap();
+
+        assert_eq!(value["data"], json!([{ "id": "a" }]));
+        assert!(value.get("complete").is_none());
+        assert_eq!(value["meta"]["pages_fetched"], 1);
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(requests.lock().unwrap()[0].contains("page%5Blimit%5D=50"));
+    }
+
+    #[tokio::test]
+    async fn events_send_priority_and_alert_type_filters() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            "application/json",
+            r#"{"data":[],"meta":{"page":{}}}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+        let mut query = events_query(50, None);
+        query.priority = Some("Low".to_string());
+        query.alert_type = Some("error".to_string());
+
+        client.query_events(query).await.unwrap();
+
+        let request = &requests.lock().unwrap()[0];
+        assert!(request.contains("filter%5Bpriority%5D=low"));
+        assert!(request.contains("filter%5Balert_type%5D=error"));
+    }
+
+    #[tokio::test]
+    async fn events_reject_unknown_priority_and_alert_type() {
+        let client = test_client("https://api.invalid", 0);
+
+        let mut query = events_query(50, None);
+        query.priority = Some("urgent".to_string());
+        let result = client.query_events(query).await;
+        assert!(
+            matches!(result, Err(DatadogError::InvalidRequest(message)) if message.contains("normal, low"))
+        );
+
+        let mut query = events_query(50, None);
+        query.alert_type = Some("critical".to_string());
+        let result = client.query_events(query).await;
+        assert!(
+            matches!(result, Err(DatadogError::InvalidRequest(message)) if message.contains("error, warning, info, success"))
+        );
+    }
+
+    #[tokio::test]
+    async fn logs_all_follows_cursor_until_page_cap() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"a"}],"meta":{"page":{"after":"c1"}}}"#,
+            ),
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"b"}],"meta":{"page":{"after":"c2"}}}"#,
+            ),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        let value = client
+            .query_logs(LogsQuery {
+                query: "*".to_string(),
+                from: "now-1h".to_string(),
+                to: "now".to_string(),
+                limit: 1,
+                sort: "desc".to_string(),
+                cursor: None,
+                all: true,
+                max_pages: Some(2),
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value["data"], json!([{ "id": "a" }, { "id": "b" }]));
+        assert_eq!(value["complete"], false);
+        assert_eq!(value["meta"]["pages_fetched"], 2);
+        assert_eq!(value["meta"]["page"]["after"], "c2");
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests[0].contains(r#""page":{"limit":1}"#));
+        assert!(requests[1].contains(r#""page":{"cursor":"c1","limit":1}"#));
+    }
+
+    #[tokio::test]
+    async fn logs_all_keeps_merged_pages_when_the_total_timeout_cuts_a_later_page() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for page in [
+                r#"{"data":[{"id":"a"}],"meta":{"page":{"after":"c1"}}}"#,
+                r#"{"data":[{"id":"b"}],"meta":{"page":{"after":"c2"}}}"#,
+            ] {
+                let (mut stream, _) = listener.accept().unwrap();
+                read_request(&stream);
+                let response = http_response("200 OK", "application/json", page);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+            // The third page never answers.
+            let (stream, _) = listener.accept().unwrap();
+            read_request(&stream);
+            thread::sleep(Duration::from_secs(2));
+        });
+        let mut client = test_client(&base_url, 0);
+        client.total_timeout_seconds = Some(1);
+        client.deadline = Some(Instant::now() + Duration::from_millis(300));
+
+        let value = client
+            .query_logs(LogsQuery {
+                query: "*".to_string(),
+                from: "now-1h".to_string(),
+                to: "now".to_string(),
+                limit: 1,
+                sort: "desc".to_string(),
+                cursor: None,
+                all: true,
+                max_pages: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value["data"], json!([{ "id": "a" }, { "id": "b" }]));
+        assert_eq!(value["complete"], false);
+        assert_eq!(value["meta"]["pages_fetched"], 2);
+        assert_eq!(value["meta"]["page"]["after"], "c2");
+    }
+
+    #[test]
+    fn logs_plan_posts_search_body() {
+        let plan = logs_request(&LogsQuery {
+            query: "service:web".to_string(),
+            from: "now-15m".to_string(),
+            to: "now".to_string(),
+            limit: 100,
+            sort: "asc".to_string(),
+            cursor: Some("c1".to_string()),
+            all: false,
+            max_pages: None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            plan.to_json(),
+            json!({
+                "method": "POST",
+                "path": "/api/v2/logs/events/search",
+                "params": [],
+                "body": {
+                    "filter": { "query": "service:web", "from": "now-15m", "to": "now" },
+                    "sort": "timestamp",
+                    "page": { "limit": 100, "cursor": "c1" }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn restriction_policy_path_encodes_the_resource_id() {
+        assert_eq!(
+            restriction_policy_path("dashboard:abc-def-ghi"),
+            "/api/v2/restriction_policy/dashboard:abc-def-ghi"
+        );
+        assert_eq!(
+            restriction_policy_path("notebook:team a/b?x=1"),
+            "/api/v2/restriction_policy/notebook:team%20a%2Fb%3Fx%3D1"
+        );
+    }
+
+    #[test]
+    fn host_tags_path_encodes_the_host() {
+        assert_eq!(
+            host_tags_path("web-1.example.com").unwrap(),
+            "/api/v1/tags/hosts/web-1.example.com"
+        );
+        assert_eq!(
+            host_tags_path("a/../../v2/x?y=1").unwrap(),
+            "/api/v1/tags/hosts/a%2F..%2F..%2Fv2%2Fx%3Fy%3D1"
+        );
+        assert!(matches!(
+            host_tags_path(".."),
+            Err(DatadogError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn logs_metric_and_archive_ids_are_encoded_as_one_segment() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("200 OK", "application/json", "{}"),
+            http_response("200 OK", "application/json", "{}"),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        client
+            .query_logs_metrics(Some("../../v1/validate"))
+            .await
+            .unwrap();
+        client
+            .rehydrate_logs(LogsRehydration {
+                archive_id: "arch?x=1".to_string(),
+                name: "r".to_string(),
+                query: "*".to_string(),
+                from: "2024-05-01T00:00:00Z".to_string(),
+                to: "2024-05-01T01:00:00Z".to_string(),
+                wait: None,
+            })
+            .await
+            .unwrap();
+        assert!(matches!(
+            client.query_logs_metrics(Some(".")).await,
+            Err(DatadogError::InvalidRequest(_))
+        ));
+
+        let requests = requests.lock().unwrap();
+        assert!(
+            requests[0].starts_with("GET /api/v2/logs/config/metrics/..%2F..%2Fv1%2Fvalidate ")
+        );
+        assert!(
+            requests[1].starts_with("POST /api/v2/logs/config/archives/arch%3Fx%3D1/rehydrations ")
+        );
+    }
+
+    #[tokio::test]
+    async fn restriction_policy_request_keeps_the_encoded_id() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            "application/json",
+            r#"{"data":{"id":"slo:a/b"}}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+
+        client
+            .query_rbac(RbacQuery::RestrictionPolicy {
+                resource_id: "slo:a/b".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("GET /api/v2/restriction_policy/slo:a%2Fb HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn slo_history_sends_the_window_as_unix_seconds() {
+        let (base_url, requests) = mock_server(vec![http_response(
+            "200 OK",
+            "application/json",
+            r#"{"data":{"overall":{"sli_value":99.9}}}"#,
+        )]);
+        let client = test_client(&base_url, 0);
+
+        client
+            .slo_history("abc123", 1_700_000_000, 1_700_086_400)
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert!(
+            requests[0]
+                .starts_with("GET /api/v1/slo/abc123/history?from_ts=1700000000&to_ts=1700086400 ")
+        );
+    }
+
+    #[tokio::test]
+    async fn roles_all_pages_until_a_short_page() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":[{"id":"r1"},{"id":"r2"}],"meta":{"page":{"total_count":3}}}"#,
+            ),
+            http_response("200 OK", "application/json", r#"{"data":[{"id":"r3"}]}"#),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        let value = client
+            .query_rbac(RbacQuery::Roles {
+                filter: None,
+                page_size: 2,
+                page_number: 0,
+                all: true,
+                max_pages: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value["data"].as_array().unwrap().len(), 3);
+        assert_eq!(value["meta"]["pages_fetched"], 2);
+        let requests = requests.lock().unwrap();
+        assert!(requests[1].starts_with("GET /api/v2/roles?page%5Bsize%5D=2&page%5Bnumber%5D=1 "));
+    }
+
+    #[test]
+    fn metrics_plan_lists_query_params() {
+        assert_eq!(
+            metrics_request("avg:system.cpu.user{*}", 100, 200).to_json(),
+            json!({
+                "method": "GET",
+                "path": "/api/v1/query",
+                "params": [["query", "avg:system.cpu.user{*}"], ["from", "100"], ["to", "200"]],
+                "body": null
+            })
+        );
+    }
+
+    fn logs_aggregation(compute: &[&str], group_by: &[&str]) -> LogsAggregation {
+        LogsAggregation {
+            query: "service:web".to_string(),
+            from: "now-1h".to_string(),
+            to: "now".to_string(),
+            compute: compute.iter().map(|spec| spec.to_string()).collect(),
+            group_by: group_by.iter().map(|facet| facet.to_string()).collect(),
+            group_limit: 5,
+        }
+    }
+
+    #[test]
+    fn logs_aggregate_plan_builds_compute_and_group_by() {
+        let plan =
+            logs_aggregate_request(&logs_aggregation(&["count", "PC95:@latency"], &["service"]))
+                .unwrap();
+
+        assert_eq!(
+            plan.to_json(),
+            json!({
+                "method": "POST",
+                "path": "/api/v2/logs/analytics/aggregate",
+                "params": [],
+                "body": {
+                    "filter": { "query": "service:web", "from": "now-1h", "to": "now" },
+                    "compute": [
+                        { "aggregation": "count", "type": "total" },
+                        { "aggregation": "pc95", "metric": "@latency", "type": "total" }
+                    ],
+                    "group_by": [{ "facet": "service", "limit": 5 }]
+                }
+            })
+        );
+        let default = logs_aggregate_request(&logs_aggregation(&[], &[])).unwrap();
+        assert_eq!(
+            default.body.unwrap()["compute"],
+            json!([{ "aggregation": "count", "type": "total" }])
+        );
+    }
+
+    #[tokio::test]
+    async fn count_logs_reads_the_aggregate_count() {
+        let (base_url, requests) = mock_server(vec![
+            http_response(
+                "200 OK",
+                "application/json",
+                r#"{"data":{"buckets":[{"by":{},"computes":{"c0":1234}}]},"meta":{"status":"done"}}"#,
+            ),
+            http_response("200 OK", "application/json", r#"{"data":{"buckets":[]}}"#),
+        ]);
+        let client = test_client(&base_url, 0);
+        let aggregation = LogsAggregation::count(
+            "status:error".to_string(),
+            "now-1h".to_string(),
+            "now".to_string(),
+        );
+        let count = || client.count_logs(&aggregation);
+
+        assert_eq!(count().await.unwrap(), json!({ "count": 1234 }));
+        assert_eq!(count().await.unwrap(), json!({ "count": 0 }));
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with("POST /api/v2/logs/analytics/aggregate "));
+        assert!(requests[0].contains(r#""compute":[{"aggregation":"count","type":"total"}]"#));
+    }
+
+    #[test]
+    fn logs_aggregate_plan_rejects_bad_compute_specs() {
+        for spec in ["avg", "p95:@latency", "sum:"] {
+            assert!(
+                logs_aggregate_request(&logs_aggregation(&[spec], &[])).is_err(),
+                "{spec} should be rejected"
+            );
+        }
+    }
+
+    fn timeseries_query(queries: &[&str], formulas: &[&str]) -> TimeseriesQuery {
+        TimeseriesQuery {
+            queries: queries.iter().map(|query| query.to_string()).collect(),
+            formulas: formulas.iter().map(|formula| formula.to_string()).collect(),
+            from_ms: 100_000,
+            to_ms: 200_000,
+            interval_ms: None,
+        }
+    }
+
+    #[test]
+    fn timeseries_plan_names_queries_and_adds_formulas() {
+        let mut query = timeseries_query(
+            &["a:avg:system.cpu.user{*}", "sum:system.load.1{host:web}"],
+            &["abs(a) / query2 * 100"],
+        );
+        query.interval_ms = Some(60_000);
+
+        assert_eq!(
+            timeseries_request(&query).unwrap().to_json(),
+            json!({
+                "method": "POST",
+                "path": "/api/v2/query/timeseries",
+                "params": [],
+                "body": {
+                    "data": {
+                        "type": "timeseries_request",
+                        "attributes": {
+                            "from": 100_000,
+                            "to": 200_000,
+                            "interval": 60_000,
+                            "queries": [
+                                {"data_source": "metrics", "name": "a", "query": "avg:system.cpu.user{*}"},
+                                {"data_source": "metrics", "name": "query2", "query": "sum:system.load.1{host:web}"}
+                            ],
+                            "formulas": [{"formula": "abs(a) / query2 * 100"}]
+                        }
+                    }
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn timeseries_plan_rejects_undefined_formula_references() {
+        let err = timeseries_request(&timeseries_query(&["a:avg:system.cpu.user{*}"], &["a / b"]))
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("undefined query `b`"));
+
+        let duplicate = timeseries_request(&timeseries_query(&["a:avg:x{*}", "a:avg:y{*}"], &[]));
+        assert!(duplicate.is_err());
+    }
+
+    #[test]
+    fn timeseries_plan_does_not_read_percentile_aggregators_as_names() {
+        let query = timeseries_query(
+            &[
+                "p95:trace.http.request.duration{*}",
+                "lat:p99:trace.http.request.duration{*}",
+            ],
+            &["lat - query1"],
+        );
+
+        let plan = timeseries_request(&query).unwrap().to_json();
+
+        let queries = &plan["body"]["data"]["attributes"]["queries"];
+        assert_eq!(queries[0]["name"], "query1");
+        assert_eq!(queries[0]["query"], "p95:trace.http.request.duration{*}");
+        assert_eq!(queries[1]["name"], "lat");
+        assert_eq!(queries[1]["query"], "p99:trace.http.request.duration{*}");
+    }
+
+    #[test]
+    fn events_plan_describes_first_page() {
+        let mut query = events_query(5000, None);
+        query.query = Some("source:nagios".to_string());
+        query.alert_type = Some("error".to_string());
+
+        assert_eq!(
+            events_request(&query).unwrap().to_json(),
+            json!({
+                "method": "GET",
+                "path": "/api/v2/events",
+                "params": [
+                    ["filter[from]", "now-1h"],
+                    ["filter[to]", "now"],
+                    ["page[limit]", "1000"],
+                    ["sort", "-timestamp"],
+                    ["filter[query]", "source:nagios"],
+                    ["filter[alert_type]", "error"]
+                ],
+                "body": null
+            })
+        );
+        query.sort = "sideways".to_string();
+        assert!(matches!(
+            events_request(&query),
+            Err(DatadogError::InvalidRequest(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn raw_returns_body_of_ignored_status() {
+        let not_found = r#"{"errors":["Monitor not found"]}"#;
+        let (base_url, _requests) = mock_server(vec![
+            http_response("404 Not Found", "application/json", not_found),
+            http_response("404 Not Found", "application/json", not_found),
+        ]);
+        let client = test_client(&base_url, 0);
+        let delete = |ignore_statuses| RawRequest {
+            method: "DELETE".to_string(),
+            path: "/api/v1/monitor/42".to_string(),
+            ignore_statuses,
+            ..raw_post(None)
+        };
+
+        let value = client.raw(delete(vec![404])).await.unwrap();
+        assert_eq!(value, json!({ "errors": ["Monitor not found"] }));
+
+        let err = client.raw(delete(Vec::new())).await.unwrap_err();
+        assert!(matches!(err, DatadogError::Api { status: 404, .. }));
+        assert_eq!(AppError::from(err).exit_code(), 6);
+    }
+
+    #[tokio::test]
+    async fn raw_chunks_an_array_body_into_separate_requests() {
+        let ok = |n: usize| http_response("200 OK", "application/json", &format!(r#"{{"n":{n}}}"#));
+        let (base_url, requests) = mock_server(vec![ok(1), ok(2), ok(3)]);
+        let client = test_client(&base_url, 0);
+        let items: Vec<_> = (0..250).map(|i| json!({ "id": i })).collect();
+
+        let value = client
+            .raw(RawRequest {
+                body: Some(json!(items)),
+                chunk_size: Some(100),
+                ..raw_post(Some("bulk".to_string()))
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(value, json!([{ "n": 1 }, { "n": 2 }, { "n": 3 }]));
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        for (request, (expected_len, key)) in
+            requests
+                .iter()
+                .zip([(100, "bulk-1"), (100, "bulk-2"), (50, "bulk-3")])
+        {
+            let (head, body) = request.split_once("\r\n\r\n").unwrap();
+            assert!(
+                head.to_ascii_lowercase()
+                    .contains(&format!("idempotency-key: {key}"))
+            );
+            let body: serde_json::Value = serde_json::from_str(body).unwrap();
+            assert_eq!(body.as_array().unwrap().len(), expected_len);
+        }
+    }
+
+    #[tokio::test]
+    async fn raw_chunk_failure_keeps_the_completed_responses() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("200 OK", "application/json", r#"{"n":1}"#),
+            http_response("500 Internal Server Error", "application/json", "{}"),
+            http_response("200 OK", "application/json", r#"{"n":3}"#),
+        ]);
+        let client = test_client(&base_url, 0);
+        let items: Vec<_> = (0..250).map(|i| json!({ "id": i })).collect();
+
+        let err = client
+            .raw(RawRequest {
+                body: Some(json!(items)),
+                chunk_size: Some(100),
+                ..raw_post(None)
+            })
+            .await
+            .unwrap_err();
+
+        let DatadogError::ChunkFailed {
+            completed,
+            chunk,
+            source,
+        } = err
+        else {
+            panic!("expected a chunk failure, got {err}");
+        };
+        assert_eq!(completed, vec![json!({ "n": 1 })]);
+        assert_eq!(chunk, 2);
+        assert!(matches!(
+            *source,
+            DatadogError::Retryable {
+                status: Some(500),
+                ..
+            }
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn monitors_list_sends_filters_and_paging() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("200 OK", "application/json", "[]"),
+            http_response("200 OK", "application/json", r#"{"id":42}"#),
+        ]);
+        let client = test_client(&base_url, 0);
+
+        client
+            .list_monitors(MonitorsQuery {
+                name: Some("cpu".to_string()),
+                tags: None,
+                monitor_tags: Some("team:core".to_string()),
+                page_size: Some(50),
+                page: Some(2),
+            })
+            .await
+            .unwrap();
+        let value = client.monitor(42).await.unwrap();
+
+        assert_eq!(value["id"], 42);
+        let requests = requests.lock().unwrap();
+        assert!(requests[0].starts_with(
+            "GET /api/v1/monitor?name=cpu&monitor_tags=team%3Acore&page_size=50&page=2 "
+        ));
+        assert!(requests[1].starts_with("GET /api/v1/monitor/42 "));
+    }
+
+    #[tokio::test]
+    async fn correlation_id_header_is_sent() {
+        let (base_url, requests) =
+            mock_server(vec![http_response("200 OK", "application/json", "{}")]);
+        let mut client = test_client(&base_url, 0);
+        client.correlation_id = Some("req-7f3a".to_string());
+
+        client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await
+            .unwrap();
+
+        assert!(
+            requests.lock().unwrap()[0]
+                .to_ascii_lowercase()
+                .contains("x-correlation-id: req-7f3a")
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_describes_the_request_without_sending_it() {
+        let (base_url, requests) = mock_server(Vec::<String>::new());
+        let mut client = test_client(&base_url, 0);
+        client.dry_run = true;
+        client.correlation_id = Some("req-1".to_string());
+
+        let description = client
+            .send_json(
+                Method::POST,
+                "/api/v2/logs/events/search",
+                Some(vec![("page[limit]".to_string(), "5".to_string())]),
+                Some(json!({ "filter": { "query": "service:web" } })),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            description,
+            json!({
+                "dry_run": true,
+                "method": "POST",
+                "url": format!("{base_url}/api/v2/logs/events/search?page%5Blimit%5D=5"),
+                "headers": {
+                    "Content-Type": "application/json",
+                    "Accept": "application/json",
+                    "DD-API-KEY": "***REDACTED***",
+                    "DD-APPLICATION-KEY": "***REDACTED***",
+                    "X-Correlation-Id": "req-1"
+                },
+                "body": { "filter": { "query": "service:web" } }
+            })
+        );
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn negotiated_http_version_is_recorded() {
+        let (base_url, _) = mock_server(vec![http_response("200 OK", "application/json", "{}")]);
+        let client = test_client(&base_url, 0);
+        assert_eq!(client.http_version(), None);
+
+        client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await
+            .unwrap();
+
+        assert_eq!(client.http_version().as_deref(), Some("HTTP/1.1"));
+    }
+
+    #[tokio::test]
+    async fn undecodable_client_error_body_keeps_status() {
+        let mut response =
+            b"HTTP/1.1 400 Bad Request\r\nContent-Type: application/json\r\nContent-Length: 4\r\nConnection: close\r\n\r\n"
+                .to_vec();
+        response.extend_from_slice(&[0xff, 0xfe, 0x7b, 0x7d]);
+        let (base_url, requests) = mock_server(vec![response]);
+        let client = test_client(&base_url, 2);
+
+        let result = client
+            .send_json(Method::GET, "/api/v1/validate", None, None)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(DatadogError::Api { status: 400, body }) if body == "<undecodable body>"
+        ));
+        assert_eq!(requests.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn webhook_receives_output_without_datadog_credentials() {
+        let (base_url, requests) = mock_server(vec![
+            http_response("502 Bad Gateway", "text/plain", "busy"),
+            http_response("204 No Content", "text/plain", ""),
+        ]);
+        let client = test_client("https://api.invalid", 1);
+
+        client
+            .post_webhook(
+                &format!("{base_url}/hooks/ddq"),
+                vec![("X-Hook-Token".to_string(), "s3cret".to_string())],
+                json!({ "data": [{ "id": "a" }] }),
+            )
+            .await
+            .unwrap();
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        let delivered = requests[1].to_ascii_lowercase();
+        assert!(delivered.starts_with("post /hooks/ddq "));
+        assert!(delivered.contains("x-hook-token: s3cret"));
+        assert!(!delivered.contains("dd-api-key"));
+        assert!(delivered.ends_with(r#"{"data":[{"id":"a"}]}"#));
+    }
 }
@@ -1,29 +1,83 @@
 mod app_error;
+mod batch;
+mod benchmark;
 mod cli;
 mod config;
+mod credentials;
 mod datadog;
+mod marker;
+mod output;
+mod query;
+mod records;
+mod spec;
 mod time_expr;
+mod tls;
+mod warnings;
 
+use std::collections::HashMap;
+use std::env;
 use std::fs;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::pin::pin;
+use std::process::{self, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
-use chrono::Utc;
-use clap::Parser;
-use serde_json::Value;
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use clap::{CommandFactory, Parser};
+use clap_complete::Shell;
+use futures::stream::{self, StreamExt, TryStreamExt};
+use serde_json::{Value, json};
+use tokio::time::sleep;
 
 use crate::app_error::AppError;
-use crate::cli::{Cli, Command};
-use crate::config::Config;
-use crate::datadog::{DatadogClient, LogsQuery};
-use crate::time_expr::parse_to_unix;
+use crate::cli::{
+    Cli, Command, HostsAction, LogsMetricsAction, MonitorsAction, OutputFormat, RbacAction,
+    RecordArgs, SloAction, TagsAction,
+};
+use crate::config::{Config, check_config_file};
+use crate::credentials::{
+    API_KEY_ACCOUNT, APP_KEY_ACCOUNT, CredentialStore, KEYRING_SERVICE, KeyringStore,
+};
+use crate::datadog::{
+    AwaitCompletion, DatadogClient, DatadogError, EventsQuery, HostsQuery, LogsAggregation,
+    LogsMetricDefinition, LogsQuery, LogsRehydration, MetricSubmission, MonitorsQuery,
+    ProcessesQuery, RawRequest, RbacQuery, RequestPlan, SloQuery, TimeseriesQuery,
+};
+use crate::time_expr::{
+    TimestampFormat, align_window, centered_window, check_strict, parse_timestamp_format,
+    parse_timezone, parse_to_unix, resolve_for_api,
+};
+use crate::warnings::Warnings;
 
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    let args = match spec::expand_args(env::args().collect()) {
+        Ok(args) => args,
+        Err(err) => {
+            let err = AppError::Usage(format!("{err:#}"));
+            eprintln!("{}", err.to_json(1));
+            process::exit(err.exit_code());
+        }
+    };
+    let cli = Cli::parse_from(args);
     let compact = cli.compact_output();
+    let error_schema_version = cli.error_schema_version;
+    let correlation_id = cli.correlation_id.clone();
+    let success_code = success_exit_code(&cli);
 
-    if let Err(err) = run(cli, compact).await {
-        if print_json_stderr(err.to_json(), compact).is_err() {
+    let result = match cli.benchmark {
+        Some(runs) => run_benchmark(cli, runs, compact).await,
+        None => run(cli, compact).await,
+    };
+    if let Err(err) = result {
+        let mut envelope = err.to_json(error_schema_version);
+        if let Some(id) = correlation_id {
+            envelope["error"]["correlation_id"] = json!(id);
+        }
+        if print_json_stderr(envelope, compact).is_err() {
             eprintln!(
                 "{{\"error\":{{\"category\":\"internal\",\"exit_code\":1,\"message\":\"Failed serializing error output\"}}}}"
             );
@@ -31,46 +85,827 @@ async fn main() {
         }
         std::process::exit(err.exit_code());
     }
+    if success_code != 0 {
+        process::exit(success_code);
+    }
+}
+
+/// Exit status when the command succeeds: `--dry-run-exit-code` for a dry run,
+/// otherwise 0.
+fn success_exit_code(cli: &Cli) -> i32 {
+    if cli.dry_run {
+        cli.dry_run_exit_code.into()
+    } else {
+        0
+    }
 }
 
 async fn run(cli: Cli, compact: bool) -> Result<(), AppError> {
+    if matches!(cli.command, Command::Login) {
+        let response = login(&cli, &KeyringStore::default(), io::stdin().lock())?;
+        print_json_stdout(response, compact).map_err(|err| AppError::Internal(err.to_string()))?;
+        return Ok(());
+    }
+    if matches!(cli.command, Command::ConfigCheck) {
+        let report = check_config_file(cli.config.as_deref());
+        let valid = report["valid"] == true;
+        print_json_stdout(report, compact).map_err(|err| AppError::Internal(err.to_string()))?;
+        if !valid {
+            return Err(AppError::Usage(
+                "The config file has errors; see `issues` in the report.".to_string(),
+            ));
+        }
+        return Ok(());
+    }
+    if let Command::Completions { shell } = cli.command {
+        return io::stdout()
+            .write_all(&completion_script(shell))
+            .map_err(|err| AppError::Internal(format!("Failed writing completions: {err}")));
+    }
+
     let config = Config::from_cli(&cli).map_err(|err| AppError::Usage(err.to_string()))?;
-    let client = DatadogClient::new(config);
+    let pretty_json = cli.pretty_json();
+    let max_depth = cli.max_depth;
+    let humanize = cli.humanize;
+    let output_format = cli.output;
+    if cli.nul_separated && output_format != OutputFormat::Ndjson {
+        return Err(AppError::Usage(
+            "--nul-separated requires --output ndjson.".to_string(),
+        ));
+    }
+    let terminator = if cli.nul_separated { '\0' } else { '\n' };
+    if cli.emit_schema.is_some() && output_format != OutputFormat::Csv {
+        return Err(AppError::Usage(
+            "--emit-schema requires --output csv.".to_string(),
+        ));
+    }
+    let emit_schema = cli.emit_schema.clone();
+    let extract = cli.extract.clone();
+    let webhook = cli.webhook.clone();
+    let webhook_headers = parse_webhook_headers(&cli.webhook_header)?;
+    let quiet = cli.quiet;
+    let use_pager = cli.pager && !cli.no_pager && !compact && io::stdout().is_terminal();
+    let require_extract = cli.require_extract;
+    let fail_on_warnings = cli.fail_on_warnings;
+    let each_from_stdin = cli.each_from_stdin;
+    let correlation_id = cli.correlation_id.clone();
+    let report_http_version = cli.report_http_version;
+    let timings = cli.timings;
+    let timezone = parse_timezone(&cli.timezone).map_err(|err| AppError::Usage(err.to_string()))?;
+    let ctx = Execution::new(&cli, config, timezone);
+    let display_timezone = cli
+        .display_timezone
+        .as_deref()
+        .map(parse_timezone)
+        .transpose()
+        .map_err(|err| AppError::Usage(err.to_string()))?;
+    let timestamp_format = cli
+        .timestamp_format
+        .as_deref()
+        .map(parse_timestamp_format)
+        .transpose()
+        .map_err(|err| AppError::Usage(err.to_string()))?;
+    let timestamp_fields = cli.timestamp_fields.clone();
+    let table_columns = table_columns(&cli.command);
+    let group_output_by = cli.group_output_by.clone();
+
+    let mut command = cli.command;
+    if cli.strict_timeparse {
+        for expr in time_args(&command) {
+            check_strict(expr).map_err(|err| AppError::Usage(err.to_string()))?;
+        }
+    }
+    let resume = match &cli.resume_from_marker {
+        Some(path) => Some((
+            path.clone(),
+            prepare_resume(&mut command, path, Utc::now(), timezone)?,
+        )),
+        None => None,
+    };
+
+    let (mut response, mut deferred_error) = if each_from_stdin {
+        run_each_line(command, &ctx, io::stdin().lock()).await?
+    } else {
+        execute(command, &ctx).await?
+    };
+    if ctx.client.dry_run() {
+        // Request descriptions, not Datadog responses: nothing to post-process.
+        return print_json_stdout(response, compact)
+            .map_err(|err| AppError::Internal(err.to_string()));
+    }
+
+    let complete = marker::is_complete(&response);
+
+    if let Some(id) = &correlation_id {
+        set_meta(&mut response, "correlation_id", json!(id));
+    }
+    if report_http_version && let Some(version) = ctx.client.http_version() {
+        set_meta(&mut response, "http_version", json!(version));
+    }
+
+    if display_timezone.is_some() || timestamp_format.is_some() {
+        let zone = display_timezone.unwrap_or(Tz::UTC);
+        let format = timestamp_format.unwrap_or(TimestampFormat::Rfc3339);
+        let records = match response.get_mut("data") {
+            Some(Value::Array(data)) => data.as_mut_slice(),
+            Some(data) => std::slice::from_mut(data),
+            None => std::slice::from_mut(&mut response),
+        };
+        records::localize_timestamps(records, &timestamp_fields, zone, &format);
+    }
+
+    if let Some(path) = &group_output_by {
+        group_records(&mut response, path);
+    }
+
+    let response = match &extract {
+        Some(pointer) => extract_pointer(response, pointer, require_extract)?,
+        None => response,
+    };
+
+    let response = match max_depth {
+        Some(depth) if pretty_json => truncate_depth(response, depth),
+        _ => response,
+    };
 
-    let response = match cli.command {
+    let response = if humanize && pretty_json {
+        humanize_numbers(response)
+    } else {
+        response
+    };
+
+    let response = if timings {
+        let meta = ctx.client.timings();
+        if !pretty_json {
+            eprintln!("{}", json!({ "timings": meta }));
+            response
+        } else {
+            json!({ "meta": meta, "result": response })
+        }
+    } else {
+        response
+    };
+
+    let webhook_body = webhook.as_ref().map(|_| response.clone());
+    if !quiet {
+        if output_format == OutputFormat::Canonical {
+            println!("{}", canonical_json(&response));
+        } else if output_format == OutputFormat::Ndjson {
+            io::stdout()
+                .write_all(output::to_ndjson(&response, terminator).as_bytes())
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+        } else if output_format == OutputFormat::Csv {
+            let csv = output::to_csv(&response).map_err(|err| AppError::Usage(err.to_string()))?;
+            if let Some(path) = &emit_schema {
+                let schema = output::csv_schema(&response, output::SCHEMA_SAMPLE_SIZE)
+                    .map_err(|err| AppError::Usage(err.to_string()))?;
+                fs::write(path, format!("{schema:#}\n")).map_err(|err| {
+                    AppError::Internal(format!(
+                        "Failed writing schema to `{}`: {err}",
+                        path.display()
+                    ))
+                })?;
+            }
+            io::stdout()
+                .write_all(csv.as_bytes())
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+        } else if output_format == OutputFormat::Table {
+            let max_width = env::var("COLUMNS")
+                .ok()
+                .and_then(|columns| columns.trim().parse().ok());
+            match output::to_table(&response, &table_columns, max_width) {
+                Some(table) => io::stdout()
+                    .write_all(table.as_bytes())
+                    .map_err(|err| AppError::Internal(err.to_string()))?,
+                None => {
+                    ctx.client.warnings().warn(
+                        "Table output needs a list of records (logs or events); printing JSON instead.",
+                    );
+                    print_json_stdout(response, false)
+                        .map_err(|err| AppError::Internal(err.to_string()))?;
+                }
+            }
+        } else if use_pager {
+            let text = serde_json::to_string_pretty(&response)
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+            let pager = env::var("PAGER")
+                .ok()
+                .filter(|pager| !pager.trim().is_empty())
+                .unwrap_or_else(|| "less -R".to_string());
+            page_output(&pager, &format!("{text}\n"), spawn_pager).map_err(|err| {
+                AppError::Internal(format!("Failed running pager `{pager}`: {err}"))
+            })?;
+        } else {
+            print_json_stdout(response, compact)
+                .map_err(|err| AppError::Internal(err.to_string()))?;
+        }
+    }
+
+    // Rendering can warn too (e.g. the table fallback), so check only now.
+    if deferred_error.is_none() {
+        deferred_error = ctx.client.warnings().failure(fail_on_warnings);
+    }
+
+    // The marker only moves past a window that fully succeeded: every batch item,
+    // every page, printing and (below) webhook delivery.
+    let resume = resume.filter(|_| {
+        deferred_error.is_none() && !ctx.batch_failed.load(Ordering::Relaxed) && complete
+    });
+
+    if let (Some(url), Some(body)) = (&webhook, webhook_body)
+        && deferred_error.is_none()
+    {
+        ctx.client
+            .post_webhook(url, webhook_headers, body)
+            .await
+            .map_err(|err| AppError::Upstream {
+                status: None,
+                transport_kind: None,
+                message: format!("Webhook delivery to `{url}` failed: {err}"),
+            })?;
+    }
+    if let Some((path, to)) = &resume {
+        marker::record_success(path, *to).map_err(|err| AppError::Internal(format!("{err:#}")))?;
+    }
+    deferred_error.map_or(Ok(()), Err)
+}
+
+/// Settings shared by every command execution within one run.
+struct Execution {
+    client: DatadogClient,
+    timezone: Tz,
+    collect_errors: bool,
+    /// Abort a batch at the first 401/403, even with --collect-errors.
+    fail_fast_auth: bool,
+    summarize_errors: bool,
+    /// Set when a --collect-errors batch carried on past a failed item.
+    batch_failed: AtomicBool,
+    error_schema_version: u8,
+    explain_request: bool,
+    normalize_query: bool,
+    /// Queries in flight at once for --each-from-stdin and multi-query logs.
+    concurrency: usize,
+}
+
+impl Execution {
+    fn new(cli: &Cli, config: Config, timezone: Tz) -> Self {
+        Self {
+            client: DatadogClient::new(config),
+            timezone,
+            collect_errors: cli.collect_errors || cli.summarize_errors,
+            fail_fast_auth: !cli.no_fail_fast_auth,
+            summarize_errors: cli.summarize_errors,
+            batch_failed: AtomicBool::new(false),
+            error_schema_version: cli.error_schema_version,
+            explain_request: cli.explain_request,
+            normalize_query: cli
+                .normalize_query
+                .unwrap_or(cli.spec.is_some() || cli.each_from_stdin),
+            concurrency: usize::from(cli.concurrency),
+        }
+    }
+
+    /// Whether commands that know their requests up front (logs, logs-aggregate,
+    /// metrics, events) stop at describing them: `--explain-request` or `--dry-run`.
+    fn plans_only(&self) -> bool {
+        self.explain_request || self.client.dry_run()
+    }
+
+    /// What `plans_only` prints for `plans`: the bare plans for
+    /// `--explain-request`, the full request descriptions for `--dry-run`. One
+    /// entry per request, unwrapped when there is only one.
+    fn planned_requests(&self, plans: Vec<RequestPlan>) -> Result<Value, AppError> {
+        let mut described = plans
+            .iter()
+            .map(|plan| {
+                if self.explain_request {
+                    Ok(plan.to_json())
+                } else {
+                    self.client.describe_plan(plan)
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(AppError::from)?;
+        Ok(if described.len() == 1 {
+            described.remove(0)
+        } else {
+            Value::Array(described)
+        })
+    }
+}
+
+/// Runs one Datadog command and returns its output plus an error to report after
+/// printing it (a partially failed batch).
+async fn execute(command: Command, ctx: &Execution) -> Result<(Value, Option<AppError>), AppError> {
+    let client = &ctx.client;
+    let timezone = ctx.timezone;
+    let warnings = ctx.client.warnings();
+    let collect_errors = ctx.collect_errors;
+    let error_schema_version = ctx.error_schema_version;
+    if ctx.explain_request
+        && !matches!(
+            command,
+            Command::Logs { .. }
+                | Command::LogsAggregate { .. }
+                | Command::Metrics { .. }
+                | Command::Events { .. }
+        )
+    {
+        return Err(AppError::Usage(
+            "--explain-request applies only to logs, logs-aggregate, metrics, and events."
+                .to_string(),
+        ));
+    }
+
+    let response = match command {
         Command::Logs {
             query,
+            queries,
             from,
             to,
+            around,
+            window,
             limit,
             sort,
             cursor,
-        } => client
-            .query_logs(LogsQuery {
+            count,
+            all,
+            max_pages,
+            any,
+            sample,
+            sample_seed,
+            return_fields,
+            flatten_attributes,
+            prefix,
+            records,
+        } => {
+            if let Some(rate) = sample
+                && !(0.0..=1.0).contains(&rate)
+            {
+                return Err(AppError::Usage(
+                    "--sample must be between 0.0 and 1.0.".to_string(),
+                ));
+            }
+            let queries = std::iter::once(query)
+                .chain(queries)
+                .map(|query| {
+                    let query = if ctx.normalize_query {
+                        query::normalize(&query)
+                    } else {
+                        query
+                    };
+                    query::expand_any(&query, &any).map_err(|err| AppError::Usage(err.to_string()))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let descending = sort.eq_ignore_ascii_case("desc");
+            let now = Utc::now();
+            let (from, to) = match (around, window) {
+                (Some(anchor), Some(window)) => {
+                    let (from, to) = centered_window(&anchor, &window, now, timezone)
+                        .map_err(|err| AppError::Usage(err.to_string()))?;
+                    (from.to_rfc3339(), to.to_rfc3339())
+                }
+                _ => (
+                    resolve_for_api(&from, now, timezone),
+                    resolve_for_api(&to, now, timezone),
+                ),
+            };
+            if count {
+                let query = queries.into_iter().next().expect("QUERY is required");
+                let aggregation = LogsAggregation::count(query, from, to);
+                if ctx.plans_only() {
+                    let plan =
+                        datadog::logs_aggregate_request(&aggregation).map_err(AppError::from)?;
+                    return Ok((ctx.planned_requests(vec![plan])?, None));
+                }
+                let response = client
+                    .count_logs(&aggregation)
+                    .await
+                    .map_err(AppError::from)?;
+                return Ok((response, None));
+            }
+            let mut logs_queries: Vec<LogsQuery> = queries
+                .into_iter()
+                .map(|query| LogsQuery {
+                    query,
+                    from: from.clone(),
+                    to: to.clone(),
+                    limit: records::request_limit(&records, limit),
+                    sort: sort.clone(),
+                    cursor: cursor.clone().filter(|_| !records.preview),
+                    all: all && !records.preview,
+                    max_pages,
+                })
+                .collect();
+            if ctx.plans_only() {
+                let plans = logs_queries
+                    .iter()
+                    .map(datadog::logs_request)
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(AppError::from)?;
+                return Ok((ctx.planned_requests(plans)?, None));
+            }
+            let response = if logs_queries.len() == 1 {
+                let logs_query = logs_queries.remove(0);
+                fetch_records(&records, || client.query_logs(logs_query.clone())).await?
+            } else {
+                // Stops at the first failure, dropping queries still in flight.
+                let responses: Vec<Value> = stream::iter(&logs_queries)
+                    .map(|logs_query| {
+                        fetch_records(&records, || client.query_logs(logs_query.clone()))
+                    })
+                    .buffered(ctx.concurrency)
+                    .try_collect()
+                    .await?;
+                records::merge_responses(responses, descending)
+            };
+            let mut response = records::apply(response, &records, descending);
+            if let Some(rate) = sample
+                && let Some(Value::Array(data)) = response.get_mut("data")
+            {
+                records::sample_records(data, rate, sample_seed);
+            }
+            if !return_fields.is_empty()
+                && let Some(Value::Array(data)) = response.get_mut("data")
+            {
+                for record in data.iter_mut() {
+                    *record = records::project_fields(record.take(), &return_fields);
+                }
+            }
+            if flatten_attributes && let Some(Value::Array(data)) = response.get_mut("data") {
+                let prefix = prefix.unwrap_or_default();
+                for record in data.iter_mut() {
+                    *record = records::flatten_attributes(record.take(), &prefix);
+                }
+            }
+            response
+        }
+        Command::Processes {
+            search,
+            tags,
+            from,
+            to,
+            limit,
+            cursor,
+        } => {
+            let now = Utc::now();
+            let from = parse_to_unix(&from, now, timezone)
+                .map_err(|err| AppError::Usage(err.to_string()))?;
+            let to = parse_to_unix(&to, now, timezone)
+                .map_err(|err| AppError::Usage(err.to_string()))?;
+            client
+                .query_processes(ProcessesQuery {
+                    search,
+                    tags,
+                    from,
+                    to,
+                    limit,
+                    cursor,
+                })
+                .await
+                .map_err(AppError::from)?
+        }
+        Command::Tags { action } => match action {
+            TagsAction::Get { host } => client.host_tags(&host).await,
+            TagsAction::Update { host, tags } => client.update_host_tags(&host, tags).await,
+            TagsAction::Add { host, tags } => client.add_host_tags(&host, tags).await,
+        }
+        .map_err(AppError::from)?,
+        Command::Monitors { action } => match action {
+            MonitorsAction::List {
+                name,
+                tags,
+                monitor_tags,
+                limit,
+                page,
+            } => {
+                client
+                    .list_monitors(MonitorsQuery {
+                        name,
+                        tags,
+                        monitor_tags,
+                        page_size: limit,
+                        page,
+                    })
+                    .await
+            }
+            MonitorsAction::Get { id } => client.monitor(id).await,
+            MonitorsAction::Mute { id, end } => {
+                let end = match end {
+                    Some(end) => {
+                        let now = Utc::now();
+                        let end = parse_to_unix(&end, now, timezone)
+                            .map_err(|err| AppError::Usage(err.to_string()))?;
+                        if end <= now.timestamp() {
+                            return Err(AppError::Usage(
+                                "Invalid mute --end: it must be in the future.".to_string(),
+                            ));
+                        }
+                        Some(end)
+                    }
+                    None => None,
+                };
+                client.mute_monitor(id, end).await
+            }
+            MonitorsAction::Unmute { id } => client.unmute_monitor(id).await,
+        }
+        .map_err(AppError::from)?,
+        Command::Hosts { action } => match action {
+            HostsAction::List {
+                filter,
+                sort_field,
+                sort_dir,
+                count,
+                start,
+            } => {
+                client
+                    .list_hosts(HostsQuery {
+                        filter,
+                        sort_field,
+                        sort_dir,
+                        count,
+                        start,
+                    })
+                    .await
+            }
+            HostsAction::Totals => client.host_totals().await,
+        }
+        .map_err(AppError::from)?,
+        Command::Slo { action } => match action {
+            SloAction::List {
                 query,
-                from,
-                to,
+                tags_query,
                 limit,
-                sort,
-                cursor,
-            })
-            .await
-            .map_err(AppError::from)?,
-        Command::Metrics { query, from, to } => {
+                offset,
+            } => {
+                client
+                    .list_slos(SloQuery {
+                        query,
+                        tags_query,
+                        limit,
+                        offset,
+                    })
+                    .await
+            }
+            SloAction::Get {
+                id, history: false, ..
+            } => client.slo(&id).await,
+            SloAction::Get { id, from, to, .. } => {
+                let now = Utc::now();
+                let from = from.as_deref().unwrap_or("now-7d");
+                let to = to.as_deref().unwrap_or("now");
+                let from = parse_to_unix(from, now, timezone)
+                    .map_err(|err| AppError::Usage(err.to_string()))?;
+                let to = parse_to_unix(to, now, timezone)
+                    .map_err(|err| AppError::Usage(err.to_string()))?;
+                if to <= from {
+                    return Err(AppError::Usage(
+                        "Invalid SLO history window: `to` must be greater than `from`.".to_string(),
+                    ));
+                }
+                client.slo_history(&id, from, to).await
+            }
+        }
+        .map_err(AppError::from)?,
+        Command::Rbac { action } => match action {
+            RbacAction::Roles {
+                filter,
+                page_size,
+                page_number,
+                all,
+                max_pages,
+            } => {
+                client
+                    .query_rbac(RbacQuery::Roles {
+                        filter,
+                        page_size,
+                        page_number,
+                        all,
+                        max_pages,
+                    })
+                    .await
+            }
+            RbacAction::RestrictionPolicy { resource_id } => {
+                client
+                    .query_rbac(RbacQuery::RestrictionPolicy { resource_id })
+                    .await
+            }
+        }
+        .map_err(AppError::from)?,
+        Command::LogsAggregate {
+            query,
+            from,
+            to,
+            compute,
+            group_by,
+            group_limit,
+        } => {
+            let now = Utc::now();
+            let aggregation = LogsAggregation {
+                query: if ctx.normalize_query {
+                    query::normalize(&query)
+                } else {
+                    query
+                },
+                from: resolve_for_api(&from, now, timezone),
+                to: resolve_for_api(&to, now, timezone),
+                compute,
+                group_by,
+                group_limit,
+            };
+            if ctx.plans_only() {
+                let plan = datadog::logs_aggregate_request(&aggregation).map_err(AppError::from)?;
+                return Ok((ctx.planned_requests(vec![plan])?, None));
+            }
+            client
+                .aggregate_logs(&aggregation)
+                .await
+                .map_err(AppError::from)?
+        }
+        Command::LogsMetrics { action } => match action {
+            LogsMetricsAction::List => client.query_logs_metrics(None).await,
+            LogsMetricsAction::Get { name } => client.query_logs_metrics(Some(&name)).await,
+            LogsMetricsAction::Create {
+                name,
+                query,
+                aggregation_type,
+                path,
+                include_percentiles,
+                group_by,
+            } => {
+                client
+                    .create_logs_metric(LogsMetricDefinition {
+                        name,
+                        query,
+                        aggregation_type,
+                        path,
+                        include_percentiles,
+                        group_by,
+                    })
+                    .await
+            }
+        }
+        .map_err(AppError::from)?,
+        Command::LogsRehydrate {
+            archive_id,
+            name,
+            query,
+            from,
+            to,
+            wait,
+            poll_interval_ms,
+            wait_timeout_seconds,
+        } => {
+            let now = Utc::now();
+            let from = rfc3339_from_expr(&from, now, timezone)?;
+            let to = rfc3339_from_expr(&to, now, timezone)?;
+            client
+                .rehydrate_logs(LogsRehydration {
+                    archive_id,
+                    name,
+                    query,
+                    from,
+                    to,
+                    wait: wait.then_some(AwaitCompletion {
+                        status_field: "data.attributes.status".to_string(),
+                        poll_interval_ms,
+                        timeout_seconds: wait_timeout_seconds,
+                    }),
+                })
+                .await
+                .map_err(AppError::from)?
+        }
+        Command::Metrics {
+            queries,
+            from,
+            to,
+            align_window: alignment,
+            max_series,
+            truncate_series,
+            annotate_units,
+            v2,
+            formula,
+            interval,
+        } => {
             let now = Utc::now();
-            let from_unix =
-                parse_to_unix(&from, now).map_err(|err| AppError::Usage(err.to_string()))?;
-            let to_unix =
-                parse_to_unix(&to, now).map_err(|err| AppError::Usage(err.to_string()))?;
+            let from_unix = parse_to_unix(&from, now, timezone)
+                .map_err(|err| AppError::Usage(err.to_string()))?;
+            let to_unix = parse_to_unix(&to, now, timezone)
+                .map_err(|err| AppError::Usage(err.to_string()))?;
 
             if to_unix <= from_unix {
                 return Err(AppError::Usage(
                     "Invalid metrics time window: `to` must be greater than `from`.".to_string(),
                 ));
             }
+            let (from_unix, to_unix) = match alignment {
+                Some(window) => align_window(from_unix, to_unix, &window)
+                    .map_err(|err| AppError::Usage(err.to_string()))?,
+                None => (from_unix, to_unix),
+            };
+            if v2 {
+                let timeseries = TimeseriesQuery {
+                    queries,
+                    formulas: formula,
+                    from_ms: from_unix * 1_000,
+                    to_ms: to_unix * 1_000,
+                    interval_ms: interval,
+                };
+                if ctx.plans_only() {
+                    let plan = datadog::timeseries_request(&timeseries).map_err(AppError::from)?;
+                    return Ok((ctx.planned_requests(vec![plan])?, None));
+                }
+                return Ok((
+                    client
+                        .query_timeseries(&timeseries)
+                        .await
+                        .map_err(AppError::from)?,
+                    None,
+                ));
+            }
+            if ctx.plans_only() {
+                let plans = queries
+                    .iter()
+                    .map(|query| datadog::metrics_request(query, from_unix, to_unix))
+                    .collect();
+                return Ok((ctx.planned_requests(plans)?, None));
+            }
+
+            let mut pending = pin!(
+                stream::iter(&queries)
+                    .map(|query| async {
+                        let response = client
+                            .query_metrics(query, from_unix, to_unix)
+                            .await
+                            .map_err(AppError::from)?;
+                        match max_series {
+                            Some(max) => {
+                                enforce_max_series(response, max, truncate_series, warnings)
+                            }
+                            None => Ok(response),
+                        }
+                    })
+                    .buffered(ctx.concurrency)
+            );
+            let mut results = Vec::new();
+            while let Some(result) = pending.next().await {
+                let abort = ctx.fail_fast_auth && batch::is_auth_error(&result);
+                results.push(result);
+                if abort {
+                    break;
+                }
+            }
 
+            if annotate_units {
+                // A metadata failure fails only its own query, so --collect-errors
+                // reports it as that item's error.
+                let mut units = HashMap::new();
+                for result in results.iter_mut() {
+                    if let Ok(response) = result
+                        && let Err(err) = client.annotate_units(response, &mut units).await
+                    {
+                        *result = Err(AppError::from(err));
+                    }
+                }
+            }
+
+            if queries.len() == 1 {
+                results.into_iter().next().expect("one query was run")?
+            } else {
+                if results.iter().any(Result::is_err) {
+                    ctx.batch_failed.store(true, Ordering::Relaxed);
+                }
+                let (output, failure) = batch::collect(
+                    results,
+                    collect_errors,
+                    ctx.fail_fast_auth,
+                    error_schema_version,
+                )?;
+                let output = if ctx.summarize_errors {
+                    batch::summarize(output, |item: &Value| Some(item))
+                } else {
+                    output
+                };
+                return Ok((output, failure));
+            }
+        }
+        Command::MetricsSubmit {
+            metric,
+            value,
+            timestamp,
+            metric_type,
+            tags,
+        } => {
+            let timestamp = parse_to_unix(&timestamp, Utc::now(), timezone)
+                .map_err(|err| AppError::Usage(err.to_string()))?;
             client
-                .query_metrics(&query, from_unix, to_unix)
+                .submit_metrics(MetricSubmission {
+                    metric,
+                    value,
+                    timestamp,
+                    metric_type,
+                    tags,
+                })
                 .await
                 .map_err(AppError::from)?
         }
@@ -79,29 +914,414 @@ async fn run(cli: Cli, compact: bool) -> Result<(), AppError> {
             from,
             to,
             limit,
+            page_size,
+            max_pages,
             sort,
-        } => client
-            .query_events(query, from, to, limit, sort)
-            .await
-            .map_err(AppError::from)?,
+            priority,
+            alert_type,
+            all,
+            group_by,
+            records,
+        } => {
+            let descending = sort.eq_ignore_ascii_case("desc");
+            let now = Utc::now();
+            let events_query = EventsQuery {
+                query: query.map(|query| {
+                    if ctx.normalize_query {
+                        query::normalize(&query)
+                    } else {
+                        query
+                    }
+                }),
+                from: resolve_for_api(&from, now, timezone),
+                to: resolve_for_api(&to, now, timezone),
+                limit: if all && !records.preview {
+                    u32::MAX
+                } else {
+                    records::request_limit(&records, limit)
+                },
+                page_size: page_size.filter(|_| !records.preview),
+                max_pages,
+                sort,
+                priority,
+                alert_type,
+            };
+            if ctx.plans_only() {
+                let plan = datadog::events_request(&events_query).map_err(AppError::from)?;
+                return Ok((ctx.planned_requests(vec![plan])?, None));
+            }
+            let response =
+                fetch_records(&records, || client.query_events(events_query.clone())).await?;
+            let response = records::apply(response, &records, descending);
+            if group_by.is_empty() {
+                response
+            } else {
+                records::group_counts(&response, &group_by).unwrap_or(response)
+            }
+        }
+        Command::Login => unreachable!("login is handled before building the client"),
+        Command::Completions { .. } | Command::ConfigCheck => {
+            unreachable!("completions and config-check are handled before building the client")
+        }
         Command::Raw {
             method,
             path,
             query_params,
             body,
             body_file,
+            idempotency_key,
+            await_completion,
+            status_field,
+            poll_interval_ms,
+            await_timeout_seconds,
+            ignore_statuses,
+            chunk_size,
         } => {
             let params = parse_query_params(&query_params)?;
             let payload = parse_raw_body(body, body_file)?;
-            client
-                .raw(&method, &path, params, payload)
-                .await
-                .map_err(AppError::from)?
+            let result = client
+                .raw(RawRequest {
+                    method,
+                    path,
+                    params,
+                    body: payload,
+                    idempotency_key,
+                    await_completion: await_completion.then_some(AwaitCompletion {
+                        status_field,
+                        poll_interval_ms,
+                        timeout_seconds: await_timeout_seconds,
+                    }),
+                    ignore_statuses,
+                    chunk_size: chunk_size.map(|size| size as usize),
+                })
+                .await;
+            match result {
+                Err(DatadogError::ChunkFailed {
+                    completed,
+                    chunk,
+                    source,
+                }) => {
+                    let output = json!({
+                        "responses": completed,
+                        "failed_chunk": chunk,
+                        "complete": false,
+                    });
+                    return Ok((output, Some(AppError::from(*source))));
+                }
+                result => result.map_err(AppError::from)?,
+            }
         }
     };
+    Ok((response, None))
+}
 
-    print_json_stdout(response, compact).map_err(|err| AppError::Internal(err.to_string()))?;
-    Ok(())
+/// Runs `command` once per non-empty input line with each `{}` in its query
+/// replaced by the line, at most `concurrency` at a time. The output lists
+/// `{"input": line, "result": ...}` in input order; failures follow the same
+/// rules as other batches (`--collect-errors`).
+async fn run_each_line(
+    command: Command,
+    ctx: &Execution,
+    input: impl BufRead,
+) -> Result<(Value, Option<AppError>), AppError> {
+    let inputs: Vec<String> = input
+        .lines()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| AppError::Usage(format!("Failed reading stdin: {err}")))?
+        .into_iter()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+    let commands = inputs
+        .iter()
+        .map(|line| with_placeholder(&command, line))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut pending = pin!(
+        stream::iter(commands)
+            .map(|command| async move {
+                match execute(command, ctx).await? {
+                    (response, None) => Ok(response),
+                    (_, Some(err)) => Err(err),
+                }
+            })
+            .buffered(ctx.concurrency)
+    );
+    let mut results = Vec::new();
+    while let Some(result) = pending.next().await {
+        let abort = ctx.fail_fast_auth && batch::is_auth_error(&result);
+        results.push(result);
+        if abort {
+            break;
+        }
+    }
+    if results.iter().any(Result::is_err) {
+        ctx.batch_failed.store(true, Ordering::Relaxed);
+    }
+    let (items, failure) = batch::collect(
+        results,
+        ctx.collect_errors,
+        ctx.fail_fast_auth,
+        ctx.error_schema_version,
+    )?;
+    let Value::Array(items) = items else {
+        unreachable!("batch output is an array");
+    };
+    let keyed = inputs
+        .iter()
+        .zip(items)
+        .map(|(input, result)| json!({ "input": input, "result": result }))
+        .collect();
+    let output = if ctx.summarize_errors {
+        batch::summarize(Value::Array(keyed), |item| item.get("result"))
+    } else {
+        Value::Array(keyed)
+    };
+    Ok((output, failure))
+}
+
+/// Copies `command` with every `{}` in its query replaced by `value`.
+fn with_placeholder(command: &Command, value: &str) -> Result<Command, AppError> {
+    let mut command = command.clone();
+    let queries: Vec<&mut String> = match &mut command {
+        Command::Logs { query, queries, .. } => {
+            std::iter::once(query).chain(queries.iter_mut()).collect()
+        }
+        Command::Metrics { queries, .. } => queries.iter_mut().collect(),
+        Command::Events { query, .. } => query.iter_mut().collect(),
+        _ => {
+            return Err(AppError::Usage(
+                "--each-from-stdin applies only to logs, metrics, and events.".to_string(),
+            ));
+        }
+    };
+    if !queries.iter().any(|query| query.contains("{}")) {
+        return Err(AppError::Usage(
+            "--each-from-stdin needs a `{}` placeholder in the query.".to_string(),
+        ));
+    }
+    for query in queries {
+        *query = query.replace("{}", value);
+    }
+    Ok(command)
+}
+
+/// Runs the command `runs` times with stdout output, webhook delivery, and the
+/// resume marker disabled, then prints latency statistics to stderr. The first
+/// failing run aborts the benchmark.
+async fn run_benchmark(cli: Cli, runs: u32, compact: bool) -> Result<(), AppError> {
+    if !is_read_only(&cli.command) {
+        return Err(AppError::Usage(
+            "--benchmark only runs read-only commands (queries, list/get actions, raw GET/HEAD)."
+                .to_string(),
+        ));
+    }
+
+    // Resolve credentials, settings and stdin once, so the runs time only the
+    // requests.
+    let config = Config::from_cli(&cli).map_err(|err| AppError::Usage(err.to_string()))?;
+    let timezone = parse_timezone(&cli.timezone).map_err(|err| AppError::Usage(err.to_string()))?;
+    if cli.strict_timeparse {
+        for expr in time_args(&cli.command) {
+            check_strict(expr).map_err(|err| AppError::Usage(err.to_string()))?;
+        }
+    }
+    let stdin = if cli.each_from_stdin {
+        let mut input = String::new();
+        io::stdin()
+            .read_to_string(&mut input)
+            .map_err(|err| AppError::Usage(format!("Failed reading stdin: {err}")))?;
+        Some(input)
+    } else {
+        None
+    };
+    let ctx = Execution::new(&cli, config, timezone);
+
+    let durations = benchmark(&cli.command, &ctx, runs, stdin.as_deref()).await?;
+    print_json_stderr(
+        json!({ "benchmark": benchmark::summarize(&durations) }),
+        compact,
+    )
+    .map_err(|err| AppError::Internal(err.to_string()))
+}
+
+/// Runs `command` `runs` times, up to `--concurrency` at once, and returns how
+/// long each run's requests took. `stdin` is the `--each-from-stdin` input.
+async fn benchmark(
+    command: &Command,
+    ctx: &Execution,
+    runs: u32,
+    stdin: Option<&str>,
+) -> Result<Vec<Duration>, AppError> {
+    stream::iter(0..runs)
+        .map(|_| async move {
+            let started = Instant::now();
+            let (_, failure) = match stdin {
+                Some(input) => run_each_line(command.clone(), ctx, input.as_bytes()).await?,
+                None => execute(command.clone(), ctx).await?,
+            };
+            failure.map_or(Ok(started.elapsed()), Err)
+        })
+        .buffer_unordered(ctx.concurrency)
+        .try_collect()
+        .await
+}
+
+/// Whether `command` only reads from Datadog, so running it repeatedly is safe.
+fn is_read_only(command: &Command) -> bool {
+    match command {
+        Command::Logs { .. }
+        | Command::LogsAggregate { .. }
+        | Command::Metrics { .. }
+        | Command::Events { .. }
+        | Command::Processes { .. }
+        | Command::Hosts { .. }
+        | Command::Slo { .. }
+        | Command::Rbac { .. } => true,
+        Command::LogsMetrics { action } => !matches!(action, LogsMetricsAction::Create { .. }),
+        Command::Tags { action } => matches!(action, TagsAction::Get { .. }),
+        Command::Monitors { action } => {
+            matches!(
+                action,
+                MonitorsAction::List { .. } | MonitorsAction::Get { .. }
+            )
+        }
+        Command::Raw { method, .. } => {
+            method.eq_ignore_ascii_case("GET") || method.eq_ignore_ascii_case("HEAD")
+        }
+        Command::LogsRehydrate { .. }
+        | Command::MetricsSubmit { .. }
+        | Command::Login
+        | Command::ConfigCheck
+        | Command::Completions { .. } => false,
+    }
+}
+
+/// Pins the command's time window for `--resume-from-marker`: `to` is resolved to
+/// a fixed instant (stored on success) and `from` is taken from the marker when
+/// one exists.
+fn prepare_resume(
+    command: &mut Command,
+    path: &std::path::Path,
+    now: DateTime<Utc>,
+    timezone: Tz,
+) -> Result<DateTime<Utc>, AppError> {
+    let (from, to) = match command {
+        Command::Logs { from, to, .. }
+        | Command::Metrics { from, to, .. }
+        | Command::Events { from, to, .. } => (from, to),
+        _ => {
+            return Err(AppError::Usage(
+                "--resume-from-marker applies only to logs, events, and metrics.".to_string(),
+            ));
+        }
+    };
+
+    let to_instant = instant_from_expr(to, now, timezone)?;
+    *to = to_instant.to_rfc3339();
+    if let Some(stored) = marker::read(path).map_err(|err| AppError::Usage(format!("{err:#}")))? {
+        *from = stored;
+    }
+    Ok(to_instant)
+}
+
+/// Columns for `--output table`: the command's `--fields`, else the defaults.
+fn table_columns(command: &Command) -> Vec<String> {
+    match command {
+        Command::Logs { records, .. } | Command::Events { records, .. }
+            if !records.fields.is_empty() =>
+        {
+            records.fields.clone()
+        }
+        _ => output::DEFAULT_TABLE_COLUMNS.map(str::to_string).to_vec(),
+    }
+}
+
+/// Time expressions given to `command`, as typed on the command line.
+fn time_args(command: &Command) -> Vec<&str> {
+    match command {
+        Command::Logs {
+            from, to, around, ..
+        } => [from, to]
+            .into_iter()
+            .chain(around)
+            .map(String::as_str)
+            .collect(),
+        Command::Metrics { from, to, .. }
+        | Command::Events { from, to, .. }
+        | Command::Processes { from, to, .. }
+        | Command::LogsAggregate { from, to, .. }
+        | Command::LogsRehydrate { from, to, .. } => vec![from, to],
+        Command::MetricsSubmit { timestamp, .. } => vec![timestamp],
+        Command::Monitors {
+            action: MonitorsAction::Mute { end: Some(end), .. },
+        } => vec![end],
+        Command::Slo {
+            action: SloAction::Get { from, to, .. },
+        } => from.iter().chain(to).map(String::as_str).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn instant_from_expr(
+    expr: &str,
+    now: DateTime<Utc>,
+    timezone: Tz,
+) -> Result<DateTime<Utc>, AppError> {
+    let unix =
+        parse_to_unix(expr, now, timezone).map_err(|err| AppError::Usage(err.to_string()))?;
+    Utc.timestamp_opt(unix, 0)
+        .single()
+        .ok_or_else(|| AppError::Usage(format!("Time `{expr}` is out of range.")))
+}
+
+fn rfc3339_from_expr(expr: &str, now: DateTime<Utc>, timezone: Tz) -> Result<String, AppError> {
+    instant_from_expr(expr, now, timezone).map(|instant| instant.to_rfc3339())
+}
+
+/// The completion script for `shell`, generated from the clap definition of `Cli`.
+fn completion_script(shell: Shell) -> Vec<u8> {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut command, name, &mut script);
+    script
+}
+
+fn login(cli: &Cli, store: &dyn CredentialStore, input: impl BufRead) -> Result<Value, AppError> {
+    let mut lines = input.lines();
+    let mut next_secret = |flag: &Option<String>, name: &str| -> Result<String, AppError> {
+        let secret = match flag {
+            Some(secret) => secret.clone(),
+            None => lines
+                .next()
+                .transpose()
+                .map_err(|err| AppError::Usage(format!("Failed reading {name} from stdin: {err}")))?
+                .unwrap_or_default(),
+        };
+        let secret = secret.trim().to_string();
+        if secret.is_empty() {
+            return Err(AppError::Usage(format!(
+                "Missing {name} for login. Pass it as a flag or on stdin."
+            )));
+        }
+        Ok(secret)
+    };
+
+    let api_key = next_secret(&cli.api_key, "API key")?;
+    let app_key = next_secret(&cli.app_key, "application key")?;
+    for (account, secret) in [(API_KEY_ACCOUNT, &api_key), (APP_KEY_ACCOUNT, &app_key)] {
+        store
+            .set(account, secret)
+            .map_err(|err| AppError::Usage(format!("{err:#}")))?;
+    }
+
+    Ok(json!({
+        "stored": {
+            "service": KEYRING_SERVICE,
+            "accounts": [API_KEY_ACCOUNT, APP_KEY_ACCOUNT],
+        }
+    }))
 }
 
 fn parse_query_params(params: &[String]) -> Result<Vec<(String, String)>, AppError> {
@@ -123,6 +1343,26 @@ fn parse_query_params(params: &[String]) -> Result<Vec<(String, String)>, AppErr
         .collect()
 }
 
+fn parse_webhook_headers(headers: &[String]) -> Result<Vec<(String, String)>, AppError> {
+    headers
+        .iter()
+        .map(|header| {
+            let (name, value) = header.split_once(':').ok_or_else(|| {
+                AppError::Usage(format!(
+                    "Invalid webhook header `{header}`. Expected \"Name: value\"."
+                ))
+            })?;
+            let name = name.trim();
+            if name.is_empty() {
+                return Err(AppError::Usage(format!(
+                    "Webhook header name cannot be empty in `{header}`."
+                )));
+            }
+            Ok((name.to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
 fn parse_raw_body(
     body: Option<String>,
     body_file: Option<std::path::PathBuf>,
@@ -150,6 +1390,266 @@ fn parse_raw_body(
     }
 }
 
+/// Runs a logs/events query, re-running it after a delay while `data` comes back
+/// empty when `--retry-on-empty` is set.
+async fn fetch_records<F, Fut>(args: &RecordArgs, mut fetch: F) -> Result<Value, AppError>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Value, DatadogError>>,
+{
+    let mut response = fetch().await?;
+    if !args.retry_on_empty {
+        return Ok(response);
+    }
+    for _ in 0..args.empty_retries {
+        let empty = response
+            .get("data")
+            .and_then(Value::as_array)
+            .is_some_and(Vec::is_empty);
+        if !empty {
+            break;
+        }
+        sleep(Duration::from_millis(args.empty_retry_delay_ms)).await;
+        response = fetch().await?;
+    }
+    Ok(response)
+}
+
+fn enforce_max_series(
+    mut response: Value,
+    max_series: usize,
+    truncate: bool,
+    warnings: &Warnings,
+) -> Result<Value, AppError> {
+    let Some(Value::Array(series)) = response.get_mut("series") else {
+        return Ok(response);
+    };
+    let count = series.len();
+    if count <= max_series {
+        return Ok(response);
+    }
+    if !truncate {
+        return Err(AppError::Usage(format!(
+            "Metrics query returned {count} series, more than --max-series {max_series}. Narrow the query or pass --truncate-series."
+        )));
+    }
+
+    series.truncate(max_series);
+    warnings.warn(&format!(
+        "Metrics query returned {count} series; output truncated to the first {max_series}."
+    ));
+    Ok(response)
+}
+
+/// Records `value` under `meta.<key>` of an object response. Responses whose
+/// `meta` is not an object, and non-object responses, are left unchanged.
+fn set_meta(response: &mut Value, key: &str, value: Value) {
+    let Value::Object(object) = response else {
+        return;
+    };
+    let meta = object
+        .entry("meta")
+        .or_insert_with(|| Value::Object(serde_json::Map::new()));
+    if let Value::Object(meta) = meta {
+        meta.insert(key.to_string(), value);
+    }
+}
+
+const UNGROUPED_KEY: &str = "_ungrouped";
+
+/// Replaces the response's `data` array with an object mapping each value found at
+/// `path` to the records holding it, in their original order. Records where the
+/// path is missing or null are kept under `_ungrouped`; non-string values are
+/// keyed by their JSON text.
+fn group_records(response: &mut Value, path: &str) {
+    let Some(data) = response.get_mut("data").filter(|data| data.is_array()) else {
+        return;
+    };
+    let Value::Array(records) = data.take() else {
+        return;
+    };
+    let mut groups = serde_json::Map::new();
+    for record in records {
+        let key = match records::lookup_path(&record, path) {
+            None | Some(Value::Null) => UNGROUPED_KEY.to_string(),
+            Some(Value::String(text)) => text.clone(),
+            Some(other) => other.to_string(),
+        };
+        if let Value::Array(group) = groups.entry(key).or_insert_with(|| json!([])) {
+            group.push(record);
+        }
+    }
+    *data = Value::Object(groups);
+}
+
+fn extract_pointer(response: Value, pointer: &str, require: bool) -> Result<Value, AppError> {
+    if !pointer.is_empty() && !pointer.starts_with('/') {
+        return Err(AppError::Usage(format!(
+            "Invalid JSON pointer `{pointer}`. Pointers start with `/`, e.g. /data/0/id."
+        )));
+    }
+
+    match response.pointer(pointer) {
+        Some(value) => Ok(value.clone()),
+        None if require => Err(AppError::Usage(format!(
+            "JSON pointer `{pointer}` did not resolve in the response."
+        ))),
+        None => Ok(Value::Null),
+    }
+}
+
+fn truncate_depth(value: Value, max_depth: usize) -> Value {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            if max_depth == 0 {
+                return json!({ "...": "truncated (depth)" });
+            }
+            Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, truncate_depth(value, max_depth - 1)))
+                    .collect(),
+            )
+        }
+        Value::Array(items) if !items.is_empty() => {
+            if max_depth == 0 {
+                return json!({ "...": "truncated (depth)" });
+            }
+            Value::Array(
+                items
+                    .into_iter()
+                    .map(|value| truncate_depth(value, max_depth - 1))
+                    .collect(),
+            )
+        }
+        other => other,
+    }
+}
+
+/// Adds a `<key>_human` string next to each object field holding a number of
+/// magnitude 1000 or more. Existing keys are never overwritten and numbers inside
+/// arrays (e.g. point lists) are left as they are.
+fn humanize_numbers(value: Value) -> Value {
+    match value {
+        Value::Object(map) => {
+            let mut output = serde_json::Map::new();
+            for (key, value) in map {
+                let human = value.as_f64().and_then(humanize_number);
+                output.insert(key.clone(), humanize_numbers(value));
+                if let Some(human) = human {
+                    output
+                        .entry(format!("{key}_human"))
+                        .or_insert(Value::String(human));
+                }
+            }
+            Value::Object(output)
+        }
+        Value::Array(items) => Value::Array(items.into_iter().map(humanize_numbers).collect()),
+        other => other,
+    }
+}
+
+fn humanize_number(number: f64) -> Option<String> {
+    let (scale, suffix) = [(1e12, "T"), (1e9, "B"), (1e6, "M"), (1e3, "K")]
+        .into_iter()
+        .find(|(scale, _)| number.abs() >= *scale)?;
+    let scaled = format!("{:.1}", number / scale);
+    Some(format!("{}{suffix}", scaled.trim_end_matches(".0")))
+}
+
+/// Serializes with sorted object keys, no insignificant whitespace, and numbers
+/// normalized so integral floats print as integers (`1.0` and `1e0` become `1`).
+fn canonical_json(value: &Value) -> String {
+    let mut out = String::new();
+    write_canonical(value, &mut out);
+    out
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by_key(|(key, _)| *key);
+            out.push('{');
+            for (index, (key, value)) in entries.into_iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        Value::Number(number) => match number.as_f64() {
+            Some(float)
+                if number.is_f64()
+                    && float.fract() == 0.0
+                    && float.abs() < 9_007_199_254_740_992.0 =>
+            {
+                out.push_str(&(float as i64).to_string());
+            }
+            _ => out.push_str(&number.to_string()),
+        },
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Writes `text` to the stdin of a pager command (program and arguments split on
+/// whitespace) and waits for the user to close it.
+fn page_output<P: Pager>(
+    command: &str,
+    text: &str,
+    spawn: impl FnOnce(&str, Vec<&str>) -> io::Result<P>,
+) -> io::Result<()> {
+    let mut parts = command.split_whitespace();
+    let program = parts
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty pager command"))?;
+    let mut pager = spawn(program, parts.collect())?;
+    if let Some(stdin) = pager.stdin() {
+        // Quitting the pager before reading everything closes the pipe early.
+        match stdin.write_all(text.as_bytes()) {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+    }
+    pager.wait()
+}
+
+/// A running pager that `page_output` writes to; tests substitute a fake.
+trait Pager {
+    fn stdin(&mut self) -> Option<&mut dyn Write>;
+    /// Closes stdin and waits for the pager to exit.
+    fn wait(&mut self) -> io::Result<()>;
+}
+
+impl Pager for process::Child {
+    fn stdin(&mut self) -> Option<&mut dyn Write> {
+        self.stdin.as_mut().map(|stdin| stdin as &mut dyn Write)
+    }
+
+    fn wait(&mut self) -> io::Result<()> {
+        process::Child::wait(self).map(drop)
+    }
+}
+
+fn spawn_pager(program: &str, args: Vec<&str>) -> io::Result<process::Child> {
+    process::Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .spawn()
+}
+
 fn print_json_stdout(value: Value, compact: bool) -> Result<(), serde_json::Error> {
     if compact {
         println!("{}", serde_json::to_string(&value)?);
@@ -167,3 +1667,663 @@ fn print_json_stderr(value: Value, compact: bool) -> Result<(), serde_json::Erro
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::io::{self, BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use clap::Parser;
+    use clap_complete::Shell;
+    use serde_json::json;
+
+    use super::{
+        Execution, Pager, Warnings, benchmark, canonical_json, completion_script,
+        enforce_max_series, execute, extract_pointer, fetch_records, group_records,
+        humanize_numbers, is_read_only, page_output, run, set_meta, success_exit_code, time_args,
+        truncate_depth, with_placeholder,
+    };
+    use crate::app_error::AppError;
+    use crate::cli::{Cli, Command, RecordArgs};
+    use crate::config::{Config, FileConfig};
+    use crate::credentials::ShellRunner;
+
+    #[test]
+    fn completion_scripts_cover_subcommands_and_flags() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let script = String::from_utf8(completion_script(shell)).unwrap();
+
+            assert!(script.contains("ddq"), "{shell}");
+            assert!(script.contains("logs-aggregate"), "{shell}");
+            assert!(script.contains("max-pages"), "{shell}");
+        }
+    }
+
+    #[test]
+    fn only_read_only_commands_can_be_benchmarked() {
+        let read_only = |args: &[&str]| {
+            let cli = Cli::try_parse_from([&["ddq"], args].concat()).unwrap();
+            is_read_only(&cli.command)
+        };
+
+        assert!(read_only(&["metrics", "avg:system.cpu.user{*}"]));
+        assert!(read_only(&["monitors", "list"]));
+        assert!(read_only(&[
+            "raw",
+            "--method",
+            "get",
+            "--path",
+            "/api/v1/validate"
+        ]));
+        assert!(!read_only(&[
+            "raw",
+            "--method",
+            "POST",
+            "--path",
+            "/api/v2/series"
+        ]));
+        assert!(!read_only(&["tags", "add", "web-1", "--tag", "env:prod"]));
+        assert!(!read_only(&["monitors", "mute", "--id", "42"]));
+        assert!(!read_only(&[
+            "metrics-submit",
+            "--metric",
+            "custom.metric",
+            "--value",
+            "1"
+        ]));
+    }
+
+    #[test]
+    fn time_args_include_logs_around() {
+        let cli = Cli::try_parse_from([
+            "ddq",
+            "logs",
+            "service:web",
+            "--around",
+            "2024-05-01T12:00:00Z",
+            "--window",
+            "10m",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            time_args(&cli.command),
+            ["now-15m", "now", "2024-05-01T12:00:00Z"]
+        );
+    }
+
+    fn execution(args: &[&str]) -> (Command, Execution) {
+        let flags = ["ddq", "--api-key", "key-1234", "--app-key", "app-5678"];
+        let cli = Cli::try_parse_from([&flags, args].concat()).unwrap();
+        let config =
+            Config::resolve(&cli, &FileConfig::default(), None, &ShellRunner, |_| None).unwrap();
+        let ctx = Execution::new(&cli, config, chrono_tz::UTC);
+        (cli.command, ctx)
+    }
+
+    fn dry_run(args: &[&str]) -> (Command, Execution) {
+        execution(&[&["--dry-run"], args].concat())
+    }
+
+    /// Serves every request with `respond(request line)` -> (status, JSON body)
+    /// and counts the requests. Returns the base URL and the count.
+    fn http_server(
+        respond: impl Fn(&str) -> (&'static str, &'static str) + Send + 'static,
+    ) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let site = format!("http://{}", listener.local_addr().unwrap());
+        let served = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&served);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let (status, body) = respond(&request_line);
+                let response = format!(
+                    "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (site, served)
+    }
+
+    #[tokio::test]
+    async fn metrics_batch_stops_sending_after_an_auth_error() {
+        let (site, served) = http_server(|_| ("403 Forbidden", r#"{"errors":["Forbidden"]}"#));
+        let (command, ctx) = execution(&[
+            "--site",
+            &site,
+            "--retries",
+            "0",
+            "--concurrency",
+            "1",
+            "metrics",
+            "avg:a{*}",
+            "avg:b{*}",
+            "avg:c{*}",
+        ]);
+
+        let result = execute(command, &ctx).await;
+
+        assert!(matches!(result, Err(AppError::Auth { status: 403, .. })));
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn events_preview_fetches_one_page_even_with_all() {
+        let (site, served) = http_server(|_| {
+            (
+                "200 OK",
+                r#"{"data":[{"id":"a","attributes":{}}],"meta":{"page":{"after":"c1"}}}"#,
+            )
+        });
+        let (command, ctx) = execution(&[
+            "--site",
+            &site,
+            "events",
+            "--all",
+            "--max-pages",
+            "3",
+            "--preview",
+        ]);
+
+        execute(command, &ctx).await.unwrap();
+
+        assert_eq!(served.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn benchmark_reuses_one_context_for_every_run() {
+        let (site, served) = http_server(|_| ("200 OK", r#"{"series":[]}"#));
+        let (command, ctx) = execution(&["--site", &site, "metrics", "avg:cpu{*}"]);
+
+        let durations = benchmark(&command, &ctx, 3, None).await.unwrap();
+
+        assert_eq!(durations.len(), 3);
+        assert_eq!(served.load(Ordering::SeqCst), 3);
+        assert_eq!(ctx.client.timings()["requests"], 3);
+    }
+
+    #[tokio::test]
+    async fn table_fallback_fails_the_run_under_fail_on_warnings() {
+        let (site, _) = http_server(|_| ("200 OK", r#"{"series":[]}"#));
+        let cli = Cli::try_parse_from([
+            "ddq",
+            "--site",
+            &site,
+            "--api-key",
+            "key-1234",
+            "--app-key",
+            "app-5678",
+            "--output",
+            "table",
+            "--fail-on-warnings",
+            "metrics",
+            "avg:cpu{*}",
+        ])
+        .unwrap();
+
+        let err = run(cli, false).await.unwrap_err();
+
+        assert!(matches!(err, AppError::WarningsPresent(_)));
+        assert_eq!(err.exit_code(), 7);
+    }
+
+    #[tokio::test]
+    async fn unit_lookup_failure_is_collected_as_that_items_error() {
+        let (site, _) = http_server(|request_line| {
+            if request_line.starts_with("GET /api/v1/metrics/cpu ") {
+                ("200 OK", r#"{"unit":"percent","per_unit":null}"#)
+            } else if request_line.starts_with("GET /api/v1/metrics/") {
+                ("404 Not Found", r#"{"errors":["Metric not found"]}"#)
+            } else if request_line.contains("query=avg%3Acpu") {
+                ("200 OK", r#"{"series":[{"metric":"cpu"}]}"#)
+            } else {
+                ("200 OK", r#"{"series":[{"metric":"gone"}]}"#)
+            }
+        });
+        let (command, ctx) = execution(&[
+            "--site",
+            &site,
+            "--retries",
+            "0",
+            "--collect-errors",
+            "metrics",
+            "avg:cpu{*}",
+            "avg:gone{*}",
+            "--annotate-units",
+        ]);
+
+        let (output, failure) = execute(command, &ctx).await.unwrap();
+
+        assert!(failure.is_none());
+        assert_eq!(
+            output[0]["ok"]["series"][0]["metric_unit"],
+            json!({ "unit": "percent", "per_unit": null })
+        );
+        assert_eq!(output[1]["error"]["status"], 404);
+    }
+
+    #[tokio::test]
+    async fn dry_run_describes_one_request_per_query() {
+        let (command, ctx) = dry_run(&["logs", "service:web", "--query", "service:api"]);
+
+        let (output, failure) = execute(command, &ctx).await.unwrap();
+
+        assert!(failure.is_none());
+        let described = output.as_array().unwrap();
+        assert_eq!(described.len(), 2);
+        for (description, query) in described.iter().zip(["service:web", "service:api"]) {
+            assert_eq!(description["dry_run"], true);
+            assert_eq!(description["method"], "POST");
+            assert_eq!(description["body"]["filter"]["query"], query);
+            assert_eq!(description["headers"]["DD-API-KEY"], "***REDACTED***");
+        }
+
+        let (command, ctx) = dry_run(&["metrics", "avg:a{*}", "avg:b{*}"]);
+        let (output, _) = execute(command, &ctx).await.unwrap();
+        assert_eq!(output.as_array().unwrap().len(), 2);
+        assert!(
+            output[1]["url"]
+                .as_str()
+                .unwrap()
+                .contains("query=avg%3Ab%7B")
+        );
+    }
+
+    #[tokio::test]
+    async fn dry_run_describes_the_count_aggregate_request() {
+        let (command, ctx) = dry_run(&["logs", "service:web", "--count"]);
+
+        let (output, _) = execute(command, &ctx).await.unwrap();
+
+        assert_eq!(output["dry_run"], true);
+        assert_eq!(output["method"], "POST");
+        assert!(
+            output["url"]
+                .as_str()
+                .unwrap()
+                .ends_with("/api/v2/logs/analytics/aggregate")
+        );
+        assert_eq!(output["body"]["filter"]["query"], "service:web");
+    }
+
+    #[tokio::test]
+    async fn dry_run_exits_with_the_configured_code_without_connecting() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let site = format!("http://{}", listener.local_addr().unwrap());
+        let cli = Cli::try_parse_from([
+            "ddq",
+            "--dry-run",
+            "--dry-run-exit-code",
+            "9",
+            "--site",
+            &site,
+            "--api-key",
+            "key-1234",
+            "--app-key",
+            "app-5678",
+            "logs",
+            "service:web",
+        ])
+        .unwrap();
+        let code = success_exit_code(&cli);
+
+        run(cli, true).await.unwrap();
+
+        assert_eq!(code, 9);
+        assert_eq!(
+            listener.accept().unwrap_err().kind(),
+            io::ErrorKind::WouldBlock
+        );
+    }
+
+    #[test]
+    fn truncate_depth_replaces_values_nested_past_limit() {
+        let value = json!({ "a": { "b": { "c": 1 } }, "d": [[1]] });
+        assert_eq!(
+            truncate_depth(value, 2),
+            json!({
+                "a": { "b": { "...": "truncated (depth)" } },
+                "d": [{ "...": "truncated (depth)" }]
+            })
+        );
+    }
+
+    #[test]
+    fn truncate_depth_keeps_shallow_values_intact() {
+        let value = json!({ "a": { "b": 1 }, "c": [], "d": "text" });
+        assert_eq!(truncate_depth(value.clone(), 2), value);
+    }
+
+    fn sample_series(count: usize) -> serde_json::Value {
+        let series: Vec<_> = (0..count)
+            .map(|index| json!({ "scope": format!("host:h{index}") }))
+            .collect();
+        json!({ "status": "ok", "series": series })
+    }
+
+    #[test]
+    fn enforce_max_series_rejects_oversized_response() {
+        let result = enforce_max_series(sample_series(3), 2, false, &Warnings::default());
+
+        assert!(matches!(result, Err(AppError::Usage(message)) if message.contains("3 series")));
+    }
+
+    #[test]
+    fn enforce_max_series_truncates_when_requested() {
+        let warnings = Warnings::default();
+        let response = enforce_max_series(sample_series(3), 2, true, &warnings).unwrap();
+
+        assert_eq!(response["series"].as_array().unwrap().len(), 2);
+        assert_eq!(response["series"][1]["scope"], "host:h1");
+        assert!(warnings.failure(false).is_none());
+        assert!(matches!(
+            warnings.failure(true),
+            Some(AppError::WarningsPresent(emitted)) if emitted.len() == 1
+        ));
+        assert_eq!(
+            enforce_max_series(sample_series(2), 2, false, &Warnings::default()).unwrap(),
+            sample_series(2)
+        );
+    }
+
+    fn retry_on_empty_args(empty_retries: u32) -> RecordArgs {
+        RecordArgs {
+            retry_on_empty: true,
+            empty_retries,
+            empty_retry_delay_ms: 1,
+            ..RecordArgs::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn retry_on_empty_returns_data_once_it_appears() {
+        let calls = Cell::new(0);
+        let response = fetch_records(&retry_on_empty_args(3), || {
+            calls.set(calls.get() + 1);
+            let data = if calls.get() < 3 {
+                json!([])
+            } else {
+                json!([{ "id": "a" }])
+            };
+            std::future::ready(Ok(json!({ "data": data })))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(response["data"][0]["id"], "a");
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[tokio::test]
+    async fn retry_on_empty_gives_up_after_limit() {
+        let calls = Cell::new(0);
+        let response = fetch_records(&retry_on_empty_args(2), || {
+            calls.set(calls.get() + 1);
+            std::future::ready(Ok(json!({ "data": [] })))
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(response["data"], json!([]));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn with_placeholder_templates_query_per_input_line() {
+        let command = Command::Metrics {
+            queries: vec!["avg:trace.http.request.duration{service:{}}".to_string()],
+            from: "now-15m".to_string(),
+            to: "now".to_string(),
+            align_window: None,
+            max_series: None,
+            truncate_series: false,
+            annotate_units: false,
+            v2: false,
+            formula: Vec::new(),
+            interval: None,
+        };
+
+        let templated: Vec<Vec<String>> = ["web", "api", "worker"]
+            .iter()
+            .map(
+                |service| match with_placeholder(&command, service).unwrap() {
+                    Command::Metrics { queries, .. } => queries,
+                    other => panic!("unexpected command {other:?}"),
+                },
+            )
+            .collect();
+
+        assert_eq!(
+            templated,
+            vec![
+                vec!["avg:trace.http.request.duration{service:web}".to_string()],
+                vec!["avg:trace.http.request.duration{service:api}".to_string()],
+                vec!["avg:trace.http.request.duration{service:worker}".to_string()],
+            ]
+        );
+    }
+
+    #[test]
+    fn with_placeholder_requires_placeholder_in_query() {
+        let command = Command::Events {
+            query: Some("service:web".to_string()),
+            from: "now-15m".to_string(),
+            to: "now".to_string(),
+            limit: 50,
+            page_size: None,
+            max_pages: None,
+            sort: "desc".to_string(),
+            priority: None,
+            alert_type: None,
+            all: false,
+            group_by: Vec::new(),
+            records: RecordArgs::default(),
+        };
+
+        assert!(matches!(
+            with_placeholder(&command, "web"),
+            Err(AppError::Usage(message)) if message.contains("placeholder")
+        ));
+    }
+
+    #[test]
+    fn group_records_buckets_by_field_value() {
+        let mut response = json!({
+            "data": [
+                {"id": "a", "attributes": {"service": "api"}},
+                {"id": "b", "attributes": {"service": "web"}},
+                {"id": "c", "attributes": {"service": "api"}},
+                {"id": "d", "attributes": {"service": null}},
+                {"id": "e", "attributes": {}},
+                {"id": "f", "attributes": {"service": 7}}
+            ],
+            "meta": {"page": {}}
+        });
+
+        group_records(&mut response, "attributes.service");
+
+        let ids = |key: &str| -> Vec<&str> {
+            response["data"][key]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|record| record["id"].as_str().unwrap())
+                .collect()
+        };
+        assert_eq!(ids("api"), ["a", "c"]);
+        assert_eq!(ids("web"), ["b"]);
+        assert_eq!(ids("7"), ["f"]);
+        assert_eq!(ids("_ungrouped"), ["d", "e"]);
+        assert_eq!(response["meta"], json!({"page": {}}));
+    }
+
+    #[test]
+    fn group_records_leaves_non_list_responses_alone() {
+        let mut response = json!({"data": {"id": "a"}});
+        group_records(&mut response, "id");
+        assert_eq!(response, json!({"data": {"id": "a"}}));
+    }
+
+    #[test]
+    fn extract_pointer_resolves_nested_values() {
+        let response = json!({ "data": [{ "attributes": { "service": "web" } }] });
+
+        assert_eq!(
+            extract_pointer(response, "/data/0/attributes/service", true).unwrap(),
+            json!("web")
+        );
+    }
+
+    #[test]
+    fn extract_pointer_handles_missing_values() {
+        let response = json!({ "data": [] });
+
+        assert_eq!(
+            extract_pointer(response.clone(), "/data/0/id", false).unwrap(),
+            serde_json::Value::Null
+        );
+        assert!(matches!(
+            extract_pointer(response.clone(), "/data/0/id", true),
+            Err(AppError::Usage(_))
+        ));
+        assert!(extract_pointer(response, "data", false).is_err());
+    }
+
+    #[test]
+    fn correlation_id_is_added_to_output_meta() {
+        let mut response = json!({ "data": [], "meta": { "page": {} } });
+        set_meta(&mut response, "correlation_id", json!("req-7f3a"));
+        assert_eq!(
+            response["meta"],
+            json!({ "page": {}, "correlation_id": "req-7f3a" })
+        );
+
+        let mut response = json!({ "series": [] });
+        set_meta(&mut response, "correlation_id", json!("req-7f3a"));
+        assert_eq!(response["meta"]["correlation_id"], "req-7f3a");
+
+        let mut response = json!([1, 2]);
+        set_meta(&mut response, "correlation_id", json!("req-7f3a"));
+        assert_eq!(response, json!([1, 2]));
+    }
+
+    #[test]
+    fn page_output_writes_text_to_pager_stdin() {
+        let mut pager = FakePager::default();
+        let mut started = Vec::new();
+
+        page_output("less -R", "{\n  \"a\": 1\n}\n", |program, args| {
+            started.push(program.to_string());
+            started.extend(args.into_iter().map(str::to_string));
+            Ok(&mut pager)
+        })
+        .unwrap();
+
+        assert_eq!(started, ["less", "-R"]);
+        assert_eq!(pager.stdin, b"{\n  \"a\": 1\n}\n");
+        assert!(pager.waited);
+        assert!(page_output("", "text", |_, _| Ok(&mut pager)).is_err());
+    }
+
+    #[derive(Default)]
+    struct FakePager {
+        stdin: Vec<u8>,
+        waited: bool,
+    }
+
+    impl Pager for &mut FakePager {
+        fn stdin(&mut self) -> Option<&mut dyn Write> {
+            Some(&mut self.stdin)
+        }
+
+        fn wait(&mut self) -> io::Result<()> {
+            self.waited = true;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn display_only_json_options_skip_tables() {
+        let pretty_json = |output: &str| {
+            Cli::try_parse_from([
+                "ddq",
+                "--output",
+                output,
+                "--humanize",
+                "--max-depth",
+                "2",
+                "logs",
+                "*",
+            ])
+            .unwrap()
+            .pretty_json()
+        };
+
+        assert!(pretty_json("pretty"));
+        assert!(!pretty_json("table"));
+        assert!(!pretty_json("json"));
+    }
+
+    #[test]
+    fn humanize_numbers_annotates_large_values_across_magnitudes() {
+        let value = json!({
+            "small": 999,
+            "thousands": 1_500,
+            "millions": 2_000_000,
+            "billions": 1_500_000_000u64,
+            "trillions": -3.25e12,
+            "nested": { "bytes": 12_345_678 },
+            "pointlist": [[1_700_000_000, 5_000]],
+            "label": "text"
+        });
+
+        assert_eq!(
+            humanize_numbers(value),
+            json!({
+                "small": 999,
+                "thousands": 1_500,
+                "thousands_human": "1.5K",
+                "millions": 2_000_000,
+                "millions_human": "2M",
+                "billions": 1_500_000_000u64,
+                "billions_human": "1.5B",
+                "trillions": -3.25e12,
+                "trillions_human": "-3.2T",
+                "nested": { "bytes": 12_345_678, "bytes_human": "12.3M" },
+                "pointlist": [[1_700_000_000, 5_000]],
+                "label": "text"
+            })
+        );
+    }
+
+    #[test]
+    fn canonical_json_is_stable_across_key_order_and_number_forms() {
+        let first: serde_json::Value =
+            serde_json::from_str(r#"{"b": [1.0, {"y": 2, "x": 1.5}], "a": -0.0, "c": "é"}"#)
+                .unwrap();
+        let second: serde_json::Value =
+            serde_json::from_str(r#"{"c":"é","a":0,"b":[1e0,{"x":15e-1,"y":2.00}]}"#).unwrap();
+
+        assert_eq!(canonical_json(&first), canonical_json(&second));
+        assert_eq!(
+            canonical_json(&first),
+            r#"{"a":0,"b":[1,{"x":1.5,"y":2}],"c":"é"}"#
+        );
+    }
+}
@@ -0,0 +1,65 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::Value;
+
+/// Reads the end time stored by the last successful run, if any.
+pub fn read(path: &Path) -> Result<Option<String>> {
+    match fs::read_to_string(path) {
+        Ok(contents) => {
+            let trimmed = contents.trim();
+            Ok((!trimmed.is_empty()).then(|| trimmed.to_string()))
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed reading resume marker `{}`", path.display()))
+        }
+    }
+}
+
+/// Whether `response` covers the whole window, so the marker may move past it;
+/// a paginated result cut short reports `"complete": false`.
+pub fn is_complete(response: &Value) -> bool {
+    response.get("complete") != Some(&Value::Bool(false))
+}
+
+/// Advances the marker to `to`. Called only once the run's output was delivered.
+pub fn record_success(path: &Path, to: DateTime<Utc>) -> Result<()> {
+    // Write beside the marker and rename so a crash never leaves it half-written.
+    let staging = path.with_extension("tmp");
+    fs::write(&staging, to.to_rfc3339_opts(SecondsFormat::Secs, true))
+        .and_then(|()| fs::rename(&staging, path))
+        .with_context(|| format!("Failed writing resume marker `{}`", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use serde_json::json;
+
+    use super::{is_complete, read, record_success};
+
+    #[test]
+    fn success_advances_marker() {
+        let path = std::env::temp_dir().join(format!("ddq-marker-{}", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(read(&path).unwrap(), None);
+
+        let first = Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap();
+        record_success(&path, first).unwrap();
+        assert_eq!(
+            read(&path).unwrap().as_deref(),
+            Some("2024-05-01T10:00:00Z")
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn partial_results_are_not_complete() {
+        assert!(is_complete(&json!({ "data": [] })));
+        assert!(!is_complete(&json!({ "data": [], "complete": false })));
+    }
+}
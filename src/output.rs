@@ -0,0 +1,425 @@
+use std::collections::HashSet;
+
+use anyhow::{Result, anyhow};
+use chrono::DateTime;
+use serde_json::{Map, Value, json};
+
+use crate::records::lookup_path;
+
+/// Renders a list of records as RFC4180 CSV: a header row of dot-joined field
+/// paths (`attributes.host`) in first-seen order, then one row per record.
+/// Records come from the response's `data` array, or the response itself when it
+/// is an array (e.g. after `--extract /data`).
+pub fn to_csv(response: &Value) -> Result<String> {
+    let rows = csv_rows(response)?;
+    let columns = csv_columns(&rows);
+
+    let mut out = String::new();
+    push_row(&mut out, columns.iter().map(|column| column.to_string()));
+    for row in &rows {
+        push_row(
+            &mut out,
+            columns
+                .iter()
+                .map(|column| row_value(row, column).map(cell).unwrap_or_default()),
+        );
+    }
+    Ok(out)
+}
+
+/// Records sampled per column by [`csv_schema`].
+pub const SCHEMA_SAMPLE_SIZE: usize = 100;
+
+/// Describes the columns [`to_csv`] writes for `response` as
+/// `{"columns": [{"name", "type"}], "sampled"}`. Each type is inferred from the
+/// non-empty values in the first `sample` records: `bool`, `number`,
+/// `timestamp` (RFC3339 strings) or, for anything else or a mix, `string`.
+pub fn csv_schema(response: &Value, sample: usize) -> Result<Value> {
+    let rows = csv_rows(response)?;
+    let columns = csv_columns(&rows);
+    let sampled = &rows[..rows.len().min(sample)];
+
+    let described: Vec<Value> = columns
+        .iter()
+        .map(|column| {
+            let types: HashSet<&str> = sampled
+                .iter()
+                .filter_map(|row| row_value(row, column))
+                .filter(|value| !value.is_null())
+                .map(value_type)
+                .collect();
+            let column_type = match types.into_iter().collect::<Vec<_>>()[..] {
+                [single] => single,
+                _ => "string",
+            };
+            json!({ "name": column, "type": column_type })
+        })
+        .collect();
+    Ok(json!({ "columns": described, "sampled": sampled.len() }))
+}
+
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "bool",
+        Value::Number(_) => "number",
+        Value::String(text) if DateTime::parse_from_rfc3339(text).is_ok() => "timestamp",
+        _ => "string",
+    }
+}
+
+/// Flattens each record of `response` into `(dotted path, value)` cells.
+fn csv_rows(response: &Value) -> Result<Vec<Vec<(String, &Value)>>> {
+    let records = match response {
+        Value::Object(object) => object.get("data").and_then(Value::as_array),
+        Value::Array(records) => Some(records),
+        _ => None,
+    }
+    .filter(|records| records.iter().all(Value::is_object))
+    .ok_or_else(|| {
+        anyhow!("CSV output needs a list of records (logs or events); this response has a different shape.")
+    })?;
+
+    Ok(records
+        .iter()
+        .map(|record| {
+            let mut row = Vec::new();
+            if let Value::Object(fields) = record {
+                flatten_into(fields, "", &mut row);
+            }
+            row
+        })
+        .collect())
+}
+
+/// Column paths across all rows, in first-seen order.
+fn csv_columns<'a>(rows: &'a [Vec<(String, &Value)>]) -> Vec<&'a str> {
+    let mut seen = HashSet::new();
+    let mut columns = Vec::new();
+    for (path, _) in rows.iter().flatten() {
+        if seen.insert(path.as_str()) {
+            columns.push(path.as_str());
+        }
+    }
+    columns
+}
+
+fn row_value<'a>(row: &[(String, &'a Value)], column: &str) -> Option<&'a Value> {
+    row.iter()
+        .find(|(path, _)| path == column)
+        .map(|(_, value)| *value)
+}
+
+/// Renders a response as newline-delimited JSON: one line per element of its
+/// `data` array, or the whole value on one line when there is no such array.
+/// Each record is followed by `terminator` (`'\n'`, or `'\0'` for `xargs -0`).
+pub fn to_ndjson(response: &Value, terminator: char) -> String {
+    match response.get("data") {
+        Some(Value::Array(records)) => records
+            .iter()
+            .map(|record| format!("{record}{terminator}"))
+            .collect(),
+        _ => format!("{response}{terminator}"),
+    }
+}
+
+/// Columns shown by `--output table` when no `--fields` are given.
+pub const DEFAULT_TABLE_COLUMNS: [&str; 5] = ["timestamp", "service", "host", "status", "message"];
+
+const TABLE_GAP: &str = "  ";
+const ELLIPSIS: char = '…';
+/// Narrowest a shrunk column gets, so it still shows a few characters.
+const MIN_SHRUNK_WIDTH: usize = 8;
+
+/// Renders logs/events records as an aligned text table with a header row.
+/// Each column is a dotted path looked up in the record's `attributes`, then its
+/// nested custom `attributes`, then the record itself. When `max_width` is set,
+/// the `message` column (or the last column) is cut with an ellipsis to fit.
+/// Returns `None` when the response is not a list of records.
+pub fn to_table(response: &Value, columns: &[String], max_width: Option<usize>) -> Option<String> {
+    let records = match response {
+        Value::Object(object) => object.get("data").and_then(Value::as_array),
+        Value::Array(records) => Some(records),
+        _ => None,
+    }
+    .filter(|records| records.iter().all(Value::is_object))?;
+
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| table_cell(record, column))
+                .collect()
+        })
+        .collect();
+    let widths = column_widths(columns, &rows, max_width);
+
+    let mut out = String::new();
+    push_table_row(&mut out, columns, &widths);
+    let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+    push_table_row(&mut out, &rule, &widths);
+    for row in &rows {
+        push_table_row(&mut out, row, &widths);
+    }
+    Some(out)
+}
+
+/// Width of each column: its widest cell or header, in characters. If the row
+/// would exceed `max_width`, the `message` column (or the last one) gives up the
+/// excess, but never shrinks below its header or a few characters.
+pub fn column_widths(
+    headers: &[String],
+    rows: &[Vec<String>],
+    max_width: Option<usize>,
+) -> Vec<usize> {
+    let mut widths: Vec<usize> = headers
+        .iter()
+        .map(|header| header.chars().count())
+        .collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let total = widths.iter().sum::<usize>() + TABLE_GAP.len() * widths.len().saturating_sub(1);
+    if let Some(max_width) = max_width
+        && total > max_width
+    {
+        let shrink = headers
+            .iter()
+            .position(|header| header == "message")
+            .or(widths.len().checked_sub(1));
+        if let Some(index) = shrink {
+            let floor = headers[index].chars().count().max(MIN_SHRUNK_WIDTH);
+            let target = widths[index].saturating_sub(total - max_width);
+            widths[index] = target.max(floor).min(widths[index]);
+        }
+    }
+    widths
+}
+
+/// Cuts `text` to `width` characters, ending with an ellipsis when it was longer.
+pub fn truncate_cell(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push(ELLIPSIS);
+    cut
+}
+
+fn table_cell(record: &Value, column: &str) -> String {
+    let value = [
+        format!("attributes.{column}"),
+        format!("attributes.attributes.{column}"),
+        column.to_string(),
+    ]
+    .iter()
+    .find_map(|path| lookup_path(record, path));
+    let text = value.map(cell).unwrap_or_default();
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn push_table_row(out: &mut String, cells: &[String], widths: &[usize]) {
+    let line: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, width)| format!("{:<width$}", truncate_cell(cell, *width)))
+        .collect();
+    out.push_str(line.join(TABLE_GAP).trim_end());
+    out.push('\n');
+}
+
+/// Collects the leaves of `fields` with dot-joined paths. Arrays and empty
+/// objects are leaves; they are written as compact JSON.
+fn flatten_into<'a>(
+    fields: &'a Map<String, Value>,
+    prefix: &str,
+    row: &mut Vec<(String, &'a Value)>,
+) {
+    for (key, value) in fields {
+        let path = format!("{prefix}{key}");
+        match value {
+            Value::Object(nested) if !nested.is_empty() => {
+                flatten_into(nested, &format!("{path}."), row)
+            }
+            _ => row.push((path, value)),
+        }
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+fn push_row(out: &mut String, cells: impl Iterator<Item = String>) {
+    let line: Vec<String> = cells.map(|cell| escape(&cell)).collect();
+    out.push_str(&line.join(","));
+    out.push_str("\r\n");
+}
+
+/// Quotes a field containing a comma, quote or line break, doubling inner quotes.
+fn escape(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{column_widths, csv_schema, to_csv, to_ndjson, to_table, truncate_cell};
+
+    #[test]
+    fn schema_infers_column_types_from_the_sampled_records() {
+        let response = json!({
+            "data": [
+                { "id": "a", "attributes": { "timestamp": "2024-05-01T00:00:00Z", "count": 3, "ok": true, "code": 1 } },
+                { "id": "b", "attributes": { "timestamp": "2024-05-01T00:01:00.5Z", "count": 4.5, "ok": null, "code": "E1" } },
+                { "id": "c", "attributes": { "timestamp": "not a time" } }
+            ]
+        });
+
+        let schema = csv_schema(&response, 2).unwrap();
+
+        assert_eq!(schema["sampled"], 2);
+        assert_eq!(
+            schema["columns"],
+            json!([
+                { "name": "attributes.code", "type": "string" },
+                { "name": "attributes.count", "type": "number" },
+                { "name": "attributes.ok", "type": "bool" },
+                { "name": "attributes.timestamp", "type": "timestamp" },
+                { "name": "id", "type": "string" }
+            ])
+        );
+        assert_eq!(
+            csv_schema(&response, 3).unwrap()["columns"][3]["type"],
+            "string"
+        );
+    }
+
+    #[test]
+    fn flattens_records_into_dotted_columns() {
+        let response = json!({
+            "data": [
+                {"id": "a", "attributes": {"host": "web-1", "status": 200, "tags": ["x", "y"]}},
+                {"id": "b", "attributes": {"host": "web-2", "custom": {"team": "core"}}}
+            ]
+        });
+
+        let csv = to_csv(&response).unwrap();
+
+        assert_eq!(
+            csv,
+            "attributes.host,attributes.status,attributes.tags,id,attributes.custom.team\r\n\
+             web-1,200,\"[\"\"x\"\",\"\"y\"\"]\",a,\r\n\
+             web-2,,,b,core\r\n"
+        );
+    }
+
+    #[test]
+    fn escapes_commas_quotes_and_newlines() {
+        let response = json!([{"message": "said \"hi\", left\nearly"}]);
+
+        assert_eq!(
+            to_csv(&response).unwrap(),
+            "message\r\n\"said \"\"hi\"\", left\nearly\"\r\n"
+        );
+    }
+
+    #[test]
+    fn rejects_responses_that_are_not_record_lists() {
+        assert!(to_csv(&json!({"series": []})).is_err());
+        assert!(to_csv(&json!({"data": {"id": "a"}})).is_err());
+        assert!(to_csv(&json!({"data": [1, 2]})).is_err());
+    }
+
+    #[test]
+    fn ndjson_prints_one_record_per_line() {
+        let response = json!({"data": [{"id": "a"}, {"id": "b"}], "meta": {}});
+        assert_eq!(
+            to_ndjson(&response, '\n'),
+            "{\"id\":\"a\"}\n{\"id\":\"b\"}\n"
+        );
+
+        let response = json!({"series": [1, 2]});
+        assert_eq!(to_ndjson(&response, '\n'), "{\"series\":[1,2]}\n");
+    }
+
+    #[test]
+    fn ndjson_can_separate_records_with_nul() {
+        let response = json!({"data": [{"message": "line one\nline two"}, {"id": "b"}]});
+
+        let output = to_ndjson(&response, '\0');
+
+        let records: Vec<&str> = output.split_terminator('\0').collect();
+        assert_eq!(
+            records,
+            ["{\"message\":\"line one\\nline two\"}", "{\"id\":\"b\"}"]
+        );
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(records[0]).unwrap()["message"],
+            "line one\nline two"
+        );
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn table_aligns_columns_from_record_attributes() {
+        let response = json!({
+            "data": [
+                {"id": "a", "attributes": {"service": "api", "status": "error", "message": "boom\nagain"}},
+                {"id": "b", "attributes": {"service": "checkout", "attributes": {"status": "info"}}}
+            ]
+        });
+
+        let table = to_table(
+            &response,
+            &strings(&["id", "service", "status", "message"]),
+            None,
+        );
+
+        assert_eq!(
+            table.as_deref(),
+            Some(
+                "id  service   status  message\n\
+                 --  --------  ------  ----------\n\
+                 a   api       error   boom again\n\
+                 b   checkout  info\n"
+            )
+        );
+        assert!(to_table(&json!({"series": []}), &strings(&["id"]), None).is_none());
+    }
+
+    #[test]
+    fn column_widths_shrink_the_message_column_to_fit() {
+        let headers = strings(&["host", "message", "status"]);
+        let rows = vec![strings(&[
+            "web-1",
+            "a very long message that will not fit",
+            "ok",
+        ])];
+
+        assert_eq!(column_widths(&headers, &rows, None), vec![5, 37, 6]);
+        assert_eq!(column_widths(&headers, &rows, Some(30)), vec![5, 15, 6]);
+        assert_eq!(column_widths(&headers, &rows, Some(10)), vec![5, 8, 6]);
+    }
+
+    #[test]
+    fn truncate_cell_ends_with_an_ellipsis() {
+        assert_eq!(truncate_cell("short", 8), "short");
+        assert_eq!(truncate_cell("much longer text", 8), "much lo…");
+    }
+}
@@ -0,0 +1,131 @@
+use anyhow::{Result, anyhow};
+
+/// Expands `--any facet=v1,v2` specs into OR-grouped clauses ANDed onto `query`,
+/// e.g. `env:prod` + `service=a,b` -> `env:prod AND (service:a OR service:b)`.
+pub fn expand_any(query: &str, specs: &[String]) -> Result<String> {
+    let mut clauses = Vec::new();
+    let base = query.trim();
+    if !base.is_empty() && base != "*" {
+        if base.to_ascii_uppercase().contains(" OR ") {
+            clauses.push(format!("({base})"));
+        } else {
+            clauses.push(base.to_string());
+        }
+    }
+
+    for spec in specs {
+        let (facet, values) = spec
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid --any `{spec}`. Expected facet=v1,v2,..."))?;
+        let facet = facet.trim();
+        if facet.is_empty() {
+            return Err(anyhow!("Facet name cannot be empty in --any `{spec}`."));
+        }
+        let terms: Vec<String> = values
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| format!("{facet}:{}", quote_value(value)))
+            .collect();
+        if terms.is_empty() {
+            return Err(anyhow!("No values given in --any `{spec}`."));
+        }
+        clauses.push(format!("({})", terms.join(" OR ")));
+    }
+
+    if clauses.is_empty() {
+        return Ok(query.to_string());
+    }
+    Ok(clauses.join(" AND "))
+}
+
+/// Trims `query` and collapses each whitespace run (including newlines) outside
+/// double-quoted strings to one space. Quoted strings, with their `\"` escapes,
+/// are kept exactly.
+pub fn normalize(query: &str) -> String {
+    let mut normalized = String::with_capacity(query.len());
+    let mut in_quotes = false;
+    let mut escaped = false;
+    let mut pending_space = false;
+    for ch in query.trim().chars() {
+        if in_quotes {
+            normalized.push(ch);
+            match ch {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_quotes = false,
+                _ => {}
+            }
+            continue;
+        }
+        if ch.is_whitespace() {
+            pending_space = true;
+            continue;
+        }
+        if pending_space {
+            normalized.push(' ');
+            pending_space = false;
+        }
+        in_quotes = ch == '"';
+        normalized.push(ch);
+    }
+    normalized
+}
+
+fn quote_value(value: &str) -> String {
+    if value
+        .chars()
+        .any(|ch| ch.is_whitespace() || matches!(ch, '"' | '(' | ')'))
+    {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{expand_any, normalize};
+
+    #[test]
+    fn expand_any_builds_or_group() {
+        assert_eq!(
+            expand_any("env:prod", &["service=a,b,c".to_string()]).unwrap(),
+            "env:prod AND (service:a OR service:b OR service:c)"
+        );
+        assert_eq!(
+            expand_any("*", &["service=api".to_string()]).unwrap(),
+            "(service:api)"
+        );
+    }
+
+    #[test]
+    fn expand_any_ands_multiple_groups() {
+        let specs = vec![
+            "service=a,b".to_string(),
+            "@http.status_code=500,503".to_string(),
+        ];
+
+        assert_eq!(
+            expand_any("env:prod OR env:staging", &specs).unwrap(),
+            "(env:prod OR env:staging) AND (service:a OR service:b) AND (@http.status_code:500 OR @http.status_code:503)"
+        );
+    }
+
+    #[test]
+    fn expand_any_rejects_empty_facet() {
+        assert!(expand_any("", &["=a,b".to_string()]).is_err());
+        assert!(expand_any("", &["service".to_string()]).is_err());
+        assert!(expand_any("", &["service=,".to_string()]).is_err());
+    }
+
+    #[test]
+    fn normalize_collapses_whitespace_outside_quotes() {
+        let query = "  service:web\n    AND  @msg:\"disk   full\\\"  now\"\n\tstatus:error \n";
+
+        assert_eq!(
+            normalize(query),
+            "service:web AND @msg:\"disk   full\\\"  now\" status:error"
+        );
+    }
+}
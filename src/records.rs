@@ -0,0 +1,610 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+
+use chrono::DateTime;
+use chrono_tz::Tz;
+use rand::rngs::StdRng;
+use rand::{RngExt, SeedableRng};
+use serde_json::{Map, Value, json};
+
+use crate::cli::RecordArgs;
+use crate::time_expr::{TimestampFormat, format_timestamp};
+
+/// Page size to request, accounting for `--preview`.
+pub fn request_limit(args: &RecordArgs, limit: u32) -> u32 {
+    if args.preview { 1 } else { limit }
+}
+
+/// Applies the record post-processing options to a logs/events response.
+pub fn apply(mut response: Value, args: &RecordArgs, descending: bool) -> Value {
+    if args.preview {
+        return preview(&response);
+    }
+
+    let Some(Value::Array(records)) = response.get_mut("data") else {
+        return response;
+    };
+
+    if args.dedupe {
+        dedupe_by_id(records);
+    }
+    if let Some(key) = &args.sort_key {
+        sort_records(records, key, descending);
+    }
+    if !args.fields.is_empty() {
+        for record in records.iter_mut() {
+            *record = project_fields(record.take(), &args.fields);
+        }
+    }
+    response
+}
+
+/// Builds an object holding only the given dotted paths of a record's
+/// `attributes`, nested as in the source. Each path is matched against the
+/// standard attributes first and then the custom `attributes.attributes`, and
+/// stays where it was found. Paths found in neither are set to null so every
+/// record has the same shape.
+pub fn select_fields(attributes: &Value, paths: &[String]) -> Value {
+    let mut selected = Map::new();
+    for path in paths {
+        let custom = format!("attributes.{path}");
+        match [path.as_str(), custom.as_str()]
+            .into_iter()
+            .find_map(|candidate| Some((candidate, lookup_path(attributes, candidate)?)))
+        {
+            Some((found, field)) => insert_path(&mut selected, found, field.clone()),
+            None => insert_path(&mut selected, path, Value::Null),
+        }
+    }
+    Value::Object(selected)
+}
+
+/// Looks up a dotted path such as `attributes.service` inside a record.
+pub fn lookup_path<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| current.get(segment))
+}
+
+fn lookup_path_mut<'a>(value: &'a mut Value, path: &str) -> Option<&'a mut Value> {
+    path.split('.')
+        .try_fold(value, |current, segment| current.get_mut(segment))
+}
+
+/// Rewrites RFC3339 timestamps at each dotted path in `records` as local time in
+/// `timezone`, written in `format`, for display only. Values that are missing or
+/// not RFC3339 strings are left as they are.
+pub fn localize_timestamps(
+    records: &mut [Value],
+    paths: &[String],
+    timezone: Tz,
+    format: &TimestampFormat,
+) {
+    for record in records {
+        for path in paths {
+            let Some(field) = lookup_path_mut(record, path) else {
+                continue;
+            };
+            if let Some(parsed) = field
+                .as_str()
+                .and_then(|text| DateTime::parse_from_rfc3339(text).ok())
+            {
+                *field = format_timestamp(&parsed.with_timezone(&timezone), format);
+            }
+        }
+    }
+}
+
+/// Removes records whose `id` already appeared earlier in the slice. Records
+/// without an `id` are always kept.
+pub fn dedupe_by_id(records: &mut Vec<Value>) {
+    let mut seen = HashSet::new();
+    records.retain(|record| match record.get("id").and_then(Value::as_str) {
+        Some(id) => seen.insert(id.to_string()),
+        None => true,
+    });
+}
+
+/// Combines the `data` records of several logs responses into one response:
+/// concatenated in order, deduplicated by `id` and sorted by
+/// `attributes.timestamp`. Per-query metadata such as page cursors is dropped.
+pub fn merge_responses(responses: Vec<Value>, descending: bool) -> Value {
+    let mut merged = Vec::new();
+    for mut response in responses {
+        if let Some(Value::Array(records)) = response.get_mut("data") {
+            merged.append(records);
+        }
+    }
+    dedupe_by_id(&mut merged);
+    sort_records(&mut merged, "attributes.timestamp", descending);
+    json!({ "data": merged })
+}
+
+/// Keeps each record with probability `rate`. A fixed `seed` makes the selection
+/// reproducible for the same input.
+pub fn sample_records(records: &mut Vec<Value>, rate: f64, seed: Option<u64>) {
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => rand::make_rng(),
+    };
+    records.retain(|_| rng.random_bool(rate));
+}
+
+/// Moves the fields of a record's `attributes` object to the top level, adding
+/// `prefix` to each key. Keys that still collide with an outer field are written
+/// as `attributes.<key>` instead.
+pub fn flatten_attributes(record: Value, prefix: &str) -> Value {
+    let Value::Object(mut outer) = record else {
+        return record;
+    };
+    let Some(Value::Object(attributes)) = outer.remove("attributes") else {
+        return Value::Object(outer);
+    };
+
+    for (key, value) in attributes {
+        let lifted = format!("{prefix}{key}");
+        let key = if outer.contains_key(&lifted) {
+            format!("attributes.{key}")
+        } else {
+            lifted
+        };
+        outer.insert(key, value);
+    }
+    Value::Object(outer)
+}
+
+/// Reduces a log record's `attributes` to the requested fields with
+/// [`select_fields`], keeping `id` and `type`.
+pub fn project_fields(record: Value, fields: &[String]) -> Value {
+    let Value::Object(mut outer) = record else {
+        return record;
+    };
+    let attributes = outer.remove("attributes").unwrap_or(Value::Null);
+    outer.insert("attributes".to_string(), select_fields(&attributes, fields));
+    Value::Object(outer)
+}
+
+fn insert_path(target: &mut Map<String, Value>, path: &str, value: Value) {
+    match path.split_once('.') {
+        Some((head, rest)) => {
+            let child = target
+                .entry(head)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if let Value::Object(child) = child {
+                insert_path(child, rest, value);
+            }
+        }
+        None => {
+            target.insert(path.to_string(), value);
+        }
+    }
+}
+
+/// Counts records grouped by each field in turn, producing nested
+/// `{group_value: count}` maps. Fields are looked up in `attributes.attributes`,
+/// then `attributes`, then as a full dotted path; records without the field are
+/// counted under `_missing`.
+pub fn count_by(records: &[Value], fields: &[String]) -> Value {
+    let mut counts = Map::new();
+    for record in records {
+        let Some((last, parents)) = fields.split_last() else {
+            break;
+        };
+        let mut node = &mut counts;
+        for field in parents {
+            node = node
+                .entry(group_value(record, field))
+                .or_insert_with(|| Value::Object(Map::new()))
+                .as_object_mut()
+                .expect("group nodes are objects");
+        }
+        let count = node.entry(group_value(record, last)).or_insert(json!(0));
+        *count = json!(count.as_u64().unwrap_or(0) + 1);
+    }
+    Value::Object(counts)
+}
+
+/// The events `--group-by` output: `count_by` over the response's records, how
+/// many were counted, and whether they are every event in the window. A result
+/// cut short by `--max-pages` or the total timeout has `"complete": false`.
+pub fn group_counts(response: &Value, fields: &[String]) -> Option<Value> {
+    let records = response.get("data")?.as_array()?;
+    Some(json!({
+        "group_by": fields,
+        "counts": count_by(records, fields),
+        "counted": records.len(),
+        "complete": response.get("complete") != Some(&Value::Bool(false)),
+    }))
+}
+
+fn group_value(record: &Value, field: &str) -> String {
+    let value = [
+        format!("attributes.attributes.{field}"),
+        format!("attributes.{field}"),
+        field.to_string(),
+    ]
+    .iter()
+    .find_map(|path| lookup_path(record, path).filter(|value| !value.is_null()));
+
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(other) => other.to_string(),
+        None => "_missing".to_string(),
+    }
+}
+
+/// Reduces a response to its first record plus the total count when Datadog
+/// reports one.
+pub fn preview(response: &Value) -> Value {
+    let mut output = Map::new();
+    output.insert(
+        "record".to_string(),
+        response
+            .get("data")
+            .and_then(|data| data.get(0))
+            .cloned()
+            .unwrap_or(Value::Null),
+    );
+    let total = ["meta.page.total_count", "meta.total_count", "meta.total"]
+        .iter()
+        .find_map(|path| lookup_path(response, path));
+    if let Some(total) = total {
+        output.insert("total_count".to_string(), total.clone());
+    }
+    Value::Object(output)
+}
+
+/// Stable-sorts records by the value at `key_path`. Numbers sort numerically and
+/// strings are read as RFC3339 timestamps; missing or unparseable values always
+/// sort last regardless of direction.
+pub fn sort_records(records: &mut [Value], key_path: &str, descending: bool) {
+    records.sort_by(|left, right| {
+        let left = sort_value(left, key_path);
+        let right = sort_value(right, key_path);
+        match (left, right) {
+            (Some(left), Some(right)) => {
+                let ordering = left.partial_cmp(&right).unwrap_or(Ordering::Equal);
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+}
+
+fn sort_value(record: &Value, key_path: &str) -> Option<f64> {
+    match lookup_path(record, key_path)? {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|dt| dt.timestamp_millis() as f64),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::{
+        apply, count_by, dedupe_by_id, flatten_attributes, group_counts, localize_timestamps,
+        merge_responses, preview, project_fields, request_limit, sample_records, select_fields,
+        sort_records,
+    };
+    use crate::cli::RecordArgs;
+    use crate::time_expr::TimestampFormat;
+
+    #[test]
+    fn merge_responses_dedupes_and_resorts_by_timestamp() {
+        let log = |id: &str, timestamp: &str| json!({ "id": id, "attributes": { "timestamp": timestamp } });
+        let errors = json!({
+            "data": [log("a", "2024-05-01T10:00:03Z"), log("b", "2024-05-01T10:00:01Z")],
+            "meta": { "page": { "after": "c1" } }
+        });
+        let timeouts = json!({
+            "data": [log("c", "2024-05-01T10:00:04Z"), log("a", "2024-05-01T10:00:03Z"), log("d", "2024-05-01T10:00:02Z")]
+        });
+
+        let merged = merge_responses(vec![errors, timeouts], true);
+
+        let ids: Vec<&str> = merged["data"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|record| record["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["c", "a", "d", "b"]);
+        assert!(merged.get("meta").is_none());
+    }
+
+    #[test]
+    fn dedupe_by_id_keeps_first_copy_across_merged_pages() {
+        let first_page = json!([{ "id": "a", "n": 1 }, { "id": "b", "n": 2 }]);
+        let second_page = json!([{ "id": "b", "n": 3 }, { "id": "c", "n": 4 }]);
+        let mut merged: Vec<Value> = first_page
+            .as_array()
+            .unwrap()
+            .iter()
+            .chain(second_page.as_array().unwrap())
+            .cloned()
+            .collect();
+
+        dedupe_by_id(&mut merged);
+
+        assert_eq!(
+            merged,
+            vec![
+                json!({ "id": "a", "n": 1 }),
+                json!({ "id": "b", "n": 2 }),
+                json!({ "id": "c", "n": 4 }),
+            ]
+        );
+    }
+
+    #[test]
+    fn sort_records_orders_by_custom_key_with_missing_values_last() {
+        let mut records = vec![
+            json!({ "id": "a", "attributes": { "attributes": { "duration": 30 } } }),
+            json!({ "id": "b", "attributes": {} }),
+            json!({ "id": "c", "attributes": { "attributes": { "duration": 10 } } }),
+            json!({ "id": "d", "attributes": { "attributes": { "duration": "slow" } } }),
+            json!({ "id": "e", "attributes": { "attributes": { "duration": 20 } } }),
+        ];
+
+        sort_records(&mut records, "attributes.attributes.duration", true);
+
+        let ids: Vec<&str> = records
+            .iter()
+            .map(|record| record["id"].as_str().unwrap())
+            .collect();
+        assert_eq!(ids, ["a", "e", "c", "b", "d"]);
+    }
+
+    #[test]
+    fn sort_records_reads_rfc3339_timestamps() {
+        let mut records = vec![
+            json!({ "id": "late", "attributes": { "timestamp": "2024-05-01T10:00:00Z" } }),
+            json!({ "id": "early", "attributes": { "timestamp": "2024-05-01T09:00:00+00:00" } }),
+        ];
+
+        sort_records(&mut records, "attributes.timestamp", false);
+
+        assert_eq!(records[0]["id"], "early");
+        assert_eq!(records[1]["id"], "late");
+    }
+
+    #[test]
+    fn preview_forces_single_record_limit() {
+        let preview_args = RecordArgs {
+            preview: true,
+            ..RecordArgs::default()
+        };
+
+        assert_eq!(request_limit(&preview_args, 500), 1);
+        assert_eq!(request_limit(&RecordArgs::default(), 500), 500);
+    }
+
+    #[test]
+    fn preview_keeps_first_record_and_total() {
+        let response = json!({
+            "data": [{ "id": "a" }],
+            "meta": { "page": { "total_count": 42, "after": "cursor" } }
+        });
+
+        assert_eq!(
+            preview(&response),
+            json!({ "record": { "id": "a" }, "total_count": 42 })
+        );
+        assert_eq!(preview(&json!({ "data": [] })), json!({ "record": null }));
+    }
+
+    #[test]
+    fn sample_records_is_deterministic_for_a_seed() {
+        let input: Vec<Value> = (0..200).map(|index| json!({ "id": index })).collect();
+
+        let mut first = input.clone();
+        sample_records(&mut first, 0.5, Some(7));
+        let mut second = input.clone();
+        sample_records(&mut second, 0.5, Some(7));
+
+        assert_eq!(first, second);
+        assert!(first.len() > 50 && first.len() < 150);
+
+        let mut all = input.clone();
+        sample_records(&mut all, 1.0, Some(7));
+        assert_eq!(all.len(), input.len());
+    }
+
+    #[test]
+    fn flatten_attributes_lifts_fields_and_prefixes_collisions() {
+        let record = json!({
+            "id": "AQAA",
+            "type": "log",
+            "attributes": { "service": "web", "type": "access", "status": "info" }
+        });
+
+        assert_eq!(
+            flatten_attributes(record.clone(), ""),
+            json!({
+                "id": "AQAA",
+                "type": "log",
+                "service": "web",
+                "attributes.type": "access",
+                "status": "info"
+            })
+        );
+        assert_eq!(
+            flatten_attributes(record, "attr_"),
+            json!({
+                "id": "AQAA",
+                "type": "log",
+                "attr_service": "web",
+                "attr_type": "access",
+                "attr_status": "info"
+            })
+        );
+    }
+
+    #[test]
+    fn count_by_builds_nested_group_counts() {
+        let records = vec![
+            json!({ "attributes": { "attributes": { "alert_type": "error", "source": "aws" } } }),
+            json!({ "attributes": { "attributes": { "alert_type": "error", "source": "k8s" } } }),
+            json!({ "attributes": { "attributes": { "alert_type": "error", "source": "aws" } } }),
+            json!({ "attributes": { "attributes": { "source": "aws" } } }),
+        ];
+
+        assert_eq!(
+            count_by(&records, &["alert_type".to_string()]),
+            json!({ "error": 3, "_missing": 1 })
+        );
+        assert_eq!(
+            count_by(&records, &["alert_type".to_string(), "source".to_string()]),
+            json!({ "error": { "aws": 2, "k8s": 1 }, "_missing": { "aws": 1 } })
+        );
+    }
+
+    #[test]
+    fn group_counts_reports_how_many_events_were_counted() {
+        let fields = vec!["alert_type".to_string()];
+        let response = json!({
+            "data": [
+                { "attributes": { "attributes": { "alert_type": "error" } } },
+                { "attributes": { "attributes": { "alert_type": "info" } } }
+            ],
+            "complete": false
+        });
+
+        assert_eq!(
+            group_counts(&response, &fields),
+            Some(json!({
+                "group_by": ["alert_type"],
+                "counts": { "error": 1, "info": 1 },
+                "counted": 2,
+                "complete": false
+            }))
+        );
+        assert_eq!(group_counts(&json!({ "errors": [] }), &fields), None);
+    }
+
+    #[test]
+    fn localize_timestamps_rewrites_utc_fields_in_display_zone() {
+        let record = json!({
+            "attributes": { "timestamp": "2024-07-01T12:30:00.250Z", "status": "info" }
+        });
+        let paths = vec![
+            "attributes.timestamp".to_string(),
+            "missing.path".to_string(),
+        ];
+
+        let mut new_york = vec![record.clone()];
+        localize_timestamps(
+            &mut new_york,
+            &paths,
+            chrono_tz::America::New_York,
+            &TimestampFormat::Rfc3339,
+        );
+        assert_eq!(
+            new_york[0]["attributes"]["timestamp"],
+            "2024-07-01T08:30:00.250-04:00"
+        );
+
+        let mut tokyo = vec![record];
+        localize_timestamps(
+            &mut tokyo,
+            &paths,
+            chrono_tz::Asia::Tokyo,
+            &TimestampFormat::Rfc3339,
+        );
+        assert_eq!(
+            tokyo[0]["attributes"]["timestamp"],
+            "2024-07-01T21:30:00.250+09:00"
+        );
+        assert_eq!(tokyo[0]["attributes"]["status"], "info");
+
+        let mut unix = vec![json!({ "attributes": { "timestamp": "2024-07-01T12:30:00.250Z" } })];
+        localize_timestamps(
+            &mut unix,
+            &paths,
+            chrono_tz::UTC,
+            &TimestampFormat::UnixMillis,
+        );
+        assert_eq!(unix[0]["attributes"]["timestamp"], 1_719_837_000_250_i64);
+    }
+
+    #[test]
+    fn project_fields_keeps_requested_standard_and_custom_attributes() {
+        let record = json!({
+            "id": "AQ",
+            "type": "log",
+            "attributes": {
+                "service": "web",
+                "message": "a long message",
+                "host": "h1",
+                "attributes": {
+                    "http": { "status_code": 500, "method": "GET" },
+                    "duration": 12
+                }
+            }
+        });
+        let fields = ["service", "http.status_code", "absent"].map(str::to_string);
+
+        assert_eq!(
+            project_fields(record, &fields),
+            json!({
+                "id": "AQ",
+                "type": "log",
+                "attributes": {
+                    "service": "web",
+                    "attributes": { "http": { "status_code": 500 } },
+                    "absent": null
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn select_fields_fills_missing_paths_with_null() {
+        let attributes = json!({
+            "timestamp": "2024-05-01T00:00:00Z",
+            "host": "h1",
+            "attributes": { "http": { "status_code": 500 } }
+        });
+        let fields = ["host", "attributes.http.status_code", "service"].map(str::to_string);
+
+        assert_eq!(
+            select_fields(&attributes, &fields),
+            json!({
+                "host": "h1",
+                "attributes": { "http": { "status_code": 500 } },
+                "service": null
+            })
+        );
+    }
+
+    #[test]
+    fn apply_projects_attributes_of_every_record() {
+        let response = json!({
+            "data": [
+                { "id": "a", "attributes": { "host": "h1", "message": "m" } },
+                { "id": "b" }
+            ]
+        });
+        let args = RecordArgs {
+            fields: vec!["host".to_string()],
+            ..RecordArgs::default()
+        };
+
+        assert_eq!(
+            apply(response, &args, true)["data"],
+            json!([
+                { "id": "a", "attributes": { "host": "h1" } },
+                { "id": "b", "attributes": { "host": null } }
+            ])
+        );
+    }
+}
@@ -0,0 +1,283 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{Context, Result, anyhow, bail};
+use clap::{Arg, ArgAction, CommandFactory};
+use serde_json::{Map, Value};
+
+use crate::cli::Cli;
+
+/// Expands `--spec <PATH>` into ordinary command-line arguments so the spec goes
+/// through the same parsing and validation as typed flags.
+///
+/// The spec is a YAML (or JSON) object with a `command` (e.g. `logs`, or
+/// `logs-metrics create`) and one field per flag or positional argument, named
+/// like the flag (`from`, `limit`, `output`, `sort_key`). Flags given on the real
+/// command line come later and therefore override spec fields. Arguments are
+/// returned unchanged when there is no `--spec`.
+pub fn expand_args(args: Vec<String>) -> Result<Vec<String>> {
+    let Some(path) = spec_path(&args) else {
+        return Ok(args);
+    };
+    let mut spec = load(Path::new(&path))?;
+    let command_path = match spec.remove("command") {
+        Some(Value::String(command)) => command,
+        _ => bail!("Spec `{path}` needs a `command` field, e.g. `command: logs`."),
+    };
+
+    let root = Cli::command();
+    let mut subcommand = &root;
+    let mut names = Vec::new();
+    for name in command_path.split_whitespace() {
+        subcommand = subcommand
+            .find_subcommand(name)
+            .ok_or_else(|| anyhow!("Unknown command `{command_path}` in spec `{path}`."))?;
+        names.push(subcommand.get_name().to_string());
+    }
+    if names.is_empty() {
+        bail!("Spec `{path}` has an empty `command` field.");
+    }
+
+    let mut spec_global = Vec::new();
+    let mut spec_positional = Vec::new();
+    let mut spec_local = Vec::new();
+    for (key, value) in &spec {
+        if let Some(arg) = find_arg(&root, key) {
+            push_arg(arg, key, value, &mut spec_global, &mut spec_positional)?;
+        } else if let Some(arg) = find_arg(subcommand, key) {
+            push_arg(arg, key, value, &mut spec_local, &mut spec_positional)?;
+        } else {
+            bail!("Unknown field `{key}` in spec `{path}` for `{command_path}`.");
+        }
+    }
+
+    let mut tokens = args.into_iter();
+    let program = tokens.next().unwrap_or_default();
+    let mut user_global = Vec::new();
+    let mut user_local = Vec::new();
+    let mut user_positional = false;
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            user_local.push(token);
+            user_local.extend(tokens.by_ref());
+            user_positional = true;
+            break;
+        }
+        if let Some(flag) = token.strip_prefix("--") {
+            let (name, inline_value) = match flag.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (flag, false),
+            };
+            let global = root
+                .get_arguments()
+                .find(|arg| arg.get_long() == Some(name));
+            let takes_value = global
+                .or_else(|| {
+                    subcommand
+                        .get_arguments()
+                        .find(|arg| arg.get_long() == Some(name))
+                })
+                .is_some_and(|arg| arg.get_action().takes_values());
+            let target = if global.is_some() || name == "help" || name == "version" {
+                &mut user_global
+            } else {
+                &mut user_local
+            };
+            target.push(token.clone());
+            if takes_value && !inline_value {
+                target.extend(tokens.next());
+            }
+        } else if let Some(cluster) = token.strip_prefix('-').filter(|rest| !rest.is_empty()) {
+            // `-vv` is a cluster of flags; `-n5` carries its value inline.
+            let mut shorts = cluster.chars();
+            let short = shorts.next();
+            let inline_value = !shorts.as_str().is_empty();
+            let global = root.get_arguments().find(|arg| arg.get_short() == short);
+            let takes_value = global
+                .or_else(|| {
+                    subcommand
+                        .get_arguments()
+                        .find(|arg| arg.get_short() == short)
+                })
+                .is_some_and(|arg| arg.get_action().takes_values());
+            let target = if global.is_some() || short == Some('h') || short == Some('V') {
+                &mut user_global
+            } else {
+                &mut user_local
+            };
+            target.push(token.clone());
+            if takes_value && !inline_value {
+                target.extend(tokens.next());
+            }
+        } else if !user_positional && root.find_subcommand(&token).is_some() {
+            if names.first() != Some(&token) {
+                bail!("Command `{token}` conflicts with `{command_path}` from spec `{path}`.");
+            }
+        } else if !user_positional && names.get(1..).is_some_and(|rest| rest.contains(&token)) {
+            // Nested action name repeated on the command line, e.g. `create`.
+        } else {
+            user_local.push(token);
+            user_positional = true;
+        }
+    }
+
+    let mut expanded = vec![program];
+    expanded.extend(spec_global);
+    expanded.extend(user_global);
+    expanded.extend(names);
+    expanded.extend(spec_local);
+    expanded.extend(user_local);
+    // After `--` so queries such as `-status:info` are not read as flags.
+    if !user_positional && !spec_positional.is_empty() {
+        expanded.push("--".to_string());
+        expanded.extend(spec_positional);
+    }
+    Ok(expanded)
+}
+
+fn spec_path(args: &[String]) -> Option<String> {
+    let mut tokens = args.iter();
+    while let Some(token) = tokens.next() {
+        if token == "--" {
+            return None;
+        }
+        if token == "--spec" {
+            return tokens.next().cloned();
+        }
+        if let Some(path) = token.strip_prefix("--spec=") {
+            return Some(path.to_string());
+        }
+    }
+    None
+}
+
+fn load(path: &Path) -> Result<Map<String, Value>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed reading spec file `{}`", path.display()))?;
+    // YAML is a superset of JSON, so one parser covers both formats.
+    match serde_yaml::from_str::<Value>(&contents)
+        .with_context(|| format!("Invalid spec file `{}`", path.display()))?
+    {
+        Value::Object(fields) => Ok(fields),
+        _ => bail!("Spec file `{}` must contain a mapping.", path.display()),
+    }
+}
+
+fn find_arg<'a>(command: &'a clap::Command, key: &str) -> Option<&'a Arg> {
+    let key = key.replace('-', "_");
+    command.get_arguments().find(|arg| {
+        arg.get_id() != "spec"
+            && (arg.get_id() == key.as_str()
+                || arg
+                    .get_long()
+                    .is_some_and(|long| long.replace('-', "_") == key))
+    })
+}
+
+fn push_arg(
+    arg: &Arg,
+    key: &str,
+    value: &Value,
+    flags: &mut Vec<String>,
+    positional: &mut Vec<String>,
+) -> Result<()> {
+    let values = match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    };
+    for value in values {
+        let text = match value {
+            Value::String(text) => text.clone(),
+            Value::Number(number) => number.to_string(),
+            Value::Bool(flag) => flag.to_string(),
+            _ => bail!("Spec field `{key}` must be a string, number, boolean, or list of those."),
+        };
+        match (arg.get_long(), arg.get_action()) {
+            (Some(long), ArgAction::SetTrue) => match value {
+                Value::Bool(true) => flags.push(format!("--{long}")),
+                Value::Bool(false) => {}
+                _ => bail!("Spec field `{key}` must be true or false."),
+            },
+            (Some(long), _) => flags.push(format!("--{long}={text}")),
+            (None, _) => positional.push(text),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::expand_args;
+    use crate::cli::{Cli, Command, OutputFormat};
+
+    fn write_spec(name: &str, contents: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ddq-spec-{}-{name}", std::process::id()));
+        std::fs::write(&path, contents).unwrap();
+        path.display().to_string()
+    }
+
+    fn args(tokens: &[&str]) -> Vec<String> {
+        tokens.iter().map(|token| token.to_string()).collect()
+    }
+
+    #[test]
+    fn spec_fields_apply_and_cli_flags_override_them() {
+        let path = write_spec(
+            "override.yaml",
+            "command: logs\nquery: -status:info\nfrom: now-1h\nlimit: 10\noutput: pretty\ndedupe: true\n",
+        );
+
+        let expanded = expand_args(args(&["ddq", "--spec", &path, "--limit", "20"])).unwrap();
+        let cli = Cli::try_parse_from(expanded).unwrap();
+
+        assert_eq!(cli.output, OutputFormat::Pretty);
+        let Command::Logs {
+            query,
+            from,
+            limit,
+            records,
+            ..
+        } = cli.command
+        else {
+            panic!("expected logs command");
+        };
+        assert_eq!(query, "-status:info");
+        assert_eq!(from, "now-1h");
+        assert_eq!(limit, 20);
+        assert!(records.dedupe);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn spec_keeps_root_short_flags_before_the_command() {
+        let path = write_spec(
+            "verbose.yaml",
+            "command: events
+limit: 5
+",
+        );
+
+        let expanded = expand_args(args(&["ddq", "--spec", &path, "-vv"])).unwrap();
+        let cli = Cli::try_parse_from(&expanded).unwrap();
+
+        assert_eq!(cli.verbose, 2);
+        assert!(matches!(cli.command, Command::Events { limit: 5, .. }));
+        assert!(
+            expanded.iter().position(|arg| arg == "-vv")
+                < expanded.iter().position(|arg| arg == "events")
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn spec_rejects_unknown_fields() {
+        let path = write_spec("unknown.json", r#"{"command": "events", "limt": 5}"#);
+
+        let err = expand_args(args(&["ddq", "--spec", &path])).unwrap_err();
+
+        assert!(err.to_string().contains("Unknown field `limt`"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
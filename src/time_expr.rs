@@ -1,18 +1,81 @@
 use anyhow::{Result, anyhow};
-use chrono::{DateTime, Duration, Utc};
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, Datelike, Duration, NaiveDate, SecondsFormat, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde_json::{Value, json};
 
-pub fn parse_to_unix(expr: &str, now: DateTime<Utc>) -> Result<i64> {
+/// How rewritten output timestamps are written (`--timestamp-format`).
+#[derive(Clone, Debug, PartialEq)]
+pub enum TimestampFormat {
+    UnixSeconds,
+    UnixMillis,
+    Rfc3339,
+    /// A chrono strftime pattern, e.g. `%Y-%m-%d %H:%M`.
+    Custom(String),
+}
+
+pub fn parse_timestamp_format(spec: &str) -> Result<TimestampFormat> {
+    match spec.trim() {
+        "unix-s" => Ok(TimestampFormat::UnixSeconds),
+        "unix-ms" => Ok(TimestampFormat::UnixMillis),
+        "rfc3339" => Ok(TimestampFormat::Rfc3339),
+        other => {
+            let pattern = other.strip_prefix("custom:").ok_or_else(|| {
+                anyhow!(
+                    "Unknown timestamp format `{other}`. Use unix-s, unix-ms, rfc3339, or custom:<strftime>."
+                )
+            })?;
+            // Formatting an invalid pattern panics, so reject it up front.
+            if pattern.is_empty()
+                || StrftimeItems::new(pattern).any(|item| matches!(item, Item::Error))
+            {
+                return Err(anyhow!("Invalid strftime pattern `{pattern}`."));
+            }
+            Ok(TimestampFormat::Custom(pattern.to_string()))
+        }
+    }
+}
+
+/// Writes `instant` in `format`: unix formats as numbers, the others as strings.
+pub fn format_timestamp<Z: TimeZone>(instant: &DateTime<Z>, format: &TimestampFormat) -> Value
+where
+    Z::Offset: std::fmt::Display,
+{
+    match format {
+        TimestampFormat::UnixSeconds => json!(instant.timestamp()),
+        TimestampFormat::UnixMillis => json!(instant.timestamp_millis()),
+        TimestampFormat::Rfc3339 => json!(instant.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+        TimestampFormat::Custom(pattern) => json!(instant.format(pattern).to_string()),
+    }
+}
+
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.trim()
+        .parse::<Tz>()
+        .map_err(|_| anyhow!("Unknown timezone `{name}`. Use an IANA name like Europe/Berlin."))
+}
+
+pub fn parse_to_unix(expr: &str, now: DateTime<Utc>, tz: Tz) -> Result<i64> {
     let trimmed = expr.trim();
     if trimmed == "now" {
         return Ok(now.timestamp());
     }
 
-    if let Ok(unix_seconds) = trimmed.parse::<i64>() {
-        return Ok(unix_seconds);
+    if let Some(anchor) = resolve_anchor(trimmed, now, tz) {
+        return Ok(anchor.timestamp());
+    }
+
+    if let Ok(epoch) = trimmed.parse::<i64>() {
+        // 1e12 seconds is more than 30,000 years away, while 1e12 milliseconds is
+        // 2001-09-09; anything that large (13+ digits) must be milliseconds.
+        if epoch.abs() >= MILLIS_EPOCH_THRESHOLD {
+            return Ok(epoch.div_euclid(1000));
+        }
+        return Ok(epoch);
     }
 
-    if let Some(offset) = trimmed.strip_prefix("now-") {
-        return parse_relative(offset, now);
+    if let Some(offset) = relative_offset(trimmed) {
+        return Ok((now + offset?).timestamp());
     }
 
     let dt = chrono::DateTime::parse_from_rfc3339(trimmed)
@@ -20,30 +83,379 @@ pub fn parse_to_unix(expr: &str, now: DateTime<Utc>) -> Result<i64> {
     Ok(dt.with_timezone(&Utc).timestamp())
 }
 
-fn parse_relative(offset: &str, now: DateTime<Utc>) -> Result<i64> {
-    if offset.len() < 2 {
+/// Bare integers at or above this are read as unix milliseconds.
+const MILLIS_EPOCH_THRESHOLD: i64 = 1_000_000_000_000;
+
+/// Smallest bare integer `--strict-timeparse` accepts as unix seconds
+/// (2000-01-01T00:00:00Z); anything lower is almost always a missing unit.
+const STRICT_MIN_UNIX_SECONDS: i64 = 946_684_800;
+
+/// Rejects expressions that parse but are likely mistakes: bare integers before
+/// 2000 and relative offsets without a unit (`now-15`, `now+15`).
+pub fn check_strict(expr: &str) -> Result<()> {
+    let trimmed = expr.trim();
+    if let Ok(unix_seconds) = trimmed.parse::<i64>()
+        && unix_seconds < STRICT_MIN_UNIX_SECONDS
+    {
+        return Err(anyhow!(
+            "Time `{trimmed}` is before 2000-01-01 as unix seconds; use e.g. now-{trimmed}m for a relative time."
+        ));
+    }
+    if let Some(offset) = trimmed
+        .strip_prefix("now-")
+        .or_else(|| trimmed.strip_prefix("now+"))
+        && offset.bytes().all(|byte| byte.is_ascii_digit())
+    {
         return Err(anyhow!(
-            "Invalid relative time `{offset}`. Expected e.g. now-15m."
+            "Relative time `{trimmed}` needs an explicit unit (s, m, h, d, w)."
         ));
     }
+    Ok(())
+}
 
-    let (value, unit) = offset.split_at(offset.len() - 1);
-    let quantity = value
-        .parse::<i64>()
-        .map_err(|_| anyhow!("Invalid relative duration quantity `{value}`"))?;
+/// The `from`/`to` of a `window` long (e.g. `10m`) centered on the `anchor` time.
+pub fn centered_window(
+    anchor: &str,
+    window: &str,
+    now: DateTime<Utc>,
+    tz: Tz,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let anchor_unix =
+        parse_to_unix(anchor, now, tz).map_err(|err| anyhow!("Invalid --around: {err}"))?;
+    let anchor = DateTime::from_timestamp(anchor_unix, 0)
+        .ok_or_else(|| anyhow!("--around `{anchor}` is out of range."))?;
+    let window = window.trim();
+    let half = relative_duration(window, window)? / 2;
+    Ok((anchor - half, anchor + half))
+}
 
-    let duration = match unit {
-        "s" => Duration::seconds(quantity),
-        "m" => Duration::minutes(quantity),
-        "h" => Duration::hours(quantity),
-        "d" => Duration::days(quantity),
-        "w" => Duration::weeks(quantity),
-        _ => {
+/// Widens the unix window `from`..`to` to multiples of `window` (e.g. `5m`):
+/// `from` rounds down and `to` rounds up, so metric buckets fall on round times.
+pub fn align_window(from: i64, to: i64, window: &str) -> Result<(i64, i64)> {
+    let window = window.trim();
+    let step = relative_duration(window, window)
+        .map_err(|err| anyhow!("Invalid --align-window: {err}"))?
+        .num_seconds();
+    if step <= 0 {
+        return Err(anyhow!(
+            "Invalid --align-window `{window}`: it must be at least one second."
+        ));
+    }
+    let from = from.div_euclid(step) * step;
+    let to = to + (step - to.rem_euclid(step)) % step;
+    Ok((from, to))
+}
+
+/// The signed offset of a `now-<duration>` (past) or `now+<duration>` (future)
+/// expression; `None` for anything else.
+fn relative_offset(expr: &str) -> Option<Result<Duration>> {
+    if let Some(offset) = expr.strip_prefix("now-") {
+        return Some(relative_duration(expr, offset).map(|duration| -duration));
+    }
+    expr.strip_prefix("now+")
+        .map(|offset| relative_duration(expr, offset))
+}
+
+/// Sums the `offset` of relative time `expr`, made of one or more
+/// `<number><unit>` segments, e.g. `15m`, `1h30m` or `1w3d12h`.
+fn relative_duration(expr: &str, offset: &str) -> Result<Duration> {
+    if offset.is_empty() {
+        return Err(anyhow!(
+            "Invalid relative time `{expr}`. Expected e.g. now-15m or now-1h30m."
+        ));
+    }
+
+    let mut total = Duration::zero();
+    let mut rest = offset;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|ch: char| !ch.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (value, tail) = rest.split_at(digits);
+        let mut chars = tail.chars();
+        let Some(unit) = chars.next() else {
+            return Err(anyhow!(
+                "Missing unit after `{value}` in relative time `{expr}`. Use one of s,m,h,d,w."
+            ));
+        };
+        if value.is_empty() {
             return Err(anyhow!(
-                "Invalid relative duration unit `{unit}`. Use one of s,m,h,d,w."
+                "Missing quantity before `{unit}` in relative time `{expr}`."
             ));
         }
+        let quantity = value
+            .parse::<i64>()
+            .map_err(|_| anyhow!("Invalid relative duration quantity `{value}`"))?;
+
+        total += match unit {
+            's' => Duration::seconds(quantity),
+            'm' => Duration::minutes(quantity),
+            'h' => Duration::hours(quantity),
+            'd' => Duration::days(quantity),
+            'w' => Duration::weeks(quantity),
+            _ => {
+                return Err(anyhow!(
+                    "Invalid relative duration unit `{unit}`. Use one of s,m,h,d,w."
+                ));
+            }
+        };
+        rest = chars.as_str();
+    }
+    Ok(total)
+}
+
+/// Resolves a time expression for endpoints that accept Datadog's own date math
+/// (logs, events): named anchors become RFC3339, everything else passes through.
+pub fn resolve_for_api(expr: &str, now: DateTime<Utc>, tz: Tz) -> String {
+    let trimmed = expr.trim();
+    if let Some(anchor) = resolve_anchor(trimmed, now, tz) {
+        return anchor.to_rfc3339();
+    }
+    // Datadog's date math takes a single unit and only looks back; compound and
+    // future offsets are resolved here.
+    let needs_resolving = match trimmed.strip_prefix("now-") {
+        Some(offset) => is_compound(offset),
+        None => trimmed.starts_with("now+"),
     };
+    if needs_resolving && let Some(Ok(offset)) = relative_offset(trimmed) {
+        return (now + offset).to_rfc3339();
+    }
+    expr.to_string()
+}
 
-    Ok((now - duration).timestamp())
+fn is_compound(offset: &str) -> bool {
+    offset
+        .chars()
+        .filter(|ch| !ch.is_ascii_digit())
+        .nth(1)
+        .is_some()
+}
+
+/// Named day boundaries in `tz`: `today` and `yesterday` are local midnights,
+/// `this-week` is Monday's midnight and `this-month` the first day's midnight.
+fn resolve_anchor(expr: &str, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+    let today = now.with_timezone(&tz).date_naive();
+    let date = match expr {
+        "today" => today,
+        "yesterday" => today.pred_opt()?,
+        "this-week" => today - Duration::days(i64::from(today.weekday().num_days_from_monday())),
+        "this-month" => today.with_day(1)?,
+        _ => return None,
+    };
+    Some(local_midnight(date, tz))
+}
+
+fn local_midnight(date: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    let midnight = date.and_hms_opt(0, 0, 0).expect("midnight is a valid time");
+    // Some zones skip midnight on DST transitions; use the first hour that exists.
+    (0..3)
+        .find_map(|hour| {
+            tz.from_local_datetime(&(midnight + Duration::hours(hour)))
+                .earliest()
+        })
+        .map(|local| local.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&midnight))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, Utc};
+    use chrono_tz::Tz;
+
+    use super::{
+        TimestampFormat, align_window, centered_window, check_strict, format_timestamp,
+        parse_timestamp_format, parse_timezone, parse_to_unix, resolve_for_api,
+    };
+
+    fn at(rfc3339: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(rfc3339)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn named_anchors_use_local_day_boundaries() {
+        // Thursday 2024-03-14 22:30 in New York (EDT, UTC-4).
+        let now = at("2024-03-15T02:30:00Z");
+        let tz = parse_timezone("America/New_York").unwrap();
+
+        let cases = [
+            ("today", "2024-03-14T04:00:00Z"),
+            ("yesterday", "2024-03-13T04:00:00Z"),
+            ("this-week", "2024-03-11T04:00:00Z"),
+            // March 1st was still EST (UTC-5).
+            ("this-month", "2024-03-01T05:00:00Z"),
+        ];
+        for (expr, expected) in cases {
+            assert_eq!(
+                parse_to_unix(expr, now, tz).unwrap(),
+                at(expected).timestamp(),
+                "{expr}"
+            );
+        }
+    }
+
+    #[test]
+    fn anchors_default_to_utc_and_pass_through_other_expressions() {
+        let now = at("2024-03-15T02:30:00Z");
+
+        assert_eq!(
+            parse_to_unix("today", now, Tz::UTC).unwrap(),
+            at("2024-03-15T00:00:00Z").timestamp()
+        );
+        assert_eq!(
+            resolve_for_api("yesterday", now, Tz::UTC),
+            "2024-03-14T00:00:00+00:00"
+        );
+        assert_eq!(resolve_for_api("now-15m", now, Tz::UTC), "now-15m");
+        assert!(parse_timezone("Mars/Olympus").is_err());
+    }
+
+    #[test]
+    fn strict_mode_rejects_small_integers_and_unitless_offsets() {
+        let now = at("2024-03-15T02:30:00Z");
+        assert_eq!(parse_to_unix("15", now, chrono_tz::UTC).unwrap(), 15);
+        assert!(check_strict("15").is_err());
+        assert!(check_strict("now-15").is_err());
+
+        for expr in [
+            "1700000000",
+            "now-15m",
+            "now",
+            "today",
+            "2024-03-15T02:30:00Z",
+        ] {
+            assert!(check_strict(expr).is_ok(), "{expr}");
+        }
+    }
+
+    #[test]
+    fn compound_relative_offsets_sum_their_segments() {
+        let now = at("2024-03-15T02:30:00Z");
+        let cases = [
+            ("now-15m", 15 * 60),
+            ("now-1h30m", 90 * 60),
+            ("now-2d6h", (2 * 24 + 6) * 3600),
+            ("now-1w3d12h", ((7 + 3) * 24 + 12) * 3600),
+            ("now-1m30s", 90),
+        ];
+        for (expr, seconds_ago) in cases {
+            assert_eq!(
+                parse_to_unix(expr, now, chrono_tz::UTC).unwrap(),
+                now.timestamp() - seconds_ago,
+                "{expr}"
+            );
+        }
+
+        let errors = [
+            ("now-1h30", "Missing unit after `30`"),
+            ("now-h", "Missing quantity before `h`"),
+            ("now-1h-30m", "Missing quantity before `-`"),
+            ("now-2y", "Invalid relative duration unit `y`"),
+            ("now-", "Invalid relative time `now-`"),
+        ];
+        for (expr, message) in errors {
+            let err = parse_to_unix(expr, now, chrono_tz::UTC).unwrap_err();
+            assert!(err.to_string().contains(message), "{expr}: {err}");
+        }
+
+        assert_eq!(
+            resolve_for_api("now-1h30m", now, chrono_tz::UTC),
+            "2024-03-15T01:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn future_offsets_add_to_now() {
+        let now = at("2024-03-15T02:30:00Z");
+
+        let in_an_hour = parse_to_unix("now+1h", now, chrono_tz::UTC).unwrap();
+        assert_eq!(in_an_hour, now.timestamp() + 3600);
+        assert_eq!(
+            parse_to_unix("now+1h30m", now, chrono_tz::UTC).unwrap(),
+            now.timestamp() + 5400
+        );
+        assert!(in_an_hour > parse_to_unix("now-15m", now, chrono_tz::UTC).unwrap());
+        assert!(parse_to_unix("now+", now, chrono_tz::UTC).is_err());
+        assert!(check_strict("now+15").is_err());
+
+        assert_eq!(
+            resolve_for_api("now+15m", now, chrono_tz::UTC),
+            "2024-03-15T02:45:00+00:00"
+        );
+    }
+
+    #[test]
+    fn millisecond_epochs_are_detected() {
+        let now = at("2024-03-15T02:30:00Z");
+
+        let seconds = parse_to_unix("1700000000", now, chrono_tz::UTC).unwrap();
+        let millis = parse_to_unix("1700000000123", now, chrono_tz::UTC).unwrap();
+
+        assert_eq!(seconds, 1_700_000_000);
+        assert_eq!(millis, seconds);
+    }
+
+    #[test]
+    fn align_window_rounds_outward_to_the_boundary() {
+        let from = at("2024-03-01T12:03:20Z").timestamp();
+        let to = at("2024-03-01T12:17:45Z").timestamp();
+
+        assert_eq!(
+            align_window(from, to, "5m").unwrap(),
+            (
+                at("2024-03-01T12:00:00Z").timestamp(),
+                at("2024-03-01T12:20:00Z").timestamp()
+            )
+        );
+        let aligned = at("2024-03-01T12:00:00Z").timestamp();
+        assert_eq!(
+            align_window(aligned, aligned + 3_600, "1h").unwrap(),
+            (aligned, aligned + 3_600)
+        );
+        assert!(align_window(from, to, "5").is_err());
+        assert!(align_window(from, to, "0m").is_err());
+    }
+
+    #[test]
+    fn centered_window_spans_the_window_around_the_anchor() {
+        let now = at("2024-03-15T02:30:00Z");
+
+        let (from, to) =
+            centered_window("2024-03-01T12:00:00Z", "10m", now, chrono_tz::UTC).unwrap();
+
+        assert_eq!(from, at("2024-03-01T11:55:00Z"));
+        assert_eq!(to, at("2024-03-01T12:05:00Z"));
+        assert_eq!(
+            centered_window("1709294400", "10m", now, chrono_tz::UTC).unwrap(),
+            (from, to)
+        );
+        assert!(centered_window("noon", "10m", now, chrono_tz::UTC).is_err());
+        assert!(centered_window("1709294400", "10", now, chrono_tz::UTC).is_err());
+    }
+
+    #[test]
+    fn timestamp_formats_render_an_instant() {
+        let instant = at("2024-05-01T12:30:45.250Z");
+        let render =
+            |spec: &str| format_timestamp(&instant, &parse_timestamp_format(spec).unwrap());
+
+        assert_eq!(render("unix-s"), serde_json::json!(1_714_566_645));
+        assert_eq!(render("unix-ms"), serde_json::json!(1_714_566_645_250_i64));
+        assert_eq!(render("rfc3339"), "2024-05-01T12:30:45.250Z");
+        assert_eq!(render("custom:%Y-%m-%d %H:%M"), "2024-05-01 12:30");
+
+        let berlin = instant.with_timezone(&"Europe/Berlin".parse::<Tz>().unwrap());
+        assert_eq!(
+            format_timestamp(&berlin, &TimestampFormat::Custom("%H:%M %z".to_string())),
+            "14:30 +0200"
+        );
+    }
+
+    #[test]
+    fn timestamp_format_rejects_unknown_specs() {
+        assert!(parse_timestamp_format("iso").is_err());
+        assert!(parse_timestamp_format("custom:").is_err());
+        assert!(parse_timestamp_format("custom:%Q").is_err());
+    }
 }
@@ -0,0 +1,145 @@
+use std::sync::Arc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use rustls::client::WebPkiServerVerifier;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::pki_types::{CertificateDer, ServerName, UnixTime};
+use rustls::{ClientConfig, DigitallySignedStruct, RootCertStore, SignatureScheme};
+use sha2::{Digest, Sha256};
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// A SHA-256 hash of a certificate's DER-encoded SubjectPublicKeyInfo.
+pub type SpkiPin = [u8; 32];
+
+/// Decodes a base64 SPKI SHA-256 pin as given to `--pin-sha256`.
+pub fn parse_pin(pin: &str) -> Result<SpkiPin, String> {
+    STANDARD
+        .decode(pin.trim())
+        .ok()
+        .and_then(|bytes| SpkiPin::try_from(bytes).ok())
+        .ok_or_else(|| {
+            format!("Invalid --pin-sha256 `{pin}`. Expected a base64 SHA-256 hash (44 characters).")
+        })
+}
+
+/// Builds an HTTP client that, on top of normal certificate validation, requires
+/// one certificate in the server's chain to match a pinned SPKI hash.
+pub fn pinned_http_client(pins: &[SpkiPin]) -> reqwest::Client {
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+    let roots = RootCertStore {
+        roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+    };
+    let inner = WebPkiServerVerifier::builder_with_provider(Arc::new(roots), provider.clone())
+        .build()
+        .expect("bundled root certificates are valid");
+    let mut config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .expect("default TLS versions are supported")
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinnedVerifier {
+            inner,
+            pins: pins.to_vec(),
+        }))
+        .with_no_client_auth();
+    // A preconfigured TLS config is used as-is, so offer HTTP/2 here as reqwest
+    // does for its own.
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+    reqwest::Client::builder()
+        .use_preconfigured_tls(config)
+        .build()
+        .expect("TLS backend can be initialized")
+}
+
+fn spki_sha256(certificate: &[u8]) -> Option<SpkiPin> {
+    let (_, parsed) = X509Certificate::from_der(certificate).ok()?;
+    Some(Sha256::digest(parsed.tbs_certificate.subject_pki.raw).into())
+}
+
+fn matches_pin(chain: &[&[u8]], pins: &[SpkiPin]) -> bool {
+    chain
+        .iter()
+        .filter_map(|certificate| spki_sha256(certificate))
+        .any(|hash| pins.contains(&hash))
+}
+
+#[derive(Debug)]
+struct PinnedVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    pins: Vec<SpkiPin>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let chain: Vec<&[u8]> = std::iter::once(end_entity)
+            .chain(intermediates)
+            .map(|certificate| certificate.as_ref())
+            .collect();
+        if !matches_pin(&chain, &self.pins) {
+            return Err(rustls::Error::General(
+                "server certificate does not match any --pin-sha256".to_string(),
+            ));
+        }
+        self.inner
+            .verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now)
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use base64::engine::general_purpose::STANDARD;
+
+    use super::{matches_pin, parse_pin};
+
+    /// Self-signed P-256 certificate for CN=pin.test.
+    const CERT_DER_BASE64: &str = "MIIBfjCCASOgAwIBAgIUeDdq9QdkHFv5kF38qfjwqDq1A88wCgYIKoZIzj0EAwIwEzERMA8GA1UEAwwIcGluLnRlc3QwIBcNMjYxMDE1MDYyMjIzWhgPMjEyNjA5MjEwNjIyMjNaMBMxETAPBgNVBAMMCHBpbi50ZXN0MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEWbP58H9B7wcnZoQ/q66ruQPkkEuwEnSuQguqDfnjf8KlPAvH1NlrKfu/NUBVC2RKE71usQB46zf1XjJ406R/daNTMFEwHQYDVR0OBBYEFDHY3k+3tSNi8VoImZsJ0k465Tl5MB8GA1UdIwQYMBaAFDHY3k+3tSNi8VoImZsJ0k465Tl5MA8GA1UdEwEB/wQFMAMBAf8wCgYIKoZIzj0EAwIDSQAwRgIhAORxnpZf2Rg73PeMM/T/7cEp+jErpDP7RTNmRq6ahbnrAiEAs3ScM0/fC/B2Q9oJ6f162665gjRbQPHFabOaGJmmH0s=";
+    /// `openssl x509 -pubkey -noout | openssl pkey -pubin -outform der | openssl dgst -sha256 -binary | base64`
+    const CERT_PIN: &str = "bgkxkIHSKbThAxpmyStsNaq0m+7BBRRKFgI/bVXUJsU=";
+
+    #[test]
+    fn pin_matches_known_certificate_and_rejects_others() {
+        let certificate = STANDARD.decode(CERT_DER_BASE64).unwrap();
+        let chain = [certificate.as_slice()];
+
+        assert!(matches_pin(&chain, &[parse_pin(CERT_PIN).unwrap()]));
+        assert!(!matches_pin(&chain, &[[0u8; 32]]));
+    }
+
+    #[test]
+    fn parse_pin_requires_a_sha256_length_hash() {
+        assert!(parse_pin(CERT_PIN).is_ok());
+        assert!(parse_pin("c2hvcnQ=").is_err());
+        assert!(parse_pin("not base64!").is_err());
+    }
+}
@@ -0,0 +1,26 @@
+use std::sync::Mutex;
+
+use serde_json::json;
+
+use crate::app_error::AppError;
+
+/// Warnings emitted during a run. Each is printed to stderr as it happens and
+/// kept so `--fail-on-warnings` can fail the run at the end.
+#[derive(Default)]
+pub struct Warnings(Mutex<Vec<String>>);
+
+impl Warnings {
+    pub fn warn(&self, message: &str) {
+        eprintln!("{}", json!({ "warning": { "message": message } }));
+        self.0
+            .lock()
+            .expect("warnings lock poisoned")
+            .push(message.to_string());
+    }
+
+    pub fn failure(&self, fail_on_warnings: bool) -> Option<AppError> {
+        let warnings = self.0.lock().expect("warnings lock poisoned");
+        (fail_on_warnings && !warnings.is_empty())
+            .then(|| AppError::WarningsPresent(warnings.clone()))
+    }
+}